@@ -46,11 +46,14 @@ pub fn find_near_exe() -> Result<Option<FfmpegBinaries>> {
 
 pub fn resolve_ffmpeg() -> Result<Option<(FfmpegBinaries, FfmpegSource)>> {
     if let Some(bins) = find_near_exe()? {
+        log::info!("ffmpeg next to the exe: {}", bins.ffmpeg.display());
         return Ok(Some((bins, FfmpegSource::NearExe)));
     }
     if let Some(bins) = find_installed()? {
+        log::info!("ffmpeg installed by setup: {}", bins.ffmpeg.display());
         return Ok(Some((bins, FfmpegSource::Bundled)));
     }
+    log::info!("ffmpeg not found");
     Ok(None)
 }
 
@@ -1,13 +1,35 @@
 use std::path::PathBuf;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-fn parse_supported_input(s: &str) -> std::result::Result<PathBuf, String> {
-    let path = PathBuf::from(s);
-    if crate::exec::input::is_supported_video(&path) {
-        Ok(path)
-    } else {
-        Err("unsupported input extension".to_string())
+fn parse_preset(s: &str) -> std::result::Result<crate::presets::Preset, String> {
+    crate::presets::Preset::parse(s)
+        .ok_or_else(|| "expected quality, balanced, speed, remux, auto, gif or webp".to_string())
+}
+
+fn parse_dither(s: &str) -> std::result::Result<crate::exec::gif::Dither, String> {
+    crate::exec::gif::Dither::parse(s)
+        .ok_or_else(|| "expected bayer, floyd_steinberg, sierra or none".to_string())
+}
+
+fn parse_size(s: &str) -> std::result::Result<u64, String> {
+    crate::exec::report::parse_size(s).ok_or_else(|| "expected a size like 8M or 500k".to_string())
+}
+
+fn parse_split_duration(s: &str) -> std::result::Result<u32, String> {
+    crate::exec::split::parse_duration(s)
+        .ok_or_else(|| "expected a length like 10m, 90s or 1h30m".to_string())
+}
+
+fn parse_container(s: &str) -> std::result::Result<crate::exec::container::Container, String> {
+    crate::exec::container::Container::parse(s)
+        .ok_or_else(|| "expected mp4, mkv, webm or mov".to_string())
+}
+
+fn parse_vmaf_target(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v > 0.0 && v <= 100.0 => Ok(v),
+        _ => Err("expected a vmaf score between 0 and 100".to_string()),
     }
 }
 
@@ -20,7 +42,7 @@ fn parse_supported_input(s: &str) -> std::result::Result<PathBuf, String> {
 )]
 pub struct Cli {
     /// input files to compress (when no subcommand is used)
-    #[arg(value_name = "INPUT", value_parser = parse_supported_input)]
+    #[arg(value_name = "INPUT")]
     pub inputs: Vec<PathBuf>,
 
     /// use gpu encoder for cli compression, overriding options.toml
@@ -31,10 +53,115 @@ pub struct Cli {
     #[arg(long, global = true, conflicts_with = "gpu")]
     pub cpu: bool,
 
+    /// video encoder: auto, cpu, gpu, nvenc, qsv, amf, vaapi or an ffmpeg name like hevc_qsv
+    #[arg(
+        long,
+        global = true,
+        value_name = "ENCODER",
+        conflicts_with_all = ["gpu", "cpu"],
+        value_parser = crate::presets::encoder::EncoderPref::parse
+    )]
+    pub encoder: Option<crate::presets::encoder::EncoderPref>,
+
+    /// only take inputs with a known video extension instead of probing the others
+    #[arg(long, global = true)]
+    pub extensions_only: bool,
+
+    /// output format: mp4, mkv, webm or mov, overriding options.toml
+    #[arg(long, global = true, value_name = "FORMAT", value_parser = parse_container)]
+    pub container: Option<crate::exec::container::Container>,
+
+    /// cut each output into numbered parts of at most this size (e.g. 25M), at keyframes
+    #[arg(long, global = true, value_name = "SIZE", value_parser = parse_size, conflicts_with = "split_duration")]
+    pub split_size: Option<u64>,
+
+    /// cut each output into numbered parts of at most this length (e.g. 10m), at keyframes
+    #[arg(long, global = true, value_name = "LENGTH", value_parser = parse_split_duration)]
+    pub split_duration: Option<u32>,
+
+    /// encode this many files at once (1-8), overriding options.toml
+    #[arg(long, short = 'j', global = true, value_name = "N", value_parser = clap::value_parser!(u8).range(1..=i64::from(crate::exec::pool::MAX_JOBS)))]
+    pub jobs: Option<u8>,
+
+    /// when a gpu encoder fails to start on a file, encode that file on the cpu instead
+    #[arg(long, global = true)]
+    pub cpu_fallback: bool,
+
+    /// decode on the gpu too when a hardware encoder is used, overriding options.toml
+    #[arg(long, global = true, conflicts_with = "no_hw_decode")]
+    pub hw_decode: bool,
+
+    /// always decode on the cpu, overriding options.toml
+    #[arg(long, global = true)]
+    pub no_hw_decode: bool,
+
+    /// carry over from the input (comma-separated), overriding options.toml
+    #[arg(long, global = true, value_name = "WHAT", value_delimiter = ',')]
+    pub preserve: Vec<PreserveArg>,
+
+    /// drop from the output (comma-separated), overriding options.toml
+    #[arg(long, global = true, value_name = "WHAT", value_delimiter = ',')]
+    pub no_preserve: Vec<PreserveArg>,
+
+    /// pick the crf per file that reaches this vmaf score (0-100) instead of the preset's
+    #[arg(long, global = true, value_name = "SCORE", value_parser = parse_vmaf_target)]
+    pub target_vmaf: Option<f64>,
+
+    /// score each output against its input (ssim, psnr and vmaf when available)
+    #[arg(long, global = true)]
+    pub measure: bool,
+
+    /// flag outputs below this score, e.g. `vmaf=93` or `ssim=0.97`; implies --measure
+    #[arg(long, global = true, value_name = "METRIC=SCORE", value_parser = crate::exec::metrics::QualityThreshold::parse)]
+    pub min_quality: Option<crate::exec::metrics::QualityThreshold>,
+
+    /// print the summary as json on stdout; progress goes to stderr
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// extra ffmpeg output options for every file, e.g. "-tune film"; also taken after `--`
+    #[arg(long, global = true, value_name = "ARGS", allow_hyphen_values = true)]
+    pub ffmpeg_args: Option<String>,
+
+    /// extra ffmpeg input options for every file, placed before `-i`
+    #[arg(long, global = true, value_name = "ARGS", allow_hyphen_values = true)]
+    pub ffmpeg_input_args: Option<String>,
+
+    /// ffmpeg output options after a trailing `--`
+    #[arg(last = true, value_name = "FFMPEG_ARGS")]
+    pub ffmpeg_tail: Vec<String>,
+
+    /// print the ffmpeg command for each file instead of running it
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// append ffmpeg's full output for every file here instead of the logs folder
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// log more to stderr (the tui logs to tinythis.log): -v info, -vv debug, -vvv trace
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// log only errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
     #[command(subcommand)]
     pub command: Option<Command>,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum PreserveArg {
+    All,
+    Metadata,
+    Chapters,
+    CreationTime,
+    Rotation,
+    Subtitles,
+    Timestamps,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
     /// compress using the balanced preset
@@ -46,6 +173,30 @@ pub enum Command {
     /// compress using the speed preset
     Speed(CompressArgs),
 
+    /// copy streams into the output container, re-encoding only what it can't hold
+    Remux(CompressArgs),
+
+    /// pick remux/quality/balanced/speed per file, or skip files that are already compact
+    Auto(AutoArgs),
+
+    /// turn clips into animated gifs
+    Gif(GifArgs),
+
+    /// turn clips into animated webps
+    Webp(GifArgs),
+
+    /// encode a sample with every preset and encoder and compare size, speed and quality
+    Bench(BenchArgs),
+
+    /// check ffmpeg, encoders, options and the install, and suggest fixes
+    Doctor(DoctorArgs),
+
+    /// finish the last batch that was interrupted, skipping files already done
+    Resume,
+
+    /// compress videos as they appear in a folder
+    Watch(WatchArgs),
+
     /// download and install ffmpeg assets and add tinythis to your PATH
     Setup(SetupCmd),
 
@@ -80,8 +231,81 @@ pub struct SetupPathArgs {}
 #[derive(Debug, Args)]
 pub struct CompressArgs {
     /// input files to compress
-    #[arg(value_name = "INPUT", required = true, value_parser = parse_supported_input)]
+    #[arg(value_name = "INPUT", required = true)]
     pub inputs: Vec<PathBuf>,
+
+    /// ffmpeg output options after a trailing `--`
+    #[arg(last = true, value_name = "FFMPEG_ARGS")]
+    pub ffmpeg_tail: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct BenchArgs {
+    /// sample file to benchmark with (a short clip keeps this quick)
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+    /// skip the update check (no network access)
+    #[arg(long)]
+    pub offline: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// folder to watch (not recursive)
+    #[arg(value_name = "DIR")]
+    pub dir: PathBuf,
+
+    /// preset for new files: quality, balanced, speed, remux or auto
+    #[arg(long, default_value = "balanced", value_parser = parse_preset)]
+    pub preset: crate::presets::Preset,
+
+    /// seconds a file's size must stay unchanged before it is compressed
+    #[arg(long, value_name = "SECS", default_value_t = 5)]
+    pub stable_secs: u64,
+
+    /// move originals into a `done` subfolder once they're handled
+    #[arg(long)]
+    pub move_done: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct AutoArgs {
+    #[command(flatten)]
+    pub compress: CompressArgs,
+
+    /// skip files whose expected size reduction is below this percentage, overriding options.toml
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub min_savings: Option<u8>,
+}
+
+#[derive(Debug, Args)]
+pub struct GifArgs {
+    #[command(flatten)]
+    pub compress: CompressArgs,
+
+    /// frames per second, overriding options.toml
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=i64::from(crate::exec::gif::MAX_FPS)))]
+    pub fps: Option<u32>,
+
+    /// widest the animation gets in pixels, overriding options.toml
+    #[arg(long, value_name = "PX", value_parser = clap::value_parser!(u32).range(i64::from(crate::exec::gif::SMALLEST_WIDTH)..))]
+    pub width: Option<u32>,
+
+    /// times the animation plays (0 loops forever), overriding options.toml
+    #[arg(long, value_name = "N")]
+    pub plays: Option<u32>,
+
+    /// bayer, floyd_steinberg, sierra or none, overriding options.toml
+    #[arg(long, value_name = "METHOD", value_parser = parse_dither)]
+    pub dither: Option<crate::exec::gif::Dither>,
+
+    /// lower the fps, then the width, until each output fits in this size (e.g. 8M)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_size: Option<u64>,
 }
 
 #[derive(Debug, Args)]
@@ -172,6 +396,256 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parses_target_vmaf_in_range() {
+        let cli = Cli::try_parse_from(["tinythis", "--target-vmaf", "93", "a.mp4"]).unwrap();
+        assert_eq!(cli.target_vmaf, Some(93.0));
+
+        let cli =
+            Cli::try_parse_from(["tinythis", "speed", "a.mp4", "--target-vmaf", "90.5"]).unwrap();
+        assert_eq!(cli.target_vmaf, Some(90.5));
+
+        assert!(Cli::try_parse_from(["tinythis", "--target-vmaf", "101", "a.mp4"]).is_err());
+    }
+
+    #[test]
+    fn parses_measure_flags() {
+        let cli = Cli::try_parse_from([
+            "tinythis",
+            "quality",
+            "--min-quality",
+            "vmaf=93",
+            "--json",
+            "a.mp4",
+        ])
+        .unwrap();
+        assert!(!cli.measure);
+        assert!(cli.json);
+        assert_eq!(
+            cli.min_quality.map(|t| t.to_string()).as_deref(),
+            Some("vmaf=93")
+        );
+
+        assert!(
+            Cli::try_parse_from(["tinythis", "--measure", "a.mp4"])
+                .unwrap()
+                .measure
+        );
+        assert!(Cli::try_parse_from(["tinythis", "--min-quality", "93", "a.mp4"]).is_err());
+    }
+
+    #[test]
+    fn parses_bench_subcommand() {
+        let cli = Cli::try_parse_from(["tinythis", "bench", "--cpu", "clip.mp4"]).unwrap();
+        assert!(cli.cpu);
+        match cli.command {
+            Some(Command::Bench(args)) => assert_eq!(args.input, PathBuf::from("clip.mp4")),
+            other => panic!("unexpected command: {other:?}"),
+        }
+        assert!(Cli::try_parse_from(["tinythis", "bench", "a.mp4", "b.mp4"]).is_err());
+    }
+
+    #[test]
+    fn parses_doctor_subcommand() {
+        let cli = Cli::try_parse_from(["tinythis", "doctor", "--offline", "--json"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Doctor(DoctorArgs { offline: true }))
+        ));
+    }
+
+    #[test]
+    fn parses_resume_subcommand() {
+        let cli = Cli::try_parse_from(["tinythis", "resume", "--gpu"]).unwrap();
+        assert!(cli.gpu);
+        assert!(matches!(cli.command, Some(Command::Resume)));
+        assert!(Cli::try_parse_from(["tinythis", "resume", "a.mp4"]).is_err());
+    }
+
+    #[test]
+    fn parses_watch_subcommand() {
+        let cli = Cli::try_parse_from(["tinythis", "watch", "C:\\in", "--move-done"]).unwrap();
+        match cli.command {
+            Some(Command::Watch(args)) => {
+                assert_eq!(args.dir, PathBuf::from("C:\\in"));
+                assert_eq!(args.preset, crate::presets::Preset::Balanced);
+                assert_eq!(args.stable_secs, 5);
+                assert!(args.move_done);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "tinythis",
+            "watch",
+            "in",
+            "--preset",
+            "speed",
+            "--stable-secs",
+            "30",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Watch(WatchArgs {
+                preset: crate::presets::Preset::Speed,
+                stable_secs: 30,
+                ..
+            }))
+        ));
+        assert!(Cli::try_parse_from(["tinythis", "watch", "in", "--preset", "fast"]).is_err());
+    }
+
+    #[test]
+    fn parses_log_file_anywhere() {
+        let cli = Cli::try_parse_from(["tinythis", "a.mp4", "--log-file", "run.log"]).unwrap();
+        assert_eq!(cli.log_file, Some(PathBuf::from("run.log")));
+
+        let cli = Cli::try_parse_from(["tinythis", "watch", "in", "--log-file", "w.log"]).unwrap();
+        assert_eq!(cli.log_file, Some(PathBuf::from("w.log")));
+
+        assert!(
+            Cli::try_parse_from(["tinythis"])
+                .unwrap()
+                .log_file
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn parses_extra_ffmpeg_args() {
+        let cli = Cli::try_parse_from([
+            "tinythis",
+            "a.mp4",
+            "--ffmpeg-args",
+            "-tune film",
+            "--",
+            "-x264-params",
+            "aq-mode=3",
+        ])
+        .unwrap();
+        assert_eq!(cli.inputs, vec![PathBuf::from("a.mp4")]);
+        assert_eq!(cli.ffmpeg_args.as_deref(), Some("-tune film"));
+        assert_eq!(cli.ffmpeg_tail, ["-x264-params", "aq-mode=3"]);
+
+        let cli = Cli::try_parse_from([
+            "tinythis",
+            "quality",
+            "a.mp4",
+            "--ffmpeg-input-args=-re",
+            "--",
+            "-an",
+        ])
+        .unwrap();
+        assert_eq!(cli.ffmpeg_input_args.as_deref(), Some("-re"));
+        match cli.command {
+            Some(Command::Quality(args)) => assert_eq!(args.ffmpeg_tail, ["-an"]),
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_dry_run_with_subcommands() {
+        let cli = Cli::try_parse_from(["tinythis", "--dry-run", "a.mp4"]).unwrap();
+        assert!(cli.dry_run);
+
+        let cli =
+            Cli::try_parse_from(["tinythis", "speed", "a.mp4", "--dry-run", "--json"]).unwrap();
+        assert!(cli.dry_run && cli.json);
+        assert!(matches!(cli.command, Some(Command::Speed(_))));
+    }
+
+    #[test]
+    fn parses_verbosity_flags() {
+        let cli = Cli::try_parse_from(["tinythis", "-vv", "a.mp4"]).unwrap();
+        assert_eq!(cli.verbose, 2);
+        assert!(!cli.quiet);
+
+        let cli = Cli::try_parse_from(["tinythis", "doctor", "-q"]).unwrap();
+        assert_eq!(cli.verbose, 0);
+        assert!(cli.quiet);
+
+        assert!(Cli::try_parse_from(["tinythis", "-v", "-q", "a.mp4"]).is_err());
+    }
+
+    #[test]
+    fn parses_remux_subcommand() {
+        let cli = Cli::try_parse_from(["tinythis", "remux", "a.mov"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Remux(_))));
+    }
+
+    #[test]
+    fn parses_auto_subcommand_with_threshold() {
+        let cli =
+            Cli::try_parse_from(["tinythis", "auto", "--min-savings", "30", "a.mov"]).unwrap();
+        match cli.command {
+            Some(Command::Auto(args)) => {
+                assert_eq!(args.min_savings, Some(30));
+                assert_eq!(args.compress.inputs, vec![PathBuf::from("a.mov")]);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        assert!(
+            Cli::try_parse_from(["tinythis", "auto", "--min-savings", "101", "a.mp4"]).is_err()
+        );
+    }
+
+    #[test]
+    fn parses_gif_and_webp_subcommands() {
+        let cli = Cli::try_parse_from([
+            "tinythis",
+            "gif",
+            "--fps",
+            "12",
+            "--dither",
+            "sierra",
+            "--max-size",
+            "8M",
+            "a.mp4",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Gif(args)) => {
+                assert_eq!(args.fps, Some(12));
+                assert_eq!(args.width, None);
+                assert_eq!(args.dither, Some(crate::exec::gif::Dither::Sierra));
+                assert_eq!(args.max_size, Some(8 << 20));
+                assert_eq!(args.compress.inputs, vec![PathBuf::from("a.mp4")]);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+        let cli = Cli::try_parse_from(["tinythis", "webp", "--plays", "1", "a.mp4"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Webp(ref a)) if a.plays == Some(1)));
+
+        assert!(Cli::try_parse_from(["tinythis", "gif", "--fps", "60", "a.mp4"]).is_err());
+        assert!(Cli::try_parse_from(["tinythis", "gif", "--width", "8", "a.mp4"]).is_err());
+        assert!(Cli::try_parse_from(["tinythis", "gif", "--max-size", "big", "a.mp4"]).is_err());
+    }
+
+    #[test]
+    fn parses_split_limits() {
+        let cli = Cli::try_parse_from(["tinythis", "--split-size", "25MB", "a.mp4"]).unwrap();
+        assert_eq!(cli.split_size, Some(25 << 20));
+        let cli =
+            Cli::try_parse_from(["tinythis", "speed", "a.mp4", "--split-duration", "10m"]).unwrap();
+        assert_eq!(cli.split_duration, Some(600));
+
+        assert!(
+            Cli::try_parse_from([
+                "tinythis",
+                "--split-size",
+                "25M",
+                "--split-duration",
+                "10m",
+                "a.mp4"
+            ])
+            .is_err()
+        );
+        assert!(Cli::try_parse_from(["tinythis", "--split-duration", "soon", "a.mp4"]).is_err());
+    }
+
     #[test]
     fn requires_inputs_for_preset_subcommands() {
         assert!(Cli::try_parse_from(["tinythis", "speed"]).is_err());
@@ -200,4 +674,48 @@ mod tests {
 
         assert!(Cli::try_parse_from(["tinythis", "--gpu", "--cpu", "a.mp4"]).is_err());
     }
+
+    #[test]
+    fn parses_encoder_choice() {
+        let cli = Cli::try_parse_from(["tinythis", "--encoder", "hevc_qsv", "a.mp4"]).unwrap();
+        assert_eq!(
+            cli.encoder.map(|e| e.to_string()).as_deref(),
+            Some("hevc_qsv")
+        );
+        // `auto` is also a subcommand, so the value has to be attached with `=`.
+        let cli = Cli::try_parse_from(["tinythis", "--encoder=auto", "a.mp4"]).unwrap();
+        assert_eq!(
+            cli.encoder,
+            Some(crate::presets::encoder::EncoderPref::Auto)
+        );
+        assert!(Cli::try_parse_from(["tinythis", "--encoder", "voodoo", "a.mp4"]).is_err());
+        assert!(Cli::try_parse_from(["tinythis", "--encoder", "qsv", "--cpu", "a.mp4"]).is_err());
+
+        let cli =
+            Cli::try_parse_from(["tinythis", "--hw-decode", "--cpu-fallback", "a.mp4"]).unwrap();
+        assert!(cli.hw_decode && cli.cpu_fallback);
+        assert!(
+            Cli::try_parse_from(["tinythis", "--hw-decode", "--no-hw-decode", "a.mp4"]).is_err()
+        );
+    }
+
+    #[test]
+    fn parses_preserve_lists() {
+        let cli = Cli::try_parse_from([
+            "tinythis",
+            "--preserve",
+            "subtitles,creation-time",
+            "--no-preserve",
+            "chapters",
+            "a.mp4",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.preserve,
+            vec![PreserveArg::Subtitles, PreserveArg::CreationTime]
+        );
+        assert_eq!(cli.no_preserve, vec![PreserveArg::Chapters]);
+
+        assert!(Cli::try_parse_from(["tinythis", "--preserve", "bogus", "a.mp4"]).is_err());
+    }
 }
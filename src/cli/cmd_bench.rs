@@ -0,0 +1,264 @@
+use std::path::Path;
+use std::time::Instant;
+
+use crate::error::Result;
+use crate::exec::container::Container;
+use crate::exec::extra_args::PresetArgs;
+use crate::exec::metrics::Scores;
+use crate::exec::probe::StreamKind;
+use crate::exec::report::format_bytes;
+use crate::json::Value;
+use crate::presets::Preset;
+use crate::presets::encoder::{Codec, Encoder};
+
+use super::Cli;
+use super::args::BenchArgs;
+
+const PRESETS: [Preset; 3] = [Preset::Quality, Preset::Balanced, Preset::Speed];
+
+#[derive(Debug, Clone)]
+struct BenchRow {
+    preset: Preset,
+    encoder: &'static str,
+    output_bytes: Option<u64>,
+    seconds: f64,
+    scores: Option<Scores>,
+    error: Option<String>,
+}
+
+pub fn run(cli: &Cli, args: &BenchArgs) -> Result<()> {
+    super::check_inputs(cli, std::slice::from_ref(&args.input))?;
+    let (bins, _) = super::ffmpeg_or_setup()?;
+    let ffmpeg = bins.ffmpeg.as_path();
+    let input = args.input.as_path();
+
+    let info = crate::exec::probe::probe(ffmpeg, input)?;
+    let input_bytes = std::fs::metadata(input)?.len();
+    let fps = info
+        .streams_of(StreamKind::Video)
+        .next()
+        .and_then(|v| v.fps);
+
+    // an explicit choice benches just that encoder; otherwise cpu and every working gpu.
+    let encoders: Vec<Encoder> = if cli.encoder.is_some() || cli.cpu || cli.gpu {
+        let opts = crate::options::load()?;
+        vec![super::resolve_encoder(
+            super::encoder_pref(cli, &opts),
+            opts.codec,
+        )?]
+    } else {
+        let caps = crate::exec::caps::detect(ffmpeg)?;
+        std::iter::once(Encoder::CPU)
+            .chain(Encoder::hardware(Codec::H264).filter(|e| caps.can_use(e.ffmpeg_name())))
+            .collect()
+    };
+    let with_vmaf = crate::exec::metrics::has_libvmaf(ffmpeg);
+
+    let say = |msg: String| {
+        if cli.json {
+            eprintln!("{msg}");
+        } else {
+            println!("{msg}");
+        }
+    };
+    say(format!(
+        "bench: {} ({})",
+        input.display(),
+        format_bytes(input_bytes)
+    ));
+    if !with_vmaf {
+        say("note: this ffmpeg build has no libvmaf; scoring with ssim/psnr only".to_string());
+    }
+
+    let dir = tempfile::Builder::new()
+        .prefix("tinythis_bench_")
+        .tempdir()?;
+
+    let mut rows = Vec::new();
+    for &encoder in &encoders {
+        for preset in PRESETS {
+            let name = encoder.ffmpeg_name();
+            say(format!("encoding {} ({name})", preset.as_str()));
+
+            let mut settings = super::resolve_settings(cli, preset)?;
+            settings.encoder = encoder;
+            // the bench compares the presets themselves, not a searched crf.
+            settings.target_vmaf = None;
+            settings.measure = false;
+            settings.preserve.timestamps = false;
+            // a bench row should time the encoder it names.
+            settings.cpu_fallback = false;
+            // mp4 holds every encoder the bench can compare.
+            settings.container = Container::Mp4;
+
+            let mut plan = crate::exec::compress::plan_file(
+                ffmpeg,
+                input,
+                &settings,
+                &PresetArgs::default(),
+                Some(info.clone()),
+            )?;
+            let out = dir.path().join(format!("{}_{name}.mp4", preset.as_str()));
+
+            let started = Instant::now();
+            let res = crate::exec::compress::compress_file(ffmpeg, input, &out, &mut plan, |p| {
+                super::print_progress(&mut std::io::stderr(), p, None)
+            });
+            let seconds = started.elapsed().as_secs_f64();
+            let res = res.and_then(|()| {
+                let bytes = std::fs::metadata(&out)?.len();
+                let scores =
+                    crate::exec::metrics::measure(ffmpeg, input, &out, fps, None, with_vmaf)?;
+                Ok((bytes, scores))
+            });
+            let _ = std::fs::remove_file(&out);
+
+            let mut row = BenchRow {
+                preset,
+                encoder: name,
+                output_bytes: None,
+                seconds,
+                scores: None,
+                error: None,
+            };
+            match res {
+                Ok((bytes, scores)) => {
+                    row.output_bytes = Some(bytes);
+                    row.scores = Some(scores);
+                }
+                Err(e) => {
+                    let msg = e.to_string();
+                    row.error = Some(msg.lines().next().unwrap_or_default().to_string());
+                }
+            }
+            rows.push(row);
+        }
+    }
+
+    if cli.json {
+        println!(
+            "{}",
+            bench_json(input, input_bytes, info.duration_us, &rows)
+        );
+    } else {
+        println!();
+        for line in format_table(&rows, input_bytes, info.duration_us) {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+fn format_table(rows: &[BenchRow], input_bytes: u64, duration_us: Option<u64>) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{:<9} {:<10} {:>10} {:>6} {:>9} {:>7} {:>7} {:>7}",
+        "preset", "encoder", "size", "ratio", "time (s)", "speed", "vmaf", "ssim"
+    )];
+
+    for r in rows {
+        let head = format!("{:<9} {:<10}", r.preset.as_str(), r.encoder);
+        let Some(bytes) = r.output_bytes else {
+            lines.push(format!(
+                "{head} failed: {}",
+                r.error.as_deref().unwrap_or("unknown error")
+            ));
+            continue;
+        };
+
+        let ratio = if input_bytes == 0 {
+            "n/a".to_string()
+        } else {
+            format!("{:.0}%", bytes as f64 / input_bytes as f64 * 100.0)
+        };
+        let speed = match duration_us {
+            Some(us) if r.seconds > 0.0 => format!("{:.1}x", us as f64 / 1e6 / r.seconds),
+            _ => "n/a".to_string(),
+        };
+        let score = |v: Option<f64>, decimals: usize| {
+            v.map(|v| format!("{v:.decimals$}"))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let scores = r.scores.unwrap_or_default();
+
+        lines.push(format!(
+            "{head} {:>10} {:>6} {:>9.1} {:>7} {:>7} {:>7}",
+            format_bytes(bytes),
+            ratio,
+            r.seconds,
+            speed,
+            score(scores.vmaf, 2),
+            score(scores.ssim, 4)
+        ));
+    }
+    lines
+}
+
+fn bench_json(
+    input: &Path,
+    input_bytes: u64,
+    duration_us: Option<u64>,
+    rows: &[BenchRow],
+) -> Value {
+    let rows = rows
+        .iter()
+        .map(|r| {
+            let scores = r.scores.unwrap_or_default();
+            Value::object([
+                ("preset", Value::from(r.preset.as_str())),
+                ("encoder", Value::from(r.encoder)),
+                ("output_bytes", Value::from(r.output_bytes)),
+                ("seconds", Value::from(r.seconds)),
+                ("vmaf", Value::from(scores.vmaf)),
+                ("ssim", Value::from(scores.ssim)),
+                ("psnr", Value::from(scores.psnr)),
+                ("error", Value::from(r.error.clone())),
+            ])
+        })
+        .collect();
+
+    Value::object([
+        ("input", Value::from(input.display().to_string())),
+        ("input_bytes", Value::from(input_bytes)),
+        ("duration_us", Value::from(duration_us)),
+        ("results", Value::Array(rows)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_lists_successful_and_failed_runs() {
+        let rows = vec![
+            BenchRow {
+                preset: Preset::Balanced,
+                encoder: "libx264",
+                output_bytes: Some(25 * 1024 * 1024),
+                seconds: 20.0,
+                scores: Some(Scores {
+                    ssim: Some(0.98765),
+                    psnr: Some(40.0),
+                    vmaf: Some(94.8368),
+                }),
+                error: None,
+            },
+            BenchRow {
+                preset: Preset::Speed,
+                encoder: "h264_qsv",
+                output_bytes: None,
+                seconds: 0.1,
+                scores: None,
+                error: Some("process failed: ffmpeg (exit code: Some(1))".to_string()),
+            },
+        ];
+
+        let lines = format_table(&rows, 100 * 1024 * 1024, Some(60_000_000));
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            "balanced  libx264       25.0 MB    25%      20.0    3.0x   94.84  0.9877"
+        );
+        assert!(lines[2].starts_with("speed     h264_qsv   failed: process failed"));
+    }
+}
@@ -0,0 +1,445 @@
+use std::path::Path;
+
+use crate::assets::ffmpeg::FfmpegSource;
+use crate::error::{Result, TinythisError};
+use crate::exec::caps::Capabilities;
+use crate::json::Value;
+use crate::presets::encoder::Encoder;
+
+use super::Cli;
+use super::args::DoctorArgs;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Pass => "pass",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+pub fn run(cli: &Cli, args: &DoctorArgs) -> Result<()> {
+    let mut checks = Vec::new();
+
+    let ffmpeg = match crate::assets::ffmpeg::resolve_ffmpeg() {
+        Ok(Some((bins, source))) => Some((bins.ffmpeg, source)),
+        Ok(None) => {
+            checks.push(Check::fail(
+                "ffmpeg",
+                "not found next to tinythis.exe or in the app folder",
+                "run `tinythis setup`",
+            ));
+            None
+        }
+        Err(e) => {
+            checks.push(Check::fail("ffmpeg", e.to_string(), "run `tinythis setup`"));
+            None
+        }
+    };
+
+    if let Some((ffmpeg, source)) = &ffmpeg {
+        let caps = crate::exec::caps::detect_fresh(ffmpeg);
+        checks.push(match &caps {
+            Ok(c) => {
+                let from = match source {
+                    FfmpegSource::NearExe => "next to tinythis.exe",
+                    FfmpegSource::Bundled => "installed by setup",
+                };
+                Check::pass(
+                    "ffmpeg",
+                    format!("{} ({from}): {}", ffmpeg.display(), c.version),
+                )
+            }
+            Err(e) => Check::fail(
+                "ffmpeg",
+                format!("{} doesn't run: {}", ffmpeg.display(), first_line(e)),
+                "run `tinythis setup --force` to reinstall it",
+            ),
+        });
+        checks.push(check_ffprobe(ffmpeg));
+        if let Ok(c) = &caps {
+            checks.push(check_encoders(c));
+            checks.push(check_vmaf(c));
+        }
+    }
+
+    checks.push(check_options());
+    checks.push(check_app_dirs());
+    checks.push(check_path());
+    checks.push(check_install_lock());
+    checks.push(check_temp_dir());
+    if !args.offline {
+        checks.push(check_update());
+    }
+
+    if cli.json {
+        println!("{}", doctor_json(&checks));
+    } else {
+        for line in format_checks(&checks) {
+            println!("{line}");
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    if failed > 0 {
+        return Err(TinythisError::InvalidArgs(format!(
+            "doctor: {failed} check(s) failed"
+        )));
+    }
+    Ok(())
+}
+
+fn first_line(e: &TinythisError) -> String {
+    e.to_string().lines().next().unwrap_or_default().to_string()
+}
+
+fn check_ffprobe(ffmpeg: &Path) -> Check {
+    let name = if cfg!(windows) {
+        "ffprobe.exe"
+    } else {
+        "ffprobe"
+    };
+    let ffprobe = ffmpeg.with_file_name(name);
+    if ffprobe.is_file() {
+        Check::pass("ffprobe", ffprobe.display().to_string())
+    } else {
+        Check::warn(
+            "ffprobe",
+            format!("not found next to {}", ffmpeg.display()),
+            "not needed by tinythis (it probes with ffmpeg); only your own scripts would miss it",
+        )
+    }
+}
+
+fn check_encoders(caps: &Capabilities) -> Check {
+    let software = ["libx264", "libx265", "libsvtav1"]
+        .into_iter()
+        .filter(|e| caps.has_encoder(e))
+        .collect::<Vec<_>>();
+    let listed = caps
+        .encoders
+        .iter()
+        .filter(|e| crate::exec::caps::is_hardware(e) && !caps.can_use(e))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    let mut detail = format!("cpu: {}", none_if_empty(&software));
+    detail.push_str(&format!("; gpu: {}", none_if_empty(&caps.usable_hardware)));
+    if !listed.is_empty() {
+        detail.push_str(&format!("; listed but not working: {}", listed.join(", ")));
+    }
+
+    if !caps.has_encoder(Encoder::CPU.ffmpeg_name()) {
+        return Check::fail(
+            "encoders",
+            detail,
+            "this ffmpeg build has no libx264; run `tinythis setup --force` for a full build",
+        );
+    }
+
+    let opts = crate::options::load().unwrap_or_default();
+    let pref = opts.encoder_pref(opts.gpu);
+    match pref.resolve(opts.codec, caps) {
+        Ok(e) => Check::pass("encoders", format!("{detail}; using {}", e.ffmpeg_name())),
+        Err(reason) => Check::warn(
+            "encoders",
+            format!("{detail}; options.toml asks for {pref}: {reason}"),
+            "update gpu drivers, or set `gpu = false` / another `encoder` in options.toml",
+        ),
+    }
+}
+
+fn none_if_empty<S: AsRef<str>>(items: &[S]) -> String {
+    if items.is_empty() {
+        "none".to_string()
+    } else {
+        items
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn check_vmaf(caps: &Capabilities) -> Check {
+    if caps.has_filter("libvmaf") {
+        Check::pass("vmaf", "libvmaf filter available")
+    } else {
+        Check::warn(
+            "vmaf",
+            "this ffmpeg build has no libvmaf; --target-vmaf and vmaf scores won't work",
+            "use an ffmpeg build with libvmaf (`tinythis setup --force` installs one)",
+        )
+    }
+}
+
+fn check_options() -> Check {
+    let path = match crate::options::options_path() {
+        Ok(p) => p,
+        Err(e) => return Check::fail("options", e.to_string(), "set LOCALAPPDATA"),
+    };
+    if !path.is_file() {
+        return Check::pass(
+            "options",
+            format!("{} (not created yet; using defaults)", path.display()),
+        );
+    }
+    match crate::options::load() {
+        Ok(_) => Check::pass("options", path.display().to_string()),
+        Err(e) => Check::fail(
+            "options",
+            format!("{}: {e}", path.display()),
+            "fix the line above, or delete the file to go back to defaults",
+        ),
+    }
+}
+
+fn check_app_dirs() -> Check {
+    let (root, bin, exe) = match (
+        crate::paths::app_root_dir(),
+        crate::paths::tinythis_bin_dir(),
+        crate::paths::tinythis_installed_exe_path(),
+    ) {
+        (Ok(root), Ok(bin), Ok(exe)) => (root, bin, exe),
+        (Err(e), ..) | (_, Err(e), _) | (.., Err(e)) => {
+            return Check::fail("app folder", e.to_string(), "set LOCALAPPDATA");
+        }
+    };
+
+    if !root.is_dir() {
+        return Check::warn(
+            "app folder",
+            format!("{} doesn't exist yet", root.display()),
+            "run `tinythis setup`",
+        );
+    }
+    if !exe.is_file() {
+        return Check::warn(
+            "app folder",
+            format!(
+                "{} exists, but {} is not installed",
+                root.display(),
+                exe.display()
+            ),
+            "run `tinythis setup path` to install tinythis into its bin folder",
+        );
+    }
+    Check::pass(
+        "app folder",
+        format!("{} (bin: {})", root.display(), bin.display()),
+    )
+}
+
+fn check_path() -> Check {
+    let bin = match crate::paths::tinythis_bin_dir() {
+        Ok(b) => b,
+        Err(e) => return Check::fail("path", e.to_string(), "set LOCALAPPDATA"),
+    };
+    match crate::self_install::user_path_contains(&bin) {
+        Ok(true) => Check::pass("path", format!("{} is on your user PATH", bin.display())),
+        Ok(false) => Check::warn(
+            "path",
+            format!("{} is not on your user PATH", bin.display()),
+            "run `tinythis setup path`",
+        ),
+        Err(e) => Check::warn("path", e.to_string(), "add the bin folder to PATH yourself"),
+    }
+}
+
+fn check_install_lock() -> Check {
+    use fs2::FileExt;
+
+    let lock_path = match crate::paths::ffmpeg_dir() {
+        Ok(d) => d.join(".install.lock"),
+        Err(e) => return Check::fail("install lock", e.to_string(), "set LOCALAPPDATA"),
+    };
+    let file = match std::fs::File::open(&lock_path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Check::pass("install lock", "no install has run yet");
+        }
+        Err(e) => {
+            return Check::warn(
+                "install lock",
+                format!("{}: {e}", lock_path.display()),
+                "check the folder's permissions",
+            );
+        }
+    };
+    match file.try_lock_exclusive() {
+        Ok(()) => {
+            let _ = FileExt::unlock(&file);
+            Check::pass("install lock", "free")
+        }
+        Err(_) => Check::warn(
+            "install lock",
+            format!("{} is held by another process", lock_path.display()),
+            "wait for `tinythis setup` to finish, or close other tinythis windows",
+        ),
+    }
+}
+
+fn check_temp_dir() -> Check {
+    let dir = std::env::temp_dir();
+    match tempfile::tempfile() {
+        Ok(_) => Check::pass("temp folder", format!("{} is writable", dir.display())),
+        Err(e) => Check::fail(
+            "temp folder",
+            format!("{}: {e}", dir.display()),
+            "point TEMP at a writable folder; benchmarks and vmaf search need it",
+        ),
+    }
+}
+
+fn check_update() -> Check {
+    match crate::update::check_latest_release(crate::update::DEFAULT_REPO) {
+        Ok(None) => Check::pass(
+            "update",
+            format!("up to date (v{})", env!("CARGO_PKG_VERSION")),
+        ),
+        Ok(Some(u)) => Check::warn(
+            "update",
+            format!("v{} -> v{} available", u.current, u.latest),
+            "run `tinythis update`",
+        ),
+        Err(e) => Check::warn(
+            "update",
+            format!("github releases unreachable: {}", first_line(&e)),
+            "check your connection or proxy; encoding works offline",
+        ),
+    }
+}
+
+fn format_checks(checks: &[Check]) -> Vec<String> {
+    let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    for c in checks {
+        lines.push(format!(
+            "{}  {:<width$}  {}",
+            c.status.as_str(),
+            c.name,
+            c.detail
+        ));
+        if let Some(fix) = &c.fix {
+            lines.push(format!("      {:<width$}  fix: {fix}", ""));
+        }
+    }
+    lines
+}
+
+fn doctor_json(checks: &[Check]) -> Value {
+    let count = |s: Status| checks.iter().filter(|c| c.status == s).count() as u64;
+    let items = checks
+        .iter()
+        .map(|c| {
+            Value::object([
+                ("name", Value::from(c.name)),
+                ("status", Value::from(c.status.as_str())),
+                ("detail", Value::from(c.detail.as_str())),
+                ("fix", Value::from(c.fix.clone())),
+            ])
+        })
+        .collect();
+
+    Value::object([
+        ("checks", Value::Array(items)),
+        ("warnings", Value::from(count(Status::Warn))),
+        ("failures", Value::from(count(Status::Fail))),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Check> {
+        vec![
+            Check::pass("ffmpeg", "C:\\ffmpeg.exe: ffmpeg version n7.1"),
+            Check::warn("path", "not on PATH", "run `tinythis setup path`"),
+        ]
+    }
+
+    #[test]
+    fn lists_checks_with_fixes_under_them() {
+        assert_eq!(
+            format_checks(&sample()),
+            vec![
+                "pass  ffmpeg  C:\\ffmpeg.exe: ffmpeg version n7.1",
+                "warn  path    not on PATH",
+                "              fix: run `tinythis setup path`",
+            ]
+        );
+
+        let v: serde_json::Value =
+            serde_json::from_str(&doctor_json(&sample()).to_string()).unwrap();
+        assert_eq!(v["warnings"], 1);
+        assert_eq!(v["failures"], 0);
+        assert_eq!(v["checks"][1]["status"], "warn");
+        assert_eq!(v["checks"][0]["fix"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn encoder_check_names_working_and_broken_gpus() {
+        let caps = Capabilities {
+            encoders: vec!["libx264".into(), "h264_nvenc".into(), "h264_qsv".into()],
+            usable_hardware: vec!["h264_qsv".into()],
+            ..Default::default()
+        };
+        let c = check_encoders(&caps);
+        assert!(
+            c.detail
+                .starts_with("cpu: libx264; gpu: h264_qsv; listed but not working: h264_nvenc")
+        );
+
+        let bare = Capabilities {
+            encoders: vec!["mpeg4".into()],
+            ..Default::default()
+        };
+        assert_eq!(check_encoders(&bare).status, Status::Fail);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::error::{Result, TinythisError};
+use crate::exec::queue::Queue;
+use crate::presets::encoder::EncoderPref;
+
+use super::Cli;
+
+pub fn run(cli: &Cli) -> Result<()> {
+    let Some(mut queue) = Queue::load()? else {
+        println!("nothing to resume");
+        return Ok(());
+    };
+    if !queue.claim()? {
+        return Err(TinythisError::InvalidArgs(
+            "another tinythis is still running that batch".to_string(),
+        ));
+    }
+    let todo = queue.unfinished().len();
+    if todo == 0 {
+        queue.remove()?;
+        println!("nothing to resume");
+        return Ok(());
+    }
+
+    // the batch keeps its own encoder and knobs; global flags given now still win.
+    let mut settings =
+        super::resolve_settings_with(cli, queue.preset, Some(EncoderPref::Fixed(queue.encoder)))?;
+    settings.min_savings = queue.min_savings;
+    settings.target_vmaf = cli.target_vmaf.or(queue.target_vmaf);
+    settings.container = cli.container.unwrap_or(queue.container);
+    if let Some(gif) = queue.gif {
+        settings.gif = gif;
+    }
+    settings.split = settings.split.or(queue.split);
+    settings.min_quality = cli.min_quality.or(queue.min_quality);
+    settings.measure = cli.measure || queue.measure || settings.min_quality.is_some();
+    settings.preserve = queue.preserve;
+    super::apply_preserve(&mut settings.preserve, &cli.preserve, true);
+    super::apply_preserve(&mut settings.preserve, &cli.no_preserve, false);
+    settings.cpu_fallback = cli.cpu_fallback || queue.cpu_fallback;
+    if !cli.hw_decode && !cli.no_hw_decode {
+        settings.hw_decode = queue.hw_decode;
+    }
+    super::check_container(&settings)?;
+
+    // ffmpeg args given now replace the batch's own.
+    let cli_extra = super::cli_extra_args(cli, &[])?;
+    if !cli_extra.is_empty() {
+        queue.extra_args = cli_extra;
+    }
+    let extra = super::extra_args(&queue.extra_args)?;
+
+    if cli.dry_run {
+        let inputs = queue
+            .unfinished()
+            .into_iter()
+            .map(|i| (queue.items[i].input.clone(), queue.items[i].overrides))
+            .collect::<Vec<_>>();
+        return super::positional::dry_run(&inputs, &settings, &extra, cli.json);
+    }
+
+    queue.discard_partial();
+    queue.set_settings(&settings);
+
+    let msg = format!(
+        "resuming {todo} of {} file(s) [{}]",
+        queue.items.len(),
+        queue.preset.as_str()
+    );
+    if cli.json {
+        eprintln!("{msg}");
+    } else {
+        println!("{msg}");
+    }
+
+    super::positional::run_queue(queue, &settings, &extra, cli.jobs, cli.json)
+}
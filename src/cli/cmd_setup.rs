@@ -2,7 +2,7 @@ use std::io::IsTerminal;
 
 use crate::error::Result;
 
-pub fn run(args: super::args::SetupArgs) -> Result<()> {
+pub fn run(args: &super::args::SetupArgs) -> Result<()> {
     let bins = crate::assets::ffmpeg::ensure_installed(args.force)?;
     println!("ffmpeg:  {}", bins.ffmpeg.display());
 
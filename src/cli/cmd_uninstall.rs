@@ -1,6 +1,8 @@
+use std::ffi::OsString;
+
 use crate::error::Result;
 
-pub fn run(args: super::args::UninstallArgs) -> Result<()> {
+pub fn run(args: &super::args::UninstallArgs) -> Result<()> {
     let _ = args;
 
     let app_root_dir = crate::paths::app_root_dir()?;
@@ -49,15 +51,16 @@ fn spawn_self_remove(bin_dir: &std::path::Path, app_root_dir: &std::path::Path)
     let helper = temp_dir.join(format!("tinythis-self-remove-{}.exe", std::process::id()));
     persist_overwrite(tmp, &helper)?;
 
-    let mut cmd = std::process::Command::new(&helper);
-    cmd.arg("self-remove")
-        .arg("--pid")
-        .arg(std::process::id().to_string())
-        .arg("--bin-dir")
-        .arg(bin_dir)
-        .arg("--app-root-dir")
-        .arg(app_root_dir);
-    cmd.spawn()?;
+    let args = [
+        OsString::from("self-remove"),
+        OsString::from("--pid"),
+        OsString::from(std::process::id().to_string()),
+        OsString::from("--bin-dir"),
+        bin_dir.as_os_str().to_owned(),
+        OsString::from("--app-root-dir"),
+        app_root_dir.as_os_str().to_owned(),
+    ];
+    crate::process::run::command(&helper, &args).spawn()?;
     Ok(())
 }
 
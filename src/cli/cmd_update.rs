@@ -1,6 +1,6 @@
 use crate::error::Result;
 
-pub fn run(args: super::args::UpdateArgs) -> Result<()> {
+pub fn run(args: &super::args::UpdateArgs) -> Result<()> {
     let update = crate::update::check_latest_release(crate::update::DEFAULT_REPO)?;
     let Some(update) = update else {
         println!("up to date");
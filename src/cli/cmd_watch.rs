@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, TinythisError};
+use crate::exec::compress::{EncodeSettings, OutputNaming};
+use crate::exec::extra_args::PresetArgs;
+use crate::exec::report::FileReport;
+use crate::exec::watch::{Handled, Stability, WatchState};
+
+use super::Cli;
+use super::args::WatchArgs;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub fn run(cli: &Cli, args: &WatchArgs) -> Result<()> {
+    if !args.dir.is_dir() {
+        return Err(TinythisError::InvalidArgs(format!(
+            "watch: {} is not a folder",
+            args.dir.display()
+        )));
+    }
+    // absolute, so the state file means the same thing from any working directory.
+    let dir = std::path::absolute(&args.dir)?;
+
+    let settings = super::resolve_settings(cli, args.preset)?;
+    super::check_container(&settings)?;
+    let naming = crate::options::load()?.output;
+    let extra = super::extra_args(&super::cli_extra_args(cli, &[])?)?;
+    let (bins, source) = super::ffmpeg_or_setup()?;
+    if source == crate::assets::ffmpeg::FfmpegSource::NearExe {
+        println!("local mode: using ffmpeg next to tinythis.exe");
+    }
+    println!(
+        "watching {} [{}] (ctrl+c to stop)",
+        dir.display(),
+        args.preset.as_str()
+    );
+
+    let mut state = WatchState::load()?;
+    let mut stability = Stability::new(Duration::from_secs(args.stable_secs));
+    loop {
+        for (path, size) in crate::exec::watch::scan(&dir)? {
+            if state.is_handled(&path, size) || !stability.observe(&path, size, Instant::now()) {
+                continue;
+            }
+            stability.forget(&path);
+
+            // one bad file shouldn't stop the watcher; it's recorded and left alone.
+            let how = match compress_one(&bins.ffmpeg, &path, &settings, &extra, &naming) {
+                Ok((report, outputs)) => {
+                    println!("  {}", report.summary_line());
+                    // a custom template may not say `.tinythis.`; the output is never an input.
+                    for out in outputs {
+                        if let Ok(meta) = std::fs::metadata(&out) {
+                            state.mark(&out, meta.len(), Handled::Skipped);
+                        }
+                    }
+                    if report.is_skipped() {
+                        Handled::Skipped
+                    } else {
+                        Handled::Done
+                    }
+                }
+                Err(e) => {
+                    eprintln!("  failed: {e}");
+                    Handled::Failed
+                }
+            };
+            state.mark(&path, size, how);
+
+            if args.move_done && how != Handled::Failed {
+                match crate::exec::watch::move_to_done(&path) {
+                    Ok(to) => println!("  moved original to {}", to.display()),
+                    Err(e) => eprintln!("  could not move original: {e}"),
+                }
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn compress_one(
+    ffmpeg: &Path,
+    input: &Path,
+    settings: &EncodeSettings,
+    extra: &PresetArgs,
+    naming: &OutputNaming,
+) -> Result<(FileReport, Vec<PathBuf>)> {
+    let mut plan = crate::exec::compress::plan_file(ffmpeg, input, settings, extra, None)?;
+    if plan.is_skip() {
+        println!("skipping {}", input.display());
+        return Ok((FileReport::new(input, None, &plan, None), Vec::new()));
+    }
+
+    let out_path = crate::exec::compress::build_output_path(
+        input,
+        plan.settings.preset,
+        plan.settings.container,
+        naming,
+    )?;
+    println!(
+        "compressing [{}] {} -> {}",
+        plan.settings.preset.as_str(),
+        input.display(),
+        out_path.display()
+    );
+
+    crate::exec::compress::compress_file(ffmpeg, input, &out_path, &mut plan, |p| {
+        super::print_progress(&mut std::io::stdout(), p, None)
+    })?;
+
+    let scores = crate::exec::compress::measure_output(ffmpeg, input, &out_path, &plan)?;
+    crate::exec::split::split_output(ffmpeg, input, &out_path, naming.on_existing, &mut plan)?;
+    let report = FileReport::new(input, Some(&out_path), &plan, scores);
+    let outputs = if plan.parts.is_empty() {
+        vec![out_path]
+    } else {
+        plan.parts
+    };
+    Ok((report, outputs))
+}
@@ -1,56 +1,368 @@
 mod args;
+mod cmd_bench;
+mod cmd_doctor;
+mod cmd_resume;
 mod cmd_setup;
 mod cmd_setup_path;
 mod cmd_uninstall;
 mod cmd_update;
+mod cmd_watch;
 mod positional;
 
 pub use args::Cli;
 
-use crate::error::Result;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+
+use crate::assets::ffmpeg::{FfmpegBinaries, FfmpegSource};
+use crate::error::{Result, TinythisError};
+use crate::exec::compress::{EncodeSettings, FileOverrides, HwDecode, Preserve};
+use crate::exec::extra_args::{ExtraArgs, PresetArgs};
+use crate::exec::progress::EncodeProgress;
+use crate::exec::split::SplitLimit;
 use crate::presets::Preset;
+use crate::presets::encoder::{Codec, Encoder, EncoderPref};
+
+pub fn run(cli: &Cli, command: &args::Command) -> Result<()> {
+    use args::Command as C;
+    if cli.dry_run
+        && !matches!(
+            command,
+            C::Balanced(_)
+                | C::Quality(_)
+                | C::Speed(_)
+                | C::Remux(_)
+                | C::Auto(_)
+                | C::Gif(_)
+                | C::Webp(_)
+                | C::Resume
+        )
+    {
+        return Err(TinythisError::InvalidArgs(
+            "--dry-run only applies when compressing files".to_string(),
+        ));
+    }
 
-pub fn run(gpu: bool, cpu: bool, command: args::Command) -> Result<()> {
     match command {
         args::Command::Balanced(args) => {
-            let use_gpu = resolve_use_gpu(gpu, cpu)?;
-            positional::run(Preset::Balanced, &args.inputs, use_gpu)
+            let settings = resolve_settings(cli, Preset::Balanced)?;
+            compress(cli, &args.inputs, &args.ffmpeg_tail, &settings)
         }
         args::Command::Quality(args) => {
-            let use_gpu = resolve_use_gpu(gpu, cpu)?;
-            positional::run(Preset::Quality, &args.inputs, use_gpu)
+            let settings = resolve_settings(cli, Preset::Quality)?;
+            compress(cli, &args.inputs, &args.ffmpeg_tail, &settings)
         }
         args::Command::Speed(args) => {
-            let use_gpu = resolve_use_gpu(gpu, cpu)?;
-            positional::run(Preset::Speed, &args.inputs, use_gpu)
+            let settings = resolve_settings(cli, Preset::Speed)?;
+            compress(cli, &args.inputs, &args.ffmpeg_tail, &settings)
+        }
+        args::Command::Remux(args) => {
+            let settings = resolve_settings(cli, Preset::Remux)?;
+            compress(cli, &args.inputs, &args.ffmpeg_tail, &settings)
         }
-        args::Command::Setup(setup) => match setup.command {
+        args::Command::Auto(args) => {
+            let mut settings = resolve_settings(cli, Preset::Auto)?;
+            if let Some(v) = args.min_savings {
+                settings.min_savings = v;
+            }
+            compress(
+                cli,
+                &args.compress.inputs,
+                &args.compress.ffmpeg_tail,
+                &settings,
+            )
+        }
+        args::Command::Gif(args) => animate(cli, Preset::Gif, args),
+        args::Command::Webp(args) => animate(cli, Preset::Webp, args),
+        args::Command::Bench(args) => cmd_bench::run(cli, args),
+        args::Command::Doctor(args) => cmd_doctor::run(cli, args),
+        args::Command::Resume => cmd_resume::run(cli),
+        args::Command::Watch(args) => cmd_watch::run(cli, args),
+        args::Command::Setup(setup) => match &setup.command {
             Some(args::SetupSubcommand::Path(args)) => cmd_setup_path::run(args),
-            None => cmd_setup::run(setup.args),
+            None => cmd_setup::run(&setup.args),
         },
         args::Command::Update(args) => cmd_update::run(args),
         args::Command::Uninstall(args) => cmd_uninstall::run(args),
         args::Command::SelfRemove(args) => {
             crate::self_install::run_self_remove(crate::self_install::SelfRemoveArgs {
                 pid: args.pid,
-                bin_dir: args.bin_dir,
-                app_root_dir: args.app_root_dir,
+                bin_dir: args.bin_dir.clone(),
+                app_root_dir: args.app_root_dir.clone(),
             })
         }
     }
 }
 
 pub fn run_positional(cli: &Cli) -> Result<()> {
-    let use_gpu = resolve_use_gpu(cli.gpu, cli.cpu)?;
-    positional::run(Preset::Balanced, &cli.inputs, use_gpu)
+    let preset = crate::options::load()?.preset;
+    let settings = resolve_settings(cli, preset)?;
+    compress(cli, &cli.inputs, &cli.ffmpeg_tail, &settings)
+}
+
+/// Runs the gif or webp preset with the subcommand's flags over options.toml's `[gif]`.
+fn animate(cli: &Cli, preset: Preset, args: &args::GifArgs) -> Result<()> {
+    let mut settings = resolve_settings(cli, preset)?;
+    let gif = &mut settings.gif;
+    gif.fps = args.fps.unwrap_or(gif.fps);
+    gif.width = args.width.unwrap_or(gif.width);
+    gif.plays = args.plays.unwrap_or(gif.plays);
+    gif.dither = args.dither.unwrap_or(gif.dither);
+    gif.max_bytes = args.max_size.or(gif.max_bytes);
+    compress(
+        cli,
+        &args.compress.inputs,
+        &args.compress.ffmpeg_tail,
+        &settings,
+    )
+}
+
+/// Compresses `inputs`, or with `--dry-run` only prints the commands that would run.
+fn compress(
+    cli: &Cli,
+    inputs: &[PathBuf],
+    ffmpeg_tail: &[String],
+    settings: &EncodeSettings,
+) -> Result<()> {
+    check_inputs(cli, inputs)?;
+    let cli_extra = cli_extra_args(cli, ffmpeg_tail)?;
+    let extra = extra_args(&cli_extra)?;
+    check_container(settings)?;
+    if settings.split.is_some() && settings.preset.is_animated() {
+        return Err(TinythisError::InvalidArgs(
+            "--split-size and --split-duration don't apply to gif or webp".to_string(),
+        ));
+    }
+    if cli.dry_run {
+        let files = inputs
+            .iter()
+            .map(|p| (p.clone(), FileOverrides::default()))
+            .collect::<Vec<_>>();
+        positional::dry_run(&files, settings, &extra, cli.json)
+    } else {
+        positional::run(inputs, settings, &cli_extra, &extra, cli.jobs, cli.json)
+    }
+}
+
+/// Refuses inputs without a known video extension, unless sniffing finds a video in them.
+fn check_inputs(cli: &Cli, inputs: &[PathBuf]) -> Result<()> {
+    let sniff = !cli.extensions_only && crate::options::load()?.sniff_inputs;
+    // only looked up once a file needs probing.
+    let mut ffmpeg = None;
+    for input in inputs {
+        if crate::exec::input::is_supported_video(input) {
+            continue;
+        }
+        if !sniff {
+            return Err(TinythisError::InvalidArgs(format!(
+                "{}: unsupported input extension",
+                input.display()
+            )));
+        }
+        let ffmpeg = match &ffmpeg {
+            Some(f) => f,
+            None => ffmpeg.insert(ffmpeg_or_setup()?.0.ffmpeg),
+        };
+        if !crate::exec::input::sniff(ffmpeg, input) {
+            return Err(TinythisError::InvalidArgs(format!(
+                "{}: no video ffmpeg can read",
+                input.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Fails before any file starts when the encoder's video can't go into the chosen container;
+/// a remux that only copies is checked per file.
+fn check_container(settings: &EncodeSettings) -> Result<()> {
+    if matches!(
+        settings.preset,
+        Preset::Remux | Preset::Auto | Preset::Gif | Preset::Webp
+    ) {
+        return Ok(());
+    }
+    settings
+        .container
+        .check(settings.encoder)
+        .map_err(TinythisError::InvalidArgs)
+}
+
+/// `--ffmpeg-input-args`, `--ffmpeg-args` and whatever follows `--`.
+fn cli_extra_args(cli: &Cli, ffmpeg_tail: &[String]) -> Result<ExtraArgs> {
+    let split = |flag: &str, value: &Option<String>| -> Result<Vec<String>> {
+        let args = crate::exec::extra_args::split(value.as_deref().unwrap_or_default())
+            .map_err(|e| TinythisError::InvalidArgs(format!("{flag}: {e}")))?;
+        crate::exec::extra_args::validate(&args)
+            .map_err(|e| TinythisError::InvalidArgs(format!("{flag}: {e}")))?;
+        Ok(args)
+    };
+    let mut output = split("--ffmpeg-args", &cli.ffmpeg_args)?;
+    output.extend(ffmpeg_tail.iter().cloned());
+    crate::exec::extra_args::validate(&output)
+        .map_err(|e| TinythisError::InvalidArgs(format!("ffmpeg args: {e}")))?;
+    Ok(ExtraArgs {
+        input: split("--ffmpeg-input-args", &cli.ffmpeg_input_args)?,
+        output,
+    })
+}
+
+/// options.toml's per-preset ffmpeg options, then the cli's.
+fn extra_args(cli_args: &ExtraArgs) -> Result<PresetArgs> {
+    Ok(crate::options::load()?.extra_args.then_all(cli_args))
+}
+
+/// Finds ffmpeg, offering to run `tinythis setup` when it's missing and stdin is a terminal.
+fn ffmpeg_or_setup() -> Result<(FfmpegBinaries, FfmpegSource)> {
+    if let Some(found) = crate::assets::ffmpeg::resolve_ffmpeg()? {
+        return Ok(found);
+    }
+
+    let err = || {
+        TinythisError::InvalidArgs(
+            "ffmpeg not available; run `tinythis setup` or place ffmpeg.exe next to tinythis.exe"
+                .to_string(),
+        )
+    };
+
+    if !std::io::stdin().is_terminal() {
+        return Err(err());
+    }
+
+    if !crate::confirm::confirm("ffmpeg not available. run `tinythis setup` now?")? {
+        return Err(err());
+    }
+
+    cmd_setup::run(&args::SetupArgs {
+        force: false,
+        yes: false,
+    })?;
+
+    crate::assets::ffmpeg::resolve_ffmpeg()?.ok_or_else(err)
+}
+
+/// Picks the encoder for `pref`, failing before any encoding starts when it can't run here.
+fn resolve_encoder(pref: EncoderPref, codec: Codec) -> Result<Encoder> {
+    if pref == EncoderPref::Cpu && codec == Codec::H264 {
+        return Ok(Encoder::CPU);
+    }
+    let (bins, _) = ffmpeg_or_setup()?;
+    let caps = crate::exec::caps::detect(&bins.ffmpeg)?;
+    pref.resolve(codec, &caps).map_err(|reason| {
+        TinythisError::InvalidArgs(format!(
+            "encoder unavailable: {reason}. use --cpu or --encoder, or change gpu/encoder in options.toml"
+        ))
+    })
+}
+
+/// The encoder preference from `--encoder`, `--gpu`/`--cpu` and options.toml.
+fn encoder_pref(cli: &Cli, opts: &crate::options::Options) -> EncoderPref {
+    if let Some(pref) = cli.encoder {
+        return pref;
+    }
+    opts.encoder_pref(cli.gpu || (!cli.cpu && opts.gpu))
+}
+
+/// Merges options.toml with the global cli overrides.
+fn resolve_settings(cli: &Cli, preset: Preset) -> Result<EncodeSettings> {
+    resolve_settings_with(cli, preset, None)
 }
 
-fn resolve_use_gpu(gpu: bool, cpu: bool) -> Result<bool> {
-    if gpu {
-        return Ok(true);
+/// Like `resolve_settings`, with `encoder` in place of options.toml's unless the cli picks one.
+fn resolve_settings_with(
+    cli: &Cli,
+    preset: Preset,
+    encoder: Option<EncoderPref>,
+) -> Result<EncodeSettings> {
+    let opts = crate::options::load()?;
+
+    let pref = match encoder {
+        Some(pref) if cli.encoder.is_none() && !cli.gpu && !cli.cpu => pref,
+        _ => encoder_pref(cli, &opts),
+    };
+    // animations have encoders of their own; don't fail over a gpu they never use.
+    let encoder = if preset.is_animated() {
+        Encoder::CPU
+    } else {
+        resolve_encoder(pref, opts.codec)?
+    };
+    let min_quality = cli.min_quality.or(opts.min_quality);
+
+    let hw_decode = if cli.hw_decode || cli.no_hw_decode {
+        HwDecode::all(cli.hw_decode)
+    } else {
+        opts.hw_decode
+    };
+
+    let mut preserve = opts.preserve;
+    apply_preserve(&mut preserve, &cli.preserve, true);
+    apply_preserve(&mut preserve, &cli.no_preserve, false);
+
+    Ok(EncodeSettings {
+        preset,
+        encoder,
+        container: cli.container.unwrap_or(opts.container),
+        preserve,
+        min_savings: opts.auto_min_savings,
+        target_vmaf: cli.target_vmaf,
+        crf: None,
+        measure: cli.measure || opts.measure || min_quality.is_some(),
+        min_quality,
+        cpu_fallback: cli.cpu_fallback || opts.cpu_fallback,
+        hw_decode,
+        trim_secs: None,
+        max_height: None,
+        gif: opts.gif,
+        split: split_limit(cli),
+    })
+}
+
+/// How a split limit is shown, e.g. `25.0 MB` or `10:00`.
+fn split_text(limit: SplitLimit) -> String {
+    match limit {
+        SplitLimit::Size(bytes) => crate::exec::report::format_bytes(bytes),
+        SplitLimit::Duration(secs) => crate::exec::progress::format_clock(u64::from(secs)),
+    }
+}
+
+/// `--split-size` or `--split-duration`, whichever was given.
+fn split_limit(cli: &Cli) -> Option<SplitLimit> {
+    cli.split_size
+        .map(SplitLimit::Size)
+        .or(cli.split_duration.map(SplitLimit::Duration))
+}
+
+/// Redraws one progress line, ending it once the encode reaches 100%.
+fn print_progress(out: &mut dyn Write, p: &EncodeProgress, batch_eta_secs: Option<u64>) {
+    let line = [
+        p.percent.map(|pct| format!("{pct:3}%")).unwrap_or_default(),
+        p.stats_line(),
+        batch_eta_secs
+            .map(|s| format!("batch {}", crate::exec::progress::format_clock(s)))
+            .unwrap_or_default(),
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join("  ");
+    // padded, so a shorter line fully covers the one before it.
+    let _ = write!(out, "\r{line:<79}");
+    let _ = out.flush();
+    if p.percent == Some(100) {
+        let _ = writeln!(out);
     }
-    if cpu {
-        return Ok(false);
+}
+
+fn apply_preserve(preserve: &mut Preserve, what: &[args::PreserveArg], value: bool) {
+    for w in what {
+        match w {
+            args::PreserveArg::All => *preserve = Preserve::all(value),
+            args::PreserveArg::Metadata => preserve.metadata = value,
+            args::PreserveArg::Chapters => preserve.chapters = value,
+            args::PreserveArg::CreationTime => preserve.creation_time = value,
+            args::PreserveArg::Rotation => preserve.rotation = value,
+            args::PreserveArg::Subtitles => preserve.subtitles = value,
+            args::PreserveArg::Timestamps => preserve.timestamps = value,
+        }
     }
-    Ok(crate::options::load()?.gpu)
 }
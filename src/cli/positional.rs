@@ -1,68 +1,388 @@
-use std::ffi::OsString;
 use std::io::{IsTerminal, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use crate::error::{Result, TinythisError};
-use crate::presets::Preset;
-
-pub fn run(preset: Preset, inputs: &[PathBuf], use_gpu: bool) -> Result<()> {
-    let (bins, source) = match crate::assets::ffmpeg::resolve_ffmpeg()? {
-        Some((bins, source)) => (bins, source),
-        None => {
-            let err = || {
-                TinythisError::InvalidArgs(
-                    "ffmpeg not available; run `tinythis setup` or place ffmpeg.exe next to tinythis.exe"
-                        .to_string(),
-                )
-            };
-
-            if !std::io::stdin().is_terminal() {
-                return Err(err());
-            }
+use crate::exec::compress::{EncodeSettings, FileOverrides, OutputNaming};
+use crate::exec::extra_args::{ExtraArgs, PresetArgs};
+use crate::exec::probe::MediaInfo;
+use crate::exec::queue::{ItemStatus, Queue};
+use crate::exec::report::FileReport;
 
-            if !crate::confirm::confirm("ffmpeg not available. run `tinythis setup` now?")? {
-                return Err(err());
-            }
+pub fn run(
+    inputs: &[PathBuf],
+    settings: &EncodeSettings,
+    cli_extra: &ExtraArgs,
+    extra: &PresetArgs,
+    jobs: Option<u8>,
+    json: bool,
+) -> Result<()> {
+    let mut queue = Queue::new(settings, inputs);
+    queue.extra_args = cli_extra.clone();
+    // a failed queue write only costs the ability to `tinythis resume`.
+    match queue.claim() {
+        Ok(true) => {
+            replace_unfinished(json)?;
+            let _ = queue.save();
+        }
+        Ok(false) => log::warn!(
+            "another tinythis is running a batch; `tinythis resume` won't pick this one up"
+        ),
+        Err(e) => log::warn!("can't record the batch for `tinythis resume`: {e}"),
+    }
+    run_queue(queue, settings, extra, jobs, json)
+}
+
+/// Makes way for a new batch. An interrupted one is only thrown away once the user agrees (or,
+/// with nobody to ask, with a warning), and its partial outputs go with it.
+fn replace_unfinished(json: bool) -> Result<()> {
+    let Ok(Some(mut old)) = Queue::load() else {
+        return Ok(());
+    };
+    let todo = old.unfinished().len();
+    if todo == 0 {
+        return Ok(());
+    }
 
-            super::cmd_setup::run(super::args::SetupArgs {
-                force: false,
-                yes: false,
-            })?;
+    let what = format!("an interrupted batch still has {todo} file(s) to do");
+    // the prompt would end up in the --json document.
+    if std::io::stdin().is_terminal() && !json {
+        let prompt = format!("{what} (`tinythis resume` finishes it). discard it?");
+        if !crate::confirm::confirm(&prompt)? {
+            return Err(TinythisError::InvalidArgs(
+                "kept the interrupted batch; run `tinythis resume` to finish it".to_string(),
+            ));
+        }
+    } else {
+        log::warn!("{what}; discarding it and its partial outputs");
+    }
+    old.discard_partial();
+    Ok(())
+}
 
-            crate::assets::ffmpeg::resolve_ffmpeg()?.ok_or_else(err)?
+/// Encodes the queue's unfinished items, `jobs` at a time (options.toml's when `None`),
+/// recording each one's progress in the queue file.
+pub fn run_queue(
+    queue: Queue,
+    settings: &EncodeSettings,
+    extra: &PresetArgs,
+    jobs: Option<u8>,
+    json: bool,
+) -> Result<()> {
+    let (bins, source) = super::ffmpeg_or_setup()?;
+    // with --json, stdout only carries the summary document.
+    let say = |msg: String| {
+        if json {
+            eprintln!("{msg}");
+        } else {
+            println!("{msg}");
         }
     };
+
     if source == crate::assets::ffmpeg::FfmpegSource::NearExe {
-        println!("local mode: using ffmpeg next to tinythis.exe");
+        say("local mode: using ffmpeg next to tinythis.exe".to_string());
+    }
+    if settings.encoder.is_hardware() {
+        say(format!("encoder: {}", settings.encoder.ffmpeg_name()));
+    }
+    if let Some(t) = settings.min_quality
+        && t.metric == crate::exec::metrics::Metric::Vmaf
+        && !crate::exec::metrics::has_libvmaf(&bins.ffmpeg)
+    {
+        return Err(TinythisError::InvalidArgs(format!(
+            "--min-quality {t} needs an ffmpeg build with libvmaf"
+        )));
     }
 
-    for (i, input) in inputs.iter().enumerate() {
-        let out_path = crate::exec::compress::build_output_path(input, preset)?;
-        let mut args = crate::exec::compress::build_ffmpeg_args(input, &out_path, preset, use_gpu);
-        args.extend([OsString::from("-progress"), OsString::from("pipe:1")]);
+    let opts = crate::options::load()?;
+    let jobs = jobs.unwrap_or(opts.jobs);
+    let run = Run {
+        ffmpeg: &bins.ffmpeg,
+        settings,
+        extra,
+        naming: &opts.output,
+        json,
+        // one progress line can't follow several encodes.
+        live: jobs == 1,
+        say,
+    };
+    let todo = queue.unfinished();
+    // probed up front for the batch eta; a file that fails here fails again in `plan_file`.
+    let infos = todo
+        .iter()
+        .map(|&i| crate::exec::probe::probe(&bins.ffmpeg, &queue.items[i].input).ok())
+        .collect::<Vec<_>>();
+    let lengths = todo
+        .iter()
+        .zip(&infos)
+        .map(|(&i, info)| {
+            let trim = queue.items[i].overrides.trim_secs.or(settings.trim_secs);
+            crate::exec::compress::output_length_us(info.as_ref(), trim).unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
 
-        println!(
-            "compressing ({}/{}) [{}] {} -> {}",
-            i + 1,
-            inputs.len(),
-            preset.as_str(),
-            input.display(),
-            out_path.display()
-        );
+    let work = todo
+        .into_iter()
+        .zip(infos)
+        .enumerate()
+        .map(|(n, (i, info))| (i, info, lengths[n + 1..].iter().sum()))
+        .collect::<Vec<_>>();
+    let queue = Mutex::new(queue);
+    let (reports, failed) = crate::exec::pool::run(jobs, work, |(i, info, later_us)| {
+        run_item(&run, &queue, i, info, later_us).inspect_err(|_| {
+            queue.lock().unwrap().mark(i, ItemStatus::Failed, None);
+        })
+    });
+    if failed.is_none() {
+        // every item is done or skipped by now; nothing is left to resume.
+        let _ = queue.lock().unwrap().remove();
+    }
 
-        let mut last: Option<u8> = None;
-        crate::exec::compress::run_ffmpeg(&bins.ffmpeg, &args, move |pct| {
-            if last == Some(pct) {
-                return;
-            }
-            last = Some(pct);
-            let _ = write!(std::io::stdout(), "\r{pct:3}%");
-            let _ = std::io::stdout().flush();
-            if pct == 100 {
-                let _ = writeln!(std::io::stdout());
+    // after a failure, the files that finished (several, with parallel jobs) are still listed.
+    if failed.is_none() || !reports.is_empty() {
+        print_summary(&reports, json);
+    }
+    if let Some(e) = failed {
+        return Err(e);
+    }
+
+    let below = reports.iter().filter(|r| r.is_below_threshold()).count();
+    if below > 0 {
+        return Err(TinythisError::InvalidArgs(format!(
+            "{below} output(s) below the quality threshold"
+        )));
+    }
+
+    Ok(())
+}
+
+fn print_summary(reports: &[FileReport], json: bool) {
+    if json {
+        println!("{}", crate::exec::report::summary_json(reports));
+    } else {
+        println!();
+        println!("summary:");
+        for line in crate::exec::report::summary_lines(reports) {
+            println!("  {line}");
+        }
+    }
+}
+
+/// Prints the ffmpeg command each input would run, with its overrides, without encoding or
+/// touching the queue.
+///
+/// Plain output is one command per line on stdout, so it can be saved as a script; notes go to
+/// stderr.
+pub fn dry_run(
+    inputs: &[(PathBuf, FileOverrides)],
+    settings: &EncodeSettings,
+    extra: &PresetArgs,
+    json: bool,
+) -> Result<()> {
+    use crate::json::Value;
+
+    let (bins, _) = super::ffmpeg_or_setup()?;
+    let naming = crate::options::load()?.output;
+    let mut files = Vec::with_capacity(inputs.len());
+    for (input, overrides) in inputs {
+        let settings = &overrides.apply(settings);
+        let plan = crate::exec::compress::plan_dry(&bins.ffmpeg, input, settings, extra, None)?;
+        let input_json = Value::from(input.display().to_string());
+        if let Some(d) = plan.decision.as_ref().filter(|_| plan.is_skip()) {
+            if !json {
+                eprintln!("skipping {}: {}", input.display(), d.reason);
             }
-        })?;
+            files.push(Value::object([
+                ("input", input_json),
+                ("skipped", Value::from(d.reason.as_str())),
+            ]));
+            continue;
+        }
+
+        // without a cached result the crf is only known once the search runs.
+        let search_pending = settings.target_vmaf.is_some() && plan.settings.crf.is_none();
+        if search_pending && !json {
+            eprintln!(
+                "{}: crf comes from the vmaf search when encoding",
+                input.display()
+            );
+        }
+        if let Some(limit) = settings.split
+            && !json
+        {
+            eprintln!(
+                "{}: split into parts of at most {} after encoding",
+                input.display(),
+                super::split_text(limit)
+            );
+        }
+        let output = crate::exec::compress::build_output_path(
+            input,
+            plan.settings.preset,
+            plan.settings.container,
+            &naming,
+        )?;
+        let args = crate::exec::compress::encode_args(
+            input,
+            &output,
+            &plan.settings,
+            plan.info.as_ref(),
+            &plan.extra_args,
+        );
+        let line = crate::process::run::command_line(&bins.ffmpeg, &args);
+        if !json {
+            println!("{line}");
+            continue;
+        }
+        files.push(Value::object([
+            ("input", input_json),
+            ("output", Value::from(output.display().to_string())),
+            ("command", Value::from(line)),
+            (
+                "args",
+                Value::Array(
+                    args.iter()
+                        .map(|a| Value::from(a.to_string_lossy().into_owned()))
+                        .collect(),
+                ),
+            ),
+            ("crf_search_pending", Value::from(search_pending)),
+        ]));
     }
 
+    if json {
+        println!("{}", Value::object([("files", Value::Array(files))]));
+    }
     Ok(())
 }
+
+/// What every item of one run shares.
+struct Run<'a, F> {
+    ffmpeg: &'a Path,
+    settings: &'a EncodeSettings,
+    extra: &'a PresetArgs,
+    naming: &'a OutputNaming,
+    json: bool,
+    /// redraw a progress line while encoding.
+    live: bool,
+    say: F,
+}
+
+fn run_item(
+    run: &Run<impl Fn(String)>,
+    queue: &Mutex<Queue>,
+    i: usize,
+    info: Option<MediaInfo>,
+    later_us: u64,
+) -> Result<FileReport> {
+    let &Run {
+        ffmpeg,
+        settings,
+        extra,
+        naming,
+        json,
+        live,
+        ref say,
+    } = run;
+    let (total, input, overrides) = {
+        let q = queue.lock().unwrap();
+        (
+            q.items.len(),
+            q.items[i].input.clone(),
+            q.items[i].overrides,
+        )
+    };
+    let input = input.as_path();
+    // a batch from the tui can carry per-file overrides.
+    let settings = &overrides.apply(settings);
+    if let Some(target) = settings.target_vmaf {
+        say(format!(
+            "searching crf for vmaf {target} ({}/{}) {}",
+            i + 1,
+            total,
+            input.display()
+        ));
+    }
+    let mut plan = crate::exec::compress::plan_file(ffmpeg, input, settings, extra, info)?;
+    if let Some(found) = plan.crf_search {
+        say(format!(
+            "  crf {} (vmaf {:.1}{})",
+            found.crf,
+            found.vmaf,
+            if found.cached { ", cached" } else { "" }
+        ));
+    }
+    if plan.is_skip() {
+        say(format!(
+            "skipping ({}/{}) {}",
+            i + 1,
+            total,
+            input.display()
+        ));
+        queue.lock().unwrap().mark(i, ItemStatus::Skipped, None);
+        return Ok(FileReport::new(input, None, &plan, None));
+    }
+
+    let preset = plan.settings.preset;
+    // recorded before encoding starts, so `resume` can throw away a half-written output.
+    let out_path = queue.lock().unwrap().start(i, |taken| {
+        crate::exec::compress::build_output_path_except(
+            input,
+            preset,
+            plan.settings.container,
+            naming,
+            taken,
+        )
+    })?;
+
+    say(format!(
+        "compressing ({}/{}) [{}] {} -> {}",
+        i + 1,
+        total,
+        preset.as_str(),
+        input.display(),
+        out_path.display()
+    ));
+
+    crate::exec::compress::compress_file(ffmpeg, input, &out_path, &mut plan, move |p| {
+        if !live {
+            return;
+        }
+        let mut out: Box<dyn Write> = if json {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(std::io::stdout())
+        };
+        let batch_eta = crate::exec::progress::batch_eta_secs(p, later_us);
+        super::print_progress(&mut out, p, batch_eta.filter(|_| later_us > 0));
+    })?;
+
+    if let Some(failed) = plan.fallback {
+        say(format!(
+            "  {} failed to start; encoded on cpu instead",
+            failed.ffmpeg_name()
+        ));
+    }
+
+    if plan.settings.measure {
+        say("  measuring quality...".to_string());
+    }
+    let scores = crate::exec::compress::measure_output(ffmpeg, input, &out_path, &plan)?;
+    if let Some(limit) = plan.settings.split {
+        say(format!(
+            "  splitting into parts of {}...",
+            super::split_text(limit)
+        ));
+    }
+    crate::exec::split::split_output(ffmpeg, input, &out_path, naming.on_existing, &mut plan)?;
+    queue.lock().unwrap().mark(i, ItemStatus::Done, None);
+    if !live {
+        say(format!(
+            "finished ({}/{}) {}",
+            i + 1,
+            total,
+            input.display()
+        ));
+    }
+    Ok(FileReport::new(input, Some(&out_path), &plan, scores))
+}
@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::error::{Result, TinythisError};
+
+const CACHE_FILE_NAME: &str = "caps_cache.txt";
+/// How long cached test encodes count; drivers, gpus and encode sessions change under a binary.
+const HARDWARE_TTL_SECS: u64 = 60 * 60;
+
+/// Hardware encoders tinythis knows about; a listed one only counts once a test encode works.
+const HARDWARE_ENCODERS: &[&str] = &[
+    "h264_nvenc",
+    "hevc_nvenc",
+    "av1_nvenc",
+    "h264_qsv",
+    "hevc_qsv",
+    "av1_qsv",
+    "h264_amf",
+    "hevc_amf",
+    "av1_amf",
+    "h264_vaapi",
+    "hevc_vaapi",
+    "av1_vaapi",
+];
+
+/// What one ffmpeg binary can do on this machine.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    /// first line of `ffmpeg -version`.
+    pub version: String,
+    /// video encoders the build lists.
+    pub encoders: Vec<String>,
+    pub hwaccels: Vec<String>,
+    pub filters: Vec<String>,
+    /// listed hardware encoders that passed a test encode.
+    pub usable_hardware: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.iter().any(|e| e == name)
+    }
+
+    pub fn has_filter(&self, name: &str) -> bool {
+        self.filters.iter().any(|f| f == name)
+    }
+
+    /// Listed by the build and, for hardware encoders, working on this machine.
+    pub fn can_use(&self, encoder: &str) -> bool {
+        if is_hardware(encoder) {
+            self.usable_hardware.iter().any(|e| e == encoder)
+        } else {
+            self.has_encoder(encoder)
+        }
+    }
+
+    /// Why `encoder` can't be used, or `None` when it can.
+    pub fn unavailable_reason(&self, encoder: &str) -> Option<String> {
+        if self.can_use(encoder) {
+            return None;
+        }
+        if !self.has_encoder(encoder) {
+            return Some(format!("{encoder} is not included in this ffmpeg build"));
+        }
+
+        let mut msg = format!("{encoder} failed to start (no supported gpu or driver found)");
+        let others = self
+            .usable_hardware
+            .iter()
+            .filter(|e| *e != encoder)
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !others.is_empty() {
+            msg.push_str(&format!("; this machine has: {}", others.join(", ")));
+        }
+        Some(msg)
+    }
+}
+
+pub fn is_hardware(encoder: &str) -> bool {
+    HARDWARE_ENCODERS.contains(&encoder)
+}
+
+/// Detects what `ffmpeg` supports. The listings are cached for the same binary and version; the
+/// hardware test encodes are repeated once they're older than an hour.
+pub fn detect(ffmpeg: &Path) -> Result<Capabilities> {
+    detect_with(ffmpeg, false)
+}
+
+/// Like `detect`, but probes everything again; for `doctor`.
+pub fn detect_fresh(ffmpeg: &Path) -> Result<Capabilities> {
+    detect_with(ffmpeg, true)
+}
+
+fn detect_with(ffmpeg: &Path, fresh: bool) -> Result<Capabilities> {
+    static MEMO: OnceLock<Mutex<HashMap<PathBuf, Capabilities>>> = OnceLock::new();
+    let memo = MEMO.get_or_init(|| Mutex::new(HashMap::new()));
+    if !fresh && let Some(c) = memo.lock().unwrap().get(ffmpeg) {
+        return Ok(c.clone());
+    }
+
+    let version = run_stdout(ffmpeg, &["-version"])?
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    let key = cache_key(ffmpeg, &version);
+
+    let now = unix_now();
+    let cached = if fresh { None } else { load_cached(&key) };
+    let caps = match cached {
+        Some((caps, tested)) if now.saturating_sub(tested) < HARDWARE_TTL_SECS => caps,
+        Some((mut caps, _)) => {
+            caps.usable_hardware = test_hardware(ffmpeg, &caps.encoders);
+            // a failed cache write only costs a repeat probe next time.
+            let _ = store_cached(&key, &caps, now);
+            caps
+        }
+        None => {
+            let caps = probe_uncached(ffmpeg, version)?;
+            let _ = store_cached(&key, &caps, now);
+            caps
+        }
+    };
+
+    memo.lock()
+        .unwrap()
+        .insert(ffmpeg.to_path_buf(), caps.clone());
+    Ok(caps)
+}
+
+fn probe_uncached(ffmpeg: &Path, version: String) -> Result<Capabilities> {
+    let encoders = parse_encoders(&run_stdout(ffmpeg, &["-hide_banner", "-encoders"])?);
+    let hwaccels = parse_hwaccels(&run_stdout(ffmpeg, &["-hide_banner", "-hwaccels"])?);
+    let filters = parse_filters(&run_stdout(ffmpeg, &["-hide_banner", "-filters"])?);
+
+    let usable_hardware = test_hardware(ffmpeg, &encoders);
+
+    Ok(Capabilities {
+        version,
+        encoders,
+        hwaccels,
+        filters,
+        usable_hardware,
+    })
+}
+
+fn test_hardware(ffmpeg: &Path, encoders: &[String]) -> Vec<String> {
+    HARDWARE_ENCODERS
+        .iter()
+        .filter(|e| encoders.iter().any(|l| l == *e))
+        .filter(|e| test_encode(ffmpeg, e))
+        .map(|e| e.to_string())
+        .collect()
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn run_stdout(ffmpeg: &Path, args: &[&str]) -> Result<String> {
+    let output = crate::process::run::command(ffmpeg, args).output()?;
+    if !output.status.success() {
+        return Err(TinythisError::ProcessFailed {
+            program: ffmpeg.display().to_string(),
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Encodes a few blank frames; listed hardware encoders fail here without a matching gpu.
+fn test_encode(ffmpeg: &Path, name: &str) -> bool {
+    let Some(encoder) = crate::presets::encoder::Encoder::parse(name) else {
+        return false;
+    };
+    let mut args = vec![
+        OsString::from("-hide_banner"),
+        OsString::from("-nostdin"),
+        OsString::from("-loglevel"),
+        OsString::from("error"),
+    ];
+    args.extend(encoder.input_args());
+    args.extend([
+        OsString::from("-f"),
+        OsString::from("lavfi"),
+        OsString::from("-i"),
+        OsString::from("color=c=black:s=256x256:r=30:d=0.2"),
+    ]);
+    if let Some(filter) = encoder.video_filter() {
+        args.extend([OsString::from("-vf"), OsString::from(filter)]);
+    }
+    args.extend([
+        OsString::from("-c:v"),
+        OsString::from(name),
+        OsString::from("-f"),
+        OsString::from("null"),
+        OsString::from("-"),
+    ]);
+
+    crate::process::run::command(ffmpeg, &args)
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+fn parse_encoders(stdout: &str) -> Vec<String> {
+    // example: " V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC (codec h264)"
+    stdout
+        .lines()
+        .skip_while(|l| !l.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            let flags = parts.next()?;
+            let name = parts.next()?;
+            flags.starts_with('V').then(|| name.to_string())
+        })
+        .collect()
+}
+
+fn parse_hwaccels(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.ends_with(':'))
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_filters(stdout: &str) -> Vec<String> {
+    // example: " ... libvmaf           VV->V      Calculate the VMAF between two video streams."
+    stdout
+        .lines()
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            let flags = parts.next()?;
+            let name = parts.next()?;
+            let io = parts.next()?;
+            let is_filter = flags.len() == 3 && flags.chars().all(|c| "TSC.".contains(c));
+            (is_filter && io.contains("->")).then(|| name.to_string())
+        })
+        .collect()
+}
+
+fn cache_key(ffmpeg: &Path, version: &str) -> String {
+    let (len, modified) = std::fs::metadata(ffmpeg)
+        .map(|m| {
+            let modified = m
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            (m.len(), modified)
+        })
+        .unwrap_or((0, 0));
+    format!("{}|{len}|{modified}|{version}", ffmpeg.display())
+}
+
+fn cache_path() -> Result<PathBuf> {
+    Ok(crate::paths::app_root_dir()?.join(CACHE_FILE_NAME))
+}
+
+fn load_cached(key: &str) -> Option<(Capabilities, u64)> {
+    let text = std::fs::read_to_string(cache_path().ok()?).ok()?;
+    parse_cache(&text, key)
+}
+
+fn store_cached(key: &str, caps: &Capabilities, tested: u64) -> Result<()> {
+    let path = cache_path()?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let old = std::fs::read_to_string(&path).unwrap_or_default();
+    let text = replace_entry(&old, key, &format_cache(key, caps, tested));
+    // written aside and moved in, so a file starting at the same time never reads half of it.
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    tmp.as_file_mut().write_all(text.as_bytes())?;
+    tmp.persist(&path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// The cache with `entry` in place of `key`'s binary's: one entry per ffmpeg path, so switching
+/// between builds doesn't probe again, while an updated build replaces its old entry.
+fn replace_entry(text: &str, key: &str, entry: &str) -> String {
+    let binary = |k: &str| k.split('|').next().unwrap_or_default().to_string();
+    let mut out = String::new();
+    let mut keep = false;
+    for line in text.lines() {
+        if let Some(k) = line.strip_prefix("key = ") {
+            keep = binary(k) != binary(key);
+        }
+        if keep {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out.push_str(entry);
+    out
+}
+
+/// One binary's entry: `key`, `version`, one space-separated list per line, and when (unix
+/// seconds) the `usable` hardware was last test-encoded. Each entry starts at its `key` line.
+fn format_cache(key: &str, caps: &Capabilities, tested: u64) -> String {
+    format!(
+        "key = {key}\nversion = {}\nencoders = {}\nhwaccels = {}\nfilters = {}\nusable = {}\n\
+tested = {tested}\n",
+        caps.version,
+        caps.encoders.join(" "),
+        caps.hwaccels.join(" "),
+        caps.filters.join(" "),
+        caps.usable_hardware.join(" ")
+    )
+}
+
+/// `key`'s cached capabilities and when their hardware was tested; entries from before `tested`
+/// was written count as never tested.
+fn parse_cache(text: &str, key: &str) -> Option<(Capabilities, u64)> {
+    let mut caps = Capabilities::default();
+    let mut tested = 0;
+    let mut key_matches = false;
+    let mut found = false;
+
+    for line in text.lines() {
+        let Some((k, v)) = line.split_once(" = ").or_else(|| {
+            // empty lists are written as "name = " and trimmed by editors to "name =".
+            line.strip_suffix(" =").map(|k| (k, ""))
+        }) else {
+            continue;
+        };
+        let list = || v.split_whitespace().map(str::to_string).collect();
+        if k == "key" {
+            key_matches = v == key;
+            found |= key_matches;
+            continue;
+        }
+        if !key_matches {
+            continue;
+        }
+        match k {
+            "version" => caps.version = v.to_string(),
+            "encoders" => caps.encoders = list(),
+            "hwaccels" => caps.hwaccels = list(),
+            "filters" => caps.filters = list(),
+            "usable" => caps.usable_hardware = list(),
+            "tested" => tested = v.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+
+    found.then_some((caps, tested))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_encoder_hwaccel_and_filter_listings() {
+        let encoders = "\
+Encoders:
+ V..... = Video
+ A..... = Audio
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC (codec h264)
+ V....D h264_nvenc           NVIDIA NVENC H.264 encoder (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)
+";
+        assert_eq!(parse_encoders(encoders), vec!["libx264", "h264_nvenc"]);
+
+        let hwaccels = "Hardware acceleration methods:\ncuda\nd3d11va\n\n";
+        assert_eq!(parse_hwaccels(hwaccels), vec!["cuda", "d3d11va"]);
+
+        let filters = "\
+Filters:
+  T.. = Timeline support
+  | = Source or sink filter
+ ... libvmaf           VV->V      Calculate the VMAF between two video streams.
+ TSC scale             V->V       Scale the input video size.
+";
+        assert_eq!(parse_filters(filters), vec!["libvmaf", "scale"]);
+    }
+
+    #[test]
+    fn listed_hardware_encoders_need_a_working_test_encode() {
+        let caps = Capabilities {
+            encoders: vec!["libx264".into(), "h264_nvenc".into(), "h264_qsv".into()],
+            usable_hardware: vec!["h264_qsv".into()],
+            ..Default::default()
+        };
+        assert!(caps.can_use("libx264"));
+        assert!(caps.can_use("h264_qsv"));
+        assert!(!caps.can_use("h264_nvenc"));
+        assert_eq!(
+            caps.unavailable_reason("h264_nvenc").as_deref(),
+            Some(
+                "h264_nvenc failed to start (no supported gpu or driver found); this machine has: h264_qsv"
+            )
+        );
+        assert_eq!(
+            caps.unavailable_reason("h264_amf").as_deref(),
+            Some("h264_amf is not included in this ffmpeg build")
+        );
+    }
+
+    #[test]
+    fn cache_round_trips_only_for_the_same_key() {
+        let caps = Capabilities {
+            version: "ffmpeg version n7.1".into(),
+            encoders: vec!["libx264".into()],
+            hwaccels: vec![],
+            filters: vec!["libvmaf".into()],
+            usable_hardware: vec![],
+        };
+        let text = format_cache(
+            "C:\\ffmpeg.exe|1|2|ffmpeg version n7.1",
+            &caps,
+            1_700_000_000,
+        );
+        assert_eq!(
+            parse_cache(&text, "C:\\ffmpeg.exe|1|2|ffmpeg version n7.1"),
+            Some((caps.clone(), 1_700_000_000))
+        );
+        assert_eq!(parse_cache(&text, "C:\\other.exe|1|2|v"), None);
+
+        // written before test times were kept: the hardware gets tested again.
+        let old = text.replace("tested = 1700000000\n", "");
+        assert_eq!(
+            parse_cache(&old, "C:\\ffmpeg.exe|1|2|ffmpeg version n7.1"),
+            Some((caps.clone(), 0))
+        );
+    }
+
+    #[test]
+    fn cache_keeps_one_entry_per_binary() {
+        let caps = |version: &str| Capabilities {
+            version: version.into(),
+            encoders: vec!["libx264".into()],
+            ..Capabilities::default()
+        };
+        let near = "C:\\app\\ffmpeg.exe|1|2|v7";
+        let installed = "C:\\ffmpeg\\bin\\ffmpeg.exe|3|4|v6";
+        let text = replace_entry("", near, &format_cache(near, &caps("v7"), 10));
+        let text = replace_entry(&text, installed, &format_cache(installed, &caps("v6"), 20));
+        assert_eq!(parse_cache(&text, near), Some((caps("v7"), 10)));
+        assert_eq!(parse_cache(&text, installed), Some((caps("v6"), 20)));
+
+        // the same binary, updated: its old entry goes.
+        let updated = "C:\\app\\ffmpeg.exe|5|6|v8";
+        let text = replace_entry(&text, updated, &format_cache(updated, &caps("v8"), 30));
+        assert_eq!(parse_cache(&text, near), None);
+        assert_eq!(parse_cache(&text, updated), Some((caps("v8"), 30)));
+        assert_eq!(parse_cache(&text, installed), Some((caps("v6"), 20)));
+        assert_eq!(text.matches("key = ").count(), 2);
+    }
+}
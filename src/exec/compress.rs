@@ -5,16 +5,294 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::error::{Result, TinythisError};
+use crate::exec::container::Container;
+use crate::exec::crf_search::CrfSearch;
+use crate::exec::extra_args::{ExtraArgs, PresetArgs};
+use crate::exec::gif::GifSettings;
+use crate::exec::metrics::{Metric, QualityThreshold, Scores};
+use crate::exec::probe::{MediaInfo, StreamKind};
+use crate::exec::progress::{EncodeProgress, ProgressParser};
+use crate::exec::split::SplitLimit;
 use crate::presets::Preset;
+use crate::presets::auto::{AutoAction, AutoDecision};
+use crate::presets::encoder::{Backend, Codec, Encoder};
 
 #[derive(Debug, Clone)]
 pub struct SelectedFile {
     pub path: PathBuf,
     pub size_bytes: u64,
+    pub info: Option<MediaInfo>,
+    pub overrides: FileOverrides,
 }
 
-pub fn build_output_path(input: &Path, preset: Preset) -> Result<PathBuf> {
-    let parent = input.parent().unwrap_or_else(|| Path::new("."));
+/// Settings one file uses instead of the batch's; `None` keeps the batch value.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct FileOverrides {
+    pub preset: Option<Preset>,
+    pub encoder: Option<Encoder>,
+    pub trim_secs: Option<u32>,
+    pub max_height: Option<u32>,
+}
+
+impl FileOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(&self, settings: &EncodeSettings) -> EncodeSettings {
+        EncodeSettings {
+            preset: self.preset.unwrap_or(settings.preset),
+            encoder: self.encoder.unwrap_or(settings.encoder),
+            trim_secs: self.trim_secs.or(settings.trim_secs),
+            max_height: self.max_height.or(settings.max_height),
+            ..*settings
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EncodeSettings {
+    pub preset: Preset,
+    pub encoder: Encoder,
+    /// the format outputs are written in.
+    pub container: Container,
+    pub preserve: Preserve,
+    /// smallest expected size reduction (percent) the auto preset re-encodes for.
+    pub min_savings: u8,
+    /// search for the crf that reaches this vmaf score instead of using the preset's.
+    pub target_vmaf: Option<f64>,
+    /// overrides the preset's crf; set per file by the vmaf search.
+    pub crf: Option<u8>,
+    /// score each output against its input after encoding.
+    pub measure: bool,
+    /// outputs scoring below this are flagged in the report; implies `measure`.
+    pub min_quality: Option<QualityThreshold>,
+    /// retry a file on the cpu when the hardware encoder fails to start.
+    pub cpu_fallback: bool,
+    /// which presets decode (and convert) on the gpu when a hardware encoder is used.
+    pub hw_decode: HwDecode,
+    /// keep only the first this many seconds of the input.
+    pub trim_secs: Option<u32>,
+    /// scale taller videos down to this height, keeping the aspect ratio.
+    pub max_height: Option<u32>,
+    /// how the gif and webp presets animate.
+    pub gif: GifSettings,
+    /// cut outputs over this into numbered parts.
+    pub split: Option<SplitLimit>,
+}
+
+impl EncodeSettings {
+    fn needs_probe(&self) -> bool {
+        matches!(self.preset, Preset::Remux | Preset::Auto)
+            || self.target_vmaf.is_some()
+            || self.measure
+            || self.preserve.needs_probe()
+            || self.uses_hw_decode()
+    }
+
+    /// hardware decoding is picked per file from the probed codec, so it needs a probe.
+    ///
+    /// the downscale runs as a cpu filter, so it turns hardware decoding off.
+    fn uses_hw_decode(&self) -> bool {
+        self.encoder.is_hardware()
+            && self.hw_decode.for_preset(self.preset)
+            && self.max_height.is_none()
+    }
+
+    /// The `scale` filter for `max_height`; shorter videos are left alone.
+    pub fn scale_filter(&self) -> Option<String> {
+        self.max_height.map(|h| format!("scale=-2:min(ih\\,{h})"))
+    }
+}
+
+/// Settings for one input after `Preset::Auto` has been resolved.
+#[derive(Debug, Clone)]
+pub struct FilePlan {
+    pub settings: EncodeSettings,
+    pub info: Option<MediaInfo>,
+    pub decision: Option<AutoDecision>,
+    pub crf_search: Option<CrfSearch>,
+    /// the user's own ffmpeg options for the resolved preset.
+    pub extra_args: ExtraArgs,
+    /// the hardware encoder that failed to start, when the file was retried on the cpu.
+    pub fallback: Option<Encoder>,
+    /// where the encode writes ffmpeg's full output; `compress_file` picks one unless the caller
+    /// already has.
+    pub log: Option<PathBuf>,
+    /// what the output was cut into by `split::split_output`, in order; empty when it wasn't.
+    pub parts: Vec<PathBuf>,
+}
+
+impl FilePlan {
+    pub fn is_skip(&self) -> bool {
+        self.decision
+            .as_ref()
+            .is_some_and(|d| d.action == AutoAction::Skip)
+    }
+}
+
+/// What to carry over from the input besides the main video/audio streams.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Preserve {
+    pub metadata: bool,
+    pub chapters: bool,
+    pub creation_time: bool,
+    pub rotation: bool,
+    pub subtitles: bool,
+    pub timestamps: bool,
+}
+
+impl Default for Preserve {
+    fn default() -> Self {
+        // matches what ffmpeg does on its own, so outputs stay the same unless opted in.
+        Self {
+            metadata: true,
+            chapters: true,
+            creation_time: true,
+            rotation: false,
+            subtitles: false,
+            timestamps: false,
+        }
+    }
+}
+
+impl Preserve {
+    pub fn all(value: bool) -> Self {
+        Self {
+            metadata: value,
+            chapters: value,
+            creation_time: value,
+            rotation: value,
+            subtitles: value,
+            timestamps: value,
+        }
+    }
+
+    fn needs_probe(&self) -> bool {
+        self.subtitles || (self.creation_time && !self.metadata)
+    }
+}
+
+/// Per-preset switch for decoding on the gpu; off by default since it only pays off
+/// when decoding is the bottleneck.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct HwDecode {
+    pub quality: bool,
+    pub balanced: bool,
+    pub speed: bool,
+}
+
+impl HwDecode {
+    pub fn all(value: bool) -> Self {
+        Self {
+            quality: value,
+            balanced: value,
+            speed: value,
+        }
+    }
+
+    /// remux and auto re-encode like balanced; animations are filtered on the cpu anyway.
+    pub fn for_preset(&self, preset: Preset) -> bool {
+        match preset {
+            Preset::Quality => self.quality,
+            Preset::Speed => self.speed,
+            Preset::Balanced | Preset::Remux | Preset::Auto => self.balanced,
+            Preset::Gif | Preset::Webp => false,
+        }
+    }
+}
+
+/// What to do when the output name is already taken.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum OnExisting {
+    /// add `.2`, `.3`, ... to the new file.
+    #[default]
+    Rename,
+    Overwrite,
+}
+
+impl OnExisting {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OnExisting::Rename => "rename",
+            OnExisting::Overwrite => "overwrite",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "rename" => Some(OnExisting::Rename),
+            "overwrite" => Some(OnExisting::Overwrite),
+            _ => None,
+        }
+    }
+}
+
+/// Where outputs go and what they're called.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputNaming {
+    /// folder for outputs; next to the input when unset.
+    pub dir: Option<PathBuf>,
+    /// file name without the extension; `{stem}` and `{preset}` are filled in.
+    pub template: String,
+    pub on_existing: OnExisting,
+}
+
+impl Default for OutputNaming {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            template: Self::DEFAULT_TEMPLATE.to_string(),
+            on_existing: OnExisting::Rename,
+        }
+    }
+}
+
+impl OutputNaming {
+    pub const DEFAULT_TEMPLATE: &str = "{stem}.tinythis.{preset}";
+
+    /// Checks a template before it's saved: it needs `{stem}` and nothing that makes a path.
+    pub fn validate_template(template: &str) -> std::result::Result<(), String> {
+        if !template.contains("{stem}") {
+            return Err("needs {stem}, or every output gets the same name".to_string());
+        }
+        if template.contains(['/', '\\', ':']) {
+            return Err("a file name can't contain / \\ or :".to_string());
+        }
+        // options.toml keeps it in a literal string and cuts comments at `#`.
+        if template.contains(['\'', '#']) {
+            return Err("can't contain ' or #".to_string());
+        }
+        let rest = template.replace("{stem}", "").replace("{preset}", "");
+        if rest.contains(['{', '}']) {
+            return Err("only {stem} and {preset} can be filled in".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub fn build_output_path(
+    input: &Path,
+    preset: Preset,
+    container: Container,
+    naming: &OutputNaming,
+) -> Result<PathBuf> {
+    build_output_path_except(input, preset, container, naming, &[])
+}
+
+/// Like `build_output_path`, but never one of `taken`: names other files of the batch got
+/// before their encode created them.
+pub fn build_output_path_except(
+    input: &Path,
+    preset: Preset,
+    container: Container,
+    naming: &OutputNaming,
+    taken: &[PathBuf],
+) -> Result<PathBuf> {
+    let parent = match &naming.dir {
+        Some(dir) => dir.as_path(),
+        None => input.parent().unwrap_or_else(|| Path::new(".")),
+    };
     let stem = input.file_stem().ok_or_else(|| {
         TinythisError::Io(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
@@ -22,15 +300,30 @@ pub fn build_output_path(input: &Path, preset: Preset) -> Result<PathBuf> {
         ))
     })?;
 
-    let base = format!("{}.tinythis.{}", stem.to_string_lossy(), preset.as_str());
-    let mut candidate = parent.join(format!("{base}.mp4"));
-    if !candidate.exists() {
+    let base = naming
+        .template
+        .replace("{stem}", &stem.to_string_lossy())
+        .replace("{preset}", preset.as_str());
+    let ext = match preset {
+        Preset::Gif | Preset::Webp => preset.as_str(),
+        _ => container.as_str(),
+    };
+    let mut candidate = parent.join(format!("{base}.{ext}"));
+    // never write over the file being read, whatever the template says.
+    let is_input = |p: &Path| {
+        p == input
+            || std::fs::canonicalize(p)
+                .is_ok_and(|p| std::fs::canonicalize(input).is_ok_and(|i| i == p))
+    };
+    let overwrite = naming.on_existing == OnExisting::Overwrite;
+    let is_taken = |p: &Path| taken.iter().any(|t| t == p);
+    if !is_taken(&candidate) && (!candidate.exists() || (overwrite && !is_input(&candidate))) {
         return Ok(candidate);
     }
 
     for n in 2u32.. {
-        candidate = parent.join(format!("{base}.{n}.mp4"));
-        if !candidate.exists() {
+        candidate = parent.join(format!("{base}.{n}.{ext}"));
+        if !candidate.exists() && !is_taken(&candidate) {
             return Ok(candidate);
         }
     }
@@ -41,9 +334,12 @@ pub fn build_output_path(input: &Path, preset: Preset) -> Result<PathBuf> {
 pub fn build_ffmpeg_args(
     input: &Path,
     output: &Path,
-    preset: Preset,
-    use_gpu: bool,
+    settings: &EncodeSettings,
+    info: Option<&MediaInfo>,
 ) -> Vec<OsString> {
+    let preset = settings.preset;
+    let preserve = settings.preserve;
+    let container = settings.container;
     let mut args = Vec::<OsString>::new();
 
     args.extend([
@@ -51,6 +347,50 @@ pub fn build_ffmpeg_args(
         OsString::from("-nostdin"),
         OsString::from("-nostats"),
         OsString::from("-y"),
+    ]);
+    if preset.is_animated() {
+        // just the picture: no sound, subtitles or tags.
+        args.extend([
+            OsString::from("-i"),
+            input.as_os_str().to_owned(),
+            OsString::from("-map"),
+            OsString::from("0:v:0"),
+            OsString::from("-an"),
+            OsString::from("-sn"),
+            OsString::from("-map_metadata"),
+            OsString::from("-1"),
+            OsString::from("-map_chapters"),
+            OsString::from("-1"),
+        ]);
+        if let Some(secs) = settings.trim_secs {
+            args.extend([OsString::from("-t"), OsString::from(secs.to_string())]);
+        }
+        args.extend(crate::exec::gif::output_args(
+            preset,
+            &settings.gif,
+            settings.max_height,
+        ));
+        args.push(output.as_os_str().to_owned());
+        return args;
+    }
+    if preserve.rotation {
+        // keep the rotation flag instead of baking it into the pixels.
+        args.push(OsString::from("-noautorotate"));
+    }
+    let copyable = info
+        .and_then(|i| copyable_video(i, container))
+        .filter(|_| preset == Preset::Remux && settings.max_height.is_none());
+    let copies_video = copyable.is_some();
+    let encoder = settings.encoder;
+    let hw_decode = !copies_video
+        && settings.uses_hw_decode()
+        && info.is_some_and(|i| hw_decodable(encoder, i, preserve));
+    if hw_decode {
+        args.extend(encoder.hw_decode_input_args());
+    } else if !copies_video {
+        args.extend(encoder.input_args());
+    }
+    args.extend([
         OsString::from("-i"),
         input.as_os_str().to_owned(),
         OsString::from("-map"),
@@ -59,33 +399,512 @@ pub fn build_ffmpeg_args(
         OsString::from("0:a?"),
     ]);
 
-    args.extend(crate::presets::ffmpeg_video_args(preset, use_gpu));
+    let subtitle_codec = container.text_subtitle_codec();
+    let subtitles = match subtitle_codec {
+        Some(_) if preserve.subtitles => text_subtitle_streams(info),
+        _ => Vec::new(),
+    };
+    for idx in &subtitles {
+        args.extend([OsString::from("-map"), OsString::from(format!("0:{idx}"))]);
+    }
+    let copies_subtitles = preserve.subtitles && subtitle_codec.is_none();
+    if copies_subtitles {
+        // mkv holds any subtitle, and the fonts styled ones are drawn with.
+        args.extend([
+            OsString::from("-map"),
+            OsString::from("0:s?"),
+            OsString::from("-map"),
+            OsString::from("0:t?"),
+        ]);
+    }
 
     args.extend([
-        OsString::from("-pix_fmt"),
-        OsString::from("yuv420p"),
-        OsString::from("-movflags"),
-        OsString::from("+faststart"),
+        OsString::from("-map_metadata"),
+        OsString::from(if preserve.metadata { "0" } else { "-1" }),
+        OsString::from("-map_chapters"),
+        OsString::from(if preserve.chapters { "0" } else { "-1" }),
     ]);
+    if !preserve.creation_time {
+        args.extend([
+            OsString::from("-metadata"),
+            OsString::from("creation_time="),
+        ]);
+    } else if !preserve.metadata
+        && let Some(ct) = info.and_then(|i| i.creation_time.as_deref())
+    {
+        args.extend([
+            OsString::from("-metadata"),
+            OsString::from(format!("creation_time={ct}")),
+        ]);
+    }
 
-    args.extend([
-        OsString::from("-c:a"),
-        OsString::from("aac"),
-        OsString::from("-b:a"),
-        OsString::from(crate::presets::audio_bitrate(preset)),
-    ]);
+    if let Some(secs) = settings.trim_secs {
+        args.extend([OsString::from("-t"), OsString::from(secs.to_string())]);
+    }
+
+    let remux = preset == Preset::Remux;
+    // players on apple devices only accept hevc tagged hvc1; matroska refuses that tag.
+    let hvc1 = [OsString::from("-tag:v"), OsString::from("hvc1")];
+    let takes_hvc1 = matches!(container, Container::Mp4 | Container::Mov);
+    match copyable {
+        Some(codec) => {
+            args.extend([OsString::from("-c:v"), OsString::from("copy")]);
+            if codec == "hevc" && takes_hvc1 {
+                args.extend(hvc1);
+            }
+        }
+        None => {
+            // frames decoded on the gpu are converted there; amf gets them back in memory.
+            let on_device = if hw_decode {
+                encoder.hw_decode_filter()
+            } else {
+                None
+            };
+            let filters = match on_device {
+                Some(f) => vec![f.to_string()],
+                None => settings
+                    .scale_filter()
+                    .into_iter()
+                    .chain(encoder.video_filter().map(str::to_string))
+                    .collect(),
+            };
+            if !filters.is_empty() {
+                args.extend([OsString::from("-vf"), OsString::from(filters.join(","))]);
+            }
+            args.extend(encoder.video_args(preset, settings.crf));
+            if encoder.codec == Codec::Hevc && takes_hvc1 {
+                args.extend(hvc1);
+            }
+            if let Some(SplitLimit::Duration(secs)) = settings.split {
+                // keyframes right where the parts start, so they all run the full length.
+                args.extend([
+                    OsString::from("-force_key_frames"),
+                    OsString::from(format!("expr:gte(t,n_forced*{secs})")),
+                ]);
+            }
+            if on_device.is_none()
+                && let Some(pix_fmt) = encoder.pix_fmt()
+            {
+                args.extend([OsString::from("-pix_fmt"), OsString::from(pix_fmt)]);
+            }
+        }
+    }
+    if container.faststart() {
+        args.extend([OsString::from("-movflags"), OsString::from("+faststart")]);
+    }
+
+    let bitrate = crate::presets::audio_bitrate(preset);
+    match info.filter(|_| remux) {
+        Some(info) => {
+            for (n, a) in info.streams_of(StreamKind::Audio).enumerate() {
+                if container.copies_audio(&a.codec) {
+                    args.extend([OsString::from(format!("-c:a:{n}")), OsString::from("copy")]);
+                } else {
+                    args.extend([
+                        OsString::from(format!("-c:a:{n}")),
+                        OsString::from(container.audio_encoder()),
+                        OsString::from(format!("-b:a:{n}")),
+                        OsString::from(bitrate),
+                    ]);
+                }
+            }
+        }
+        None => {
+            args.extend([
+                OsString::from("-c:a"),
+                OsString::from(container.audio_encoder()),
+                OsString::from("-b:a"),
+                OsString::from(bitrate),
+            ]);
+        }
+    }
+
+    match subtitle_codec {
+        Some(codec) if !subtitles.is_empty() => {
+            args.extend([OsString::from("-c:s"), OsString::from(codec)]);
+        }
+        None if copies_subtitles => {
+            args.extend([OsString::from("-c:s"), OsString::from("copy")]);
+        }
+        _ => {}
+    }
 
     args.push(output.as_os_str().to_owned());
     args
 }
 
+/// Probes `input` when the settings need it, resolves `Preset::Auto` into a concrete preset
+/// and runs the vmaf crf search when a target is set.
+///
+/// `info` is reused when the caller already probed the file.
+pub fn plan_file(
+    ffmpeg: &Path,
+    input: &Path,
+    settings: &EncodeSettings,
+    extra: &PresetArgs,
+    info: Option<MediaInfo>,
+) -> Result<FilePlan> {
+    let info = match info {
+        Some(i) => Some(i),
+        None if settings.needs_probe() => Some(crate::exec::probe::probe(ffmpeg, input)?),
+        None => None,
+    };
+
+    let mut settings = *settings;
+    let mut decision = None;
+    if settings.preset == Preset::Auto
+        && let Some(info) = info.as_ref()
+    {
+        let d = crate::presets::auto::decide(input, info, settings.min_savings, settings.container);
+        if let AutoAction::Encode(p) = d.action {
+            settings.preset = p;
+        }
+        decision = Some(d);
+    }
+
+    let skip = decision
+        .as_ref()
+        .is_some_and(|d| d.action == AutoAction::Skip);
+    // a remux that copies the video never runs the encoder, whatever the container.
+    if !skip && info.as_ref().is_none_or(|i| reencodes_video(&settings, i)) {
+        settings
+            .container
+            .check(settings.encoder)
+            .map_err(TinythisError::InvalidArgs)?;
+    }
+
+    let mut crf_search = None;
+    if let Some(target) = settings.target_vmaf
+        && let Some(info) = info.as_ref()
+        && reencodes_video(&settings, info)
+        && !skip
+    {
+        let found = crate::exec::crf_search::search(ffmpeg, input, &settings, info, target)?;
+        settings.crf = Some(found.crf);
+        crf_search = Some(found);
+    }
+
+    Ok(FilePlan {
+        settings,
+        info,
+        decision,
+        crf_search,
+        extra_args: extra.for_preset(settings.preset).clone(),
+        fallback: None,
+        log: None,
+        parts: Vec::new(),
+    })
+}
+
+/// Plans `input` like `plan_file` without encoding anything: a vmaf target only picks up a crf
+/// the search has already cached.
+pub fn plan_dry(
+    ffmpeg: &Path,
+    input: &Path,
+    settings: &EncodeSettings,
+    extra: &PresetArgs,
+    info: Option<MediaInfo>,
+) -> Result<FilePlan> {
+    let no_search = EncodeSettings {
+        target_vmaf: None,
+        ..*settings
+    };
+    let mut plan = plan_file(ffmpeg, input, &no_search, extra, info)?;
+    plan.settings.target_vmaf = settings.target_vmaf;
+    if let Some(target) = settings.target_vmaf
+        && !plan.is_skip()
+    {
+        plan.crf_search = crate::exec::crf_search::cached(input, &plan.settings, target);
+        plan.settings.crf = plan.crf_search.map(|found| found.crf);
+    }
+    Ok(plan)
+}
+
+/// Everything after `ffmpeg` in the command `compress_file` runs for these settings.
+pub fn encode_args(
+    input: &Path,
+    output: &Path,
+    settings: &EncodeSettings,
+    info: Option<&MediaInfo>,
+    extra: &ExtraArgs,
+) -> Vec<OsString> {
+    let mut args = build_ffmpeg_args(input, output, settings, info);
+    extra.apply(&mut args);
+    args.extend([OsString::from("-progress"), OsString::from("pipe:1")]);
+    args
+}
+
+/// Encodes `input` into `output`, then applies any post-processing the settings ask for.
+///
+/// With `cpu_fallback`, a hardware encoder that fails to start is replaced by the cpu one and
+/// the file encoded again, unless extra args were given; `plan` records the switch for the report.
+pub fn compress_file(
+    ffmpeg: &Path,
+    input: &Path,
+    output: &Path,
+    plan: &mut FilePlan,
+    on_progress: impl FnMut(&EncodeProgress) + Clone + Send + 'static,
+) -> Result<()> {
+    // ffmpeg reports the input's full length; a trimmed output ends sooner.
+    let duration_us = plan
+        .settings
+        .trim_secs
+        .and_then(|_| output_length_us(plan.info.as_ref(), plan.settings.trim_secs));
+    if plan.log.is_none() {
+        plan.log = crate::exec::joblog::path_for(input).ok();
+    }
+    let encode = |settings: &EncodeSettings, on_progress| {
+        let args = encode_args(
+            input,
+            output,
+            settings,
+            plan.info.as_ref(),
+            &plan.extra_args,
+        );
+        run_ffmpeg(ffmpeg, &args, duration_us, plan.log.as_deref(), on_progress)
+    };
+
+    let settings = plan.settings;
+    log::info!(
+        "encoding {} -> {} ({}, {})",
+        input.display(),
+        output.display(),
+        settings.preset.as_str(),
+        settings.encoder.ffmpeg_name()
+    );
+    if !plan.extra_args.is_empty() {
+        log::info!(
+            "extra ffmpeg args: input {:?}, output {:?}",
+            plan.extra_args.input,
+            plan.extra_args.output
+        );
+    }
+    if settings.preset.is_animated() {
+        // without a size target this runs once.
+        let mut current = settings;
+        encode(&current, on_progress.clone())?;
+        while let Some(max) = current.gif.max_bytes
+            && std::fs::metadata(output)?.len() > max
+        {
+            let size = crate::exec::report::format_bytes(max);
+            let Some(gif) = current.gif.smaller() else {
+                log::warn!(
+                    "{} is still over {size} at {} fps and {} px wide",
+                    output.display(),
+                    current.gif.fps,
+                    current.gif.width
+                );
+                break;
+            };
+            log::info!(
+                "{} is over {size}, retrying at {} fps and {} px wide",
+                output.display(),
+                gif.fps,
+                gif.width
+            );
+            current.gif = gif;
+            encode(&current, on_progress.clone())?;
+        }
+        plan.settings = current;
+    } else {
+        match encode(&settings, on_progress.clone()) {
+            Err(TinythisError::ProcessFailed { ref stderr, .. })
+                if settings.cpu_fallback
+                    && settings.encoder.is_hardware()
+                    // extra args are usually tuned for the encoder that just failed.
+                    && plan.extra_args.is_empty()
+                    && is_encoder_init_failure(stderr) =>
+            {
+                log::warn!(
+                    "{} failed to start, retrying {} on the cpu",
+                    settings.encoder.ffmpeg_name(),
+                    input.display()
+                );
+                // the cpu default is h264; a container that can't hold it keeps the codec.
+                let encoder = if settings.container.holds(Encoder::CPU.codec) {
+                    Encoder::CPU
+                } else {
+                    Encoder {
+                        backend: Backend::Cpu,
+                        codec: settings.encoder.codec,
+                    }
+                };
+                let mut cpu = EncodeSettings {
+                    encoder,
+                    ..settings
+                };
+                // a searched crf was tuned for the hardware encoder; the cpu one needs its own.
+                if let Some(target) = settings.target_vmaf
+                    && plan.crf_search.is_some()
+                    && let Some(info) = plan.info.as_ref()
+                {
+                    cpu.crf = None;
+                    let found = crate::exec::crf_search::search(ffmpeg, input, &cpu, info, target)?;
+                    cpu.crf = Some(found.crf);
+                    plan.crf_search = Some(found);
+                }
+                encode(&cpu, on_progress)?;
+                plan.fallback = Some(settings.encoder);
+                plan.settings = cpu;
+            }
+            res => res?,
+        }
+    }
+
+    if settings.preserve.timestamps {
+        copy_file_times(input, output)?;
+    }
+    Ok(())
+}
+
+/// How long the output runs: the input's length, cut short by `trim_secs`.
+pub fn output_length_us(info: Option<&MediaInfo>, trim_secs: Option<u32>) -> Option<u64> {
+    let full = info.and_then(|i| i.duration_us);
+    let Some(secs) = trim_secs else {
+        return full;
+    };
+    let trimmed = u64::from(secs) * 1_000_000;
+    Some(full.map_or(trimmed, |full| full.min(trimmed)))
+}
+
+/// Whether ffmpeg's stderr says the video encoder couldn't start, as opposed to a broken input.
+///
+/// Covers a missing or too old driver, no matching gpu and nvenc's concurrent session limit.
+pub fn is_encoder_init_failure(stderr: &str) -> bool {
+    // only driver, device and session errors: "Error while opening encoder" also follows a bad
+    // -preset or -tune.
+    const MARKERS: &[&str] = &[
+        "OpenEncodeSessionEx failed",
+        "No capable devices found",
+        "Driver does not support the required nvenc API version",
+        "minimum required Nvidia driver",
+        "Cannot load nvcuda.dll",
+        "Cannot load libcuda.so",
+        "Cannot load libnvidia-encode",
+        "Error creating a MFX session",
+        "Error initializing an internal MFX session",
+        "Failed to initialise VAAPI connection",
+        "No VA display found",
+        "Failed to create a VAAPI device",
+        "AMF failed to initialise",
+        "hwaccel initialisation returned error",
+        "DLL amfrt64.dll failed to open",
+    ];
+    stderr
+        .lines()
+        .any(|l| MARKERS.iter().any(|m| l.contains(m)))
+}
+
+/// Scores `output` against `input` when the settings ask for it.
+///
+/// vmaf is included when the ffmpeg build has libvmaf, and required when the threshold uses it.
+pub fn measure_output(
+    ffmpeg: &Path,
+    input: &Path,
+    output: &Path,
+    plan: &FilePlan,
+) -> Result<Option<Scores>> {
+    let settings = &plan.settings;
+    // an animation has other frames and sizes than its input, so there's nothing to compare.
+    if !settings.measure || settings.preset.is_animated() {
+        return Ok(None);
+    }
+
+    let needs_vmaf = settings
+        .min_quality
+        .is_some_and(|t| t.metric == Metric::Vmaf);
+    let fps = plan
+        .info
+        .as_ref()
+        .and_then(|i| i.streams_of(StreamKind::Video).next())
+        .and_then(|v| v.fps);
+    let with_vmaf = needs_vmaf || crate::exec::metrics::has_libvmaf(ffmpeg);
+    crate::exec::metrics::measure(ffmpeg, input, output, fps, settings.trim_secs, with_vmaf)
+        .map(Some)
+}
+
+/// Whether every stream the remux preset maps can be copied into `container` untouched.
+pub fn can_remux(info: &MediaInfo, container: Container) -> bool {
+    copyable_video(info, container).is_some()
+        && info
+            .streams_of(StreamKind::Audio)
+            .all(|a| container.copies_audio(&a.codec))
+}
+
+fn reencodes_video(settings: &EncodeSettings, info: &MediaInfo) -> bool {
+    match settings.preset {
+        Preset::Remux => copyable_video(info, settings.container).is_none(),
+        // animations are written by their own encoders, not `settings.encoder`.
+        Preset::Gif | Preset::Webp => false,
+        _ => info.streams_of(StreamKind::Video).next().is_some(),
+    }
+}
+
+/// Whether the first video stream can stay on the gpu from decoder to encoder.
+fn hw_decodable(encoder: Encoder, info: &MediaInfo, preserve: Preserve) -> bool {
+    let Some(video) = info.streams_of(StreamKind::Video).next() else {
+        return false;
+    };
+    // autorotate inserts a software transpose, which can't take gpu frames.
+    let autorotates = video.rotation.is_some() && !preserve.rotation;
+    !autorotates && encoder.can_hw_decode(&video.codec, video.pix_fmt.as_deref())
+}
+
+/// Returns the codec of the main video stream when `container` can hold it as-is.
+fn copyable_video(info: &MediaInfo, container: Container) -> Option<&str> {
+    let v = info.streams_of(StreamKind::Video).next()?;
+    container.copies_video(&v.codec).then_some(v.codec.as_str())
+}
+
+/// mp4, mov and webm can only carry text subtitles (converted); bitmap ones like pgs are skipped.
+fn text_subtitle_streams(info: Option<&MediaInfo>) -> Vec<usize> {
+    let Some(info) = info else {
+        return Vec::new();
+    };
+    info.streams_of(StreamKind::Subtitle)
+        .filter(|s| {
+            matches!(
+                s.codec.as_str(),
+                "subrip" | "srt" | "ass" | "ssa" | "mov_text" | "webvtt" | "text"
+            )
+        })
+        .map(|s| s.index)
+        .collect()
+}
+
+pub(crate) fn copy_file_times(src: &Path, dest: &Path) -> Result<()> {
+    let meta = std::fs::metadata(src)?;
+    let times = std::fs::FileTimes::new()
+        .set_modified(meta.modified()?)
+        .set_accessed(meta.accessed()?);
+
+    #[cfg(windows)]
+    let times = {
+        use std::os::windows::fs::FileTimesExt;
+
+        match meta.created() {
+            Ok(created) => times.set_created(created),
+            Err(_) => times,
+        }
+    };
+
+    let file = std::fs::OpenOptions::new().write(true).open(dest)?;
+    file.set_times(times)?;
+    Ok(())
+}
+
+/// Runs an encode, reporting progress against `duration_us` or, without it, the input's length.
+///
+/// All of stderr goes to `log` when given; the error only carries the last lines.
 pub fn run_ffmpeg(
     ffmpeg: &Path,
     args: &[OsString],
-    mut on_percent: impl FnMut(u8) + Send + 'static,
+    duration_us: Option<u64>,
+    log: Option<&Path>,
+    mut on_progress: impl FnMut(&EncodeProgress) + Send + 'static,
 ) -> Result<()> {
-    let mut cmd = std::process::Command::new(ffmpeg);
-    cmd.args(args).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut cmd = crate::process::run::command(ffmpeg, args);
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
     let mut child = cmd.spawn()?;
 
@@ -98,11 +917,13 @@ pub fn run_ffmpeg(
         .take()
         .ok_or_else(|| TinythisError::Io(std::io::Error::other("missing stderr")))?;
 
-    let total_us = Arc::new(AtomicU64::new(0));
+    let total_us = Arc::new(AtomicU64::new(duration_us.unwrap_or(0)));
     let stderr_tail = Arc::new(std::sync::Mutex::new(
         std::collections::VecDeque::<String>::new(),
     ));
 
+    // a log that can't be opened only costs the full output.
+    let mut log_file = log.and_then(|p| crate::exec::joblog::open(p, ffmpeg, args).ok());
     let total_us_stderr = Arc::clone(&total_us);
     let stderr_tail_stderr = Arc::clone(&stderr_tail);
     let stderr_thread = std::thread::spawn(move || {
@@ -110,6 +931,9 @@ pub fn run_ffmpeg(
 
         let reader = std::io::BufReader::new(stderr);
         for line in reader.lines().map_while(|r| r.ok()) {
+            if let Some(f) = log_file.as_mut() {
+                let _ = f.line(&line);
+            }
             if total_us_stderr.load(Ordering::Relaxed) == 0
                 && let Some(us) = parse_duration_us_from_stderr_line(&line)
             {
@@ -129,52 +953,10 @@ pub fn run_ffmpeg(
         use std::io::BufRead;
 
         let reader = std::io::BufReader::new(stdout);
-        let mut last_pct: Option<u8> = None;
-        let mut seen_end = false;
-
+        let mut parser = ProgressParser::default();
         for line in reader.lines().map_while(|r| r.ok()) {
-            let (key, val) = match line.split_once('=') {
-                Some(kv) => kv,
-                None => continue,
-            };
-
-            match key {
-                "progress" => {
-                    if val.trim() == "end" {
-                        seen_end = true;
-                        if last_pct != Some(100) {
-                            on_percent(100);
-                            last_pct = Some(100);
-                        }
-                    }
-                }
-                "out_time_us" => {
-                    if let Ok(out_us) = val.trim().parse::<u64>()
-                        && let Some(pct) = compute_percent(
-                            out_us,
-                            total_us_stdout.load(Ordering::Relaxed),
-                            seen_end,
-                        )
-                        && last_pct != Some(pct)
-                    {
-                        on_percent(pct);
-                        last_pct = Some(pct);
-                    }
-                }
-                "out_time_ms" => {
-                    if let Ok(out_us) = val.trim().parse::<u64>()
-                        && let Some(pct) = compute_percent(
-                            out_us,
-                            total_us_stdout.load(Ordering::Relaxed),
-                            seen_end,
-                        )
-                        && last_pct != Some(pct)
-                    {
-                        on_percent(pct);
-                        last_pct = Some(pct);
-                    }
-                }
-                _ => {}
+            if let Some(p) = parser.feed(&line, total_us_stdout.load(Ordering::Relaxed)) {
+                on_progress(&p);
             }
         }
     });
@@ -188,7 +970,10 @@ pub fn run_ffmpeg(
     }
 
     let tail = stderr_tail.lock().unwrap();
-    let stderr = tail.iter().cloned().collect::<Vec<_>>().join("\n");
+    let mut stderr = tail.iter().cloned().collect::<Vec<_>>().join("\n");
+    if let Some(log) = log {
+        stderr.push_str(&format!("\nfull log: {}", log.display()));
+    }
     Err(TinythisError::ProcessFailed {
         program: ffmpeg.display().to_string(),
         code: status.code(),
@@ -196,22 +981,7 @@ pub fn run_ffmpeg(
     })
 }
 
-fn compute_percent(out_us: u64, total_us: u64, seen_end: bool) -> Option<u8> {
-    if total_us == 0 {
-        return None;
-    }
-    let raw = ((out_us as u128) * 100u128) / (total_us as u128);
-    let mut pct = raw.min(100) as u8;
-    if !seen_end && pct == 100 {
-        pct = 99;
-    }
-    if !seen_end {
-        pct = pct.min(99);
-    }
-    Some(pct)
-}
-
-fn parse_duration_us_from_stderr_line(line: &str) -> Option<u64> {
+pub(crate) fn parse_duration_us_from_stderr_line(line: &str) -> Option<u64> {
     // example: "  Duration: 00:00:08.05, start: 0.000000, bitrate: ..."
     let idx = line.find("Duration: ")?;
     let after = &line[idx + "Duration: ".len()..];
@@ -249,31 +1019,448 @@ fn parse_hhmmss_to_us(s: &str) -> Option<u64> {
 mod tests {
     use super::*;
 
+    fn settings(encoder: Encoder, preserve: Preserve) -> EncodeSettings {
+        EncodeSettings {
+            preset: Preset::Balanced,
+            encoder,
+            container: Container::Mp4,
+            preserve,
+            min_savings: 20,
+            target_vmaf: None,
+            crf: None,
+            measure: false,
+            min_quality: None,
+            cpu_fallback: false,
+            hw_decode: HwDecode::default(),
+            trim_secs: None,
+            max_height: None,
+            gif: GifSettings::default(),
+            split: None,
+        }
+    }
+
+    fn joined(args: &[OsString]) -> String {
+        args.iter()
+            .map(|v| v.to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     #[test]
     fn ffmpeg_args_selects_codec_based_on_gpu_flag() {
         let input = Path::new("C:\\in.mp4");
         let output = Path::new("C:\\out.mp4");
 
-        let cpu = build_ffmpeg_args(input, output, Preset::Balanced, false);
-        let cpu_s = cpu
-            .iter()
-            .map(|v| v.to_string_lossy().into_owned())
-            .collect::<Vec<_>>()
-            .join(" ");
+        let cpu = build_ffmpeg_args(
+            input,
+            output,
+            &settings(Encoder::CPU, Preserve::default()),
+            None,
+        );
+        let cpu_s = joined(&cpu);
         assert!(cpu_s.contains("libx264"));
         assert!(cpu_s.contains(" -crf "));
         assert!(!cpu_s.contains("h264_nvenc"));
 
-        let gpu = build_ffmpeg_args(input, output, Preset::Balanced, true);
-        let gpu_s = gpu
-            .iter()
-            .map(|v| v.to_string_lossy().into_owned())
-            .collect::<Vec<_>>()
-            .join(" ");
+        let gpu = build_ffmpeg_args(
+            input,
+            output,
+            &settings(Encoder::NVENC, Preserve::default()),
+            None,
+        );
+        let gpu_s = joined(&gpu);
         assert!(gpu_s.contains("h264_nvenc"));
         assert!(!gpu_s.contains(" -crf "));
     }
 
+    #[test]
+    fn searched_crf_replaces_the_preset_quality_knob() {
+        let input = Path::new("C:\\in.mp4");
+        let output = Path::new("C:\\out.mp4");
+
+        let mut s = settings(Encoder::CPU, Preserve::default());
+        s.crf = Some(27);
+        let cpu_s = joined(&build_ffmpeg_args(input, output, &s, None));
+        assert!(cpu_s.contains(" -crf 27 "));
+        assert!(!cpu_s.contains(" -crf 23 "));
+
+        s.encoder = Encoder::NVENC;
+        let gpu_s = joined(&build_ffmpeg_args(input, output, &s, None));
+        assert!(gpu_s.contains(" -b:v 0 "));
+        assert!(gpu_s.contains(" -cq 27 "));
+
+        s.encoder = Encoder::parse("qsv").unwrap();
+        let qsv_s = joined(&build_ffmpeg_args(input, output, &s, None));
+        assert!(qsv_s.contains(" -global_quality 27 "));
+    }
+
+    #[test]
+    fn vaapi_opens_the_device_and_uploads_frames() {
+        let input = Path::new("C:\\in.mp4");
+        let output = Path::new("C:\\out.mp4");
+        let vaapi = Encoder::parse("hevc_vaapi").unwrap();
+
+        let s = joined(&build_ffmpeg_args(
+            input,
+            output,
+            &settings(vaapi, Preserve::default()),
+            None,
+        ));
+        assert!(s.contains("-vaapi_device /dev/dri/renderD128 -i C:\\in.mp4"));
+        assert!(s.contains("-vf format=nv12,hwupload -c:v hevc_vaapi"));
+        assert!(s.contains("-tag:v hvc1"));
+        assert!(!s.contains("-pix_fmt"));
+    }
+
+    #[test]
+    fn hw_decode_keeps_supported_sources_on_the_gpu() {
+        let input = Path::new("C:\\in.mp4");
+        let output = Path::new("C:\\out.mp4");
+        let mut s = settings(Encoder::NVENC, Preserve::default());
+        s.hw_decode = HwDecode::all(true);
+
+        let mut video = stream(0, StreamKind::Video, "h264");
+        video.pix_fmt = Some("yuv420p".to_string());
+        let mut info = crate::exec::probe::MediaInfo {
+            streams: vec![video, stream(1, StreamKind::Audio, "aac")],
+            ..Default::default()
+        };
+
+        let gpu = joined(&build_ffmpeg_args(input, output, &s, Some(&info)));
+        assert!(gpu.contains("-hwaccel cuda -hwaccel_output_format cuda -i "));
+        assert!(gpu.contains("-vf scale_cuda=format=yuv420p -c:v h264_nvenc"));
+        assert!(!gpu.contains("-pix_fmt"));
+
+        // off for this preset, or a cpu encoder: software decode as before.
+        s.hw_decode.balanced = false;
+        s.hw_decode.speed = true;
+        let off = joined(&build_ffmpeg_args(input, output, &s, Some(&info)));
+        assert!(!off.contains("-hwaccel"));
+        assert!(off.contains("-pix_fmt yuv420p"));
+        s.hw_decode = HwDecode::all(true);
+        s.encoder = Encoder::CPU;
+        assert!(!joined(&build_ffmpeg_args(input, output, &s, Some(&info))).contains("-hwaccel"));
+        s.encoder = Encoder::NVENC;
+
+        // 4:2:2 and rotated sources fall back to software decoding.
+        info.streams[0].pix_fmt = Some("yuv422p".to_string());
+        assert!(!joined(&build_ffmpeg_args(input, output, &s, Some(&info))).contains("-hwaccel"));
+        info.streams[0].pix_fmt = Some("yuv420p".to_string());
+        info.streams[0].rotation = Some(90.0);
+        assert!(!joined(&build_ffmpeg_args(input, output, &s, Some(&info))).contains("-hwaccel"));
+        s.preserve.rotation = true;
+        assert!(joined(&build_ffmpeg_args(input, output, &s, Some(&info))).contains("-hwaccel"));
+
+        // amf decodes on the gpu but converts in memory.
+        s.encoder = Encoder::parse("amf").unwrap();
+        let amf = joined(&build_ffmpeg_args(input, output, &s, Some(&info)));
+        assert!(amf.contains("-hwaccel d3d11va -i "));
+        assert!(!amf.contains("-vf"));
+        assert!(amf.contains("-pix_fmt yuv420p"));
+    }
+
+    #[test]
+    fn trim_and_max_height_cut_and_scale_the_output() {
+        let input = Path::new("C:\\in.mov");
+        let output = Path::new("C:\\out.mp4");
+        let info = crate::exec::probe::MediaInfo {
+            streams: vec![
+                stream(0, StreamKind::Video, "h264"),
+                stream(1, StreamKind::Audio, "aac"),
+            ],
+            ..Default::default()
+        };
+        let batch = settings(Encoder::parse("vaapi").unwrap(), Preserve::default());
+        let overrides = FileOverrides {
+            preset: Some(Preset::Remux),
+            trim_secs: Some(30),
+            max_height: Some(720),
+            ..Default::default()
+        };
+
+        let s = joined(&build_ffmpeg_args(
+            input,
+            output,
+            &overrides.apply(&batch),
+            Some(&info),
+        ));
+        assert!(s.contains(" -t 30 "));
+        // remux can't copy a stream it has to scale.
+        assert!(s.contains("-vf scale=-2:min(ih\\,720),format=nv12,hwupload -c:v h264_vaapi"));
+
+        let plain = joined(&build_ffmpeg_args(
+            input,
+            output,
+            &FileOverrides {
+                max_height: None,
+                ..overrides
+            }
+            .apply(&batch),
+            Some(&info),
+        ));
+        assert!(plain.contains("-c:v copy"));
+        assert!(plain.contains(" -t 30 "));
+    }
+
+    #[test]
+    fn ffmpeg_args_follow_preserve_switches() {
+        let input = Path::new("C:\\in.mkv");
+        let output = Path::new("C:\\out.mp4");
+        let info = crate::exec::probe::MediaInfo {
+            creation_time: Some("2024-05-01T10:20:30.000000Z".to_string()),
+            streams: vec![
+                stream(0, StreamKind::Video, "h264"),
+                stream(1, StreamKind::Audio, "aac"),
+                stream(2, StreamKind::Subtitle, "subrip"),
+                stream(3, StreamKind::Subtitle, "hdmv_pgs_subtitle"),
+            ],
+            ..Default::default()
+        };
+
+        let all = build_ffmpeg_args(
+            input,
+            output,
+            &settings(Encoder::CPU, Preserve::all(true)),
+            Some(&info),
+        );
+        let all_s = joined(&all);
+        assert!(all_s.contains("-noautorotate -i"));
+        assert!(all_s.contains("-map 0:2 "));
+        assert!(!all_s.contains("-map 0:3 "));
+        assert!(all_s.contains("-map_metadata 0 -map_chapters 0"));
+        assert!(all_s.contains("-c:s mov_text"));
+        assert!(!all_s.contains("creation_time"));
+
+        let only_ct = Preserve {
+            creation_time: true,
+            ..Preserve::all(false)
+        };
+        let none = build_ffmpeg_args(input, output, &settings(Encoder::CPU, only_ct), Some(&info));
+        let none_s = joined(&none);
+        assert!(!none_s.contains("-noautorotate"));
+        assert!(!none_s.contains("-c:s"));
+        assert!(none_s.contains("-map_metadata -1 -map_chapters -1"));
+        assert!(none_s.contains("-metadata creation_time=2024-05-01T10:20:30.000000Z"));
+    }
+
+    #[test]
+    fn remux_copies_compatible_streams_and_reencodes_the_rest() {
+        let input = Path::new("C:\\in.mov");
+        let output = Path::new("C:\\out.mp4");
+        let remux = EncodeSettings {
+            preset: Preset::Remux,
+            ..settings(Encoder::CPU, Preserve::default())
+        };
+
+        let info = crate::exec::probe::MediaInfo {
+            streams: vec![
+                stream(0, StreamKind::Video, "hevc"),
+                stream(1, StreamKind::Audio, "aac"),
+                stream(2, StreamKind::Audio, "pcm_s16le"),
+            ],
+            ..Default::default()
+        };
+        let s = joined(&build_ffmpeg_args(input, output, &remux, Some(&info)));
+        assert!(s.contains("-c:v copy -tag:v hvc1"));
+        assert!(!s.contains("-pix_fmt"));
+        assert!(s.contains("-c:a:0 copy"));
+        assert!(s.contains("-c:a:1 aac -b:a:1 128k"));
+        assert!(s.contains("+faststart"));
+
+        let info = crate::exec::probe::MediaInfo {
+            streams: vec![
+                stream(0, StreamKind::Video, "wmv3"),
+                stream(1, StreamKind::Audio, "mp3"),
+            ],
+            ..Default::default()
+        };
+        let s = joined(&build_ffmpeg_args(input, output, &remux, Some(&info)));
+        assert!(!s.contains("-c:v copy"));
+        assert!(s.contains("libx264"));
+        assert!(s.contains("-pix_fmt yuv420p"));
+        assert!(s.contains("-c:a:0 copy"));
+    }
+
+    #[test]
+    fn animations_drop_audio_and_skip_the_video_encoder() {
+        let input = Path::new("C:\\in.mp4");
+        let output = Path::new("C:\\out.gif");
+        let gif = EncodeSettings {
+            preset: Preset::Gif,
+            trim_secs: Some(5),
+            preserve: Preserve::all(true),
+            ..settings(Encoder::NVENC, Preserve::default())
+        };
+        let s = joined(&build_ffmpeg_args(input, output, &gif, None));
+        assert!(
+            s.starts_with("-hide_banner -nostdin -nostats -y -i C:\\in.mp4 -map 0:v:0 -an -sn")
+        );
+        assert!(s.contains("-map_metadata -1 -map_chapters -1 -t 5 -vf fps=15,"));
+        assert!(s.contains("palettegen"));
+        assert!(!s.contains("nvenc"));
+        assert!(!s.contains("cuda"));
+        assert!(!s.contains("-c:a"));
+        assert!(!s.contains("faststart"));
+        assert!(s.ends_with("-loop 0 C:\\out.gif"));
+
+        let webp = EncodeSettings {
+            preset: Preset::Webp,
+            ..gif
+        };
+        let s = joined(&build_ffmpeg_args(input, output, &webp, None));
+        assert!(s.contains("-c:v libwebp"));
+    }
+
+    #[test]
+    fn containers_pick_their_flags_audio_and_subtitles() {
+        let input = Path::new("C:\\in.mkv");
+        let info = crate::exec::probe::MediaInfo {
+            streams: vec![
+                stream(0, StreamKind::Video, "h264"),
+                stream(1, StreamKind::Audio, "dts"),
+                stream(2, StreamKind::Subtitle, "ass"),
+                stream(3, StreamKind::Subtitle, "hdmv_pgs_subtitle"),
+            ],
+            ..Default::default()
+        };
+        let with_subs = Preserve {
+            subtitles: true,
+            ..Preserve::default()
+        };
+
+        let mkv = EncodeSettings {
+            container: Container::Mkv,
+            ..settings(Encoder::CPU, with_subs)
+        };
+        let s = joined(&build_ffmpeg_args(
+            input,
+            Path::new("C:\\out.mkv"),
+            &mkv,
+            Some(&info),
+        ));
+        assert!(!s.contains("+faststart"));
+        assert!(s.contains("-map 0:s? -map 0:t?"));
+        assert!(s.contains("-c:s copy"));
+        assert!(s.contains("-c:a aac"));
+
+        let remux = EncodeSettings {
+            preset: Preset::Remux,
+            ..mkv
+        };
+        let s = joined(&build_ffmpeg_args(
+            input,
+            Path::new("C:\\out.mkv"),
+            &remux,
+            Some(&info),
+        ));
+        assert!(s.contains("-c:v copy"));
+        assert!(!s.contains("hvc1"));
+        assert!(s.contains("-c:a:0 copy"));
+
+        for name in [
+            "libx265",
+            "hevc_nvenc",
+            "hevc_qsv",
+            "hevc_amf",
+            "hevc_vaapi",
+        ] {
+            let hevc = EncodeSettings {
+                container: Container::Mkv,
+                ..settings(Encoder::parse(name).unwrap(), with_subs)
+            };
+            let s = joined(&build_ffmpeg_args(
+                input,
+                Path::new("C:\\out.mkv"),
+                &hevc,
+                Some(&info),
+            ));
+            assert!(s.contains(&format!("-c:v {name}")), "{s}");
+            assert!(!s.contains("hvc1"), "{s}");
+        }
+
+        let webm = EncodeSettings {
+            container: Container::Webm,
+            ..settings(
+                Encoder {
+                    codec: crate::presets::encoder::Codec::Av1,
+                    ..Encoder::CPU
+                },
+                with_subs,
+            )
+        };
+        let s = joined(&build_ffmpeg_args(
+            input,
+            Path::new("C:\\out.webm"),
+            &webm,
+            Some(&info),
+        ));
+        assert!(!s.contains("+faststart"));
+        assert!(s.contains("-c:a libopus"));
+        assert!(s.contains("-map 0:2 -map_metadata"));
+        assert!(s.contains("-c:s webvtt"));
+
+        let mov = EncodeSettings {
+            container: Container::Mov,
+            ..settings(Encoder::CPU, Preserve::default())
+        };
+        let s = joined(&build_ffmpeg_args(
+            input,
+            Path::new("C:\\out.mov"),
+            &mov,
+            Some(&info),
+        ));
+        assert!(s.contains("-movflags +faststart"));
+    }
+
+    #[test]
+    fn copy_file_times_copies_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("a.mp4");
+        let dest = dir.path().join("b.mp4");
+        std::fs::write(&src, b"a").unwrap();
+        std::fs::write(&dest, b"b").unwrap();
+
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        let f = std::fs::OpenOptions::new().write(true).open(&src).unwrap();
+        f.set_times(std::fs::FileTimes::new().set_modified(old))
+            .unwrap();
+        drop(f);
+
+        copy_file_times(&src, &dest).unwrap();
+        assert_eq!(std::fs::metadata(&dest).unwrap().modified().unwrap(), old);
+    }
+
+    fn stream(index: usize, kind: StreamKind, codec: &str) -> crate::exec::probe::StreamInfo {
+        crate::exec::probe::StreamInfo {
+            index,
+            kind,
+            codec: codec.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn recognises_encoder_start_failures_only() {
+        let nvenc = "\
+[h264_nvenc @ 0x55] OpenEncodeSessionEx failed: incompatible client key (21): (no details)
+[vost#0:0/h264_nvenc @ 0x56] Error while opening encoder - maybe incorrect parameters
+Conversion failed!";
+        assert!(is_encoder_init_failure(nvenc));
+        assert!(is_encoder_init_failure(
+            "[h264_qsv @ 0x1] Error initializing an internal MFX session: unsupported (-3)"
+        ));
+        assert!(is_encoder_init_failure(
+            "[AVHWDeviceContext @ 0x1] Failed to initialise VAAPI connection: -1 (unknown libva error)."
+        ));
+        assert!(!is_encoder_init_failure(
+            "[h264_nvenc @ 0x1] Undefined constant or missing '(' in 'slowest'\n\
+[vost#0:0/h264_nvenc @ 0x2] Error while opening encoder - maybe incorrect parameters"
+        ));
+        assert!(!is_encoder_init_failure(
+            "[mov,mp4,m4a,3gp,3g2,mj2 @ 0x1] moov atom not found\nin.mp4: Invalid data found when processing input"
+        ));
+    }
+
     #[test]
     fn parses_duration_us_from_stderr() {
         let line = "Duration: 00:00:08.05, start: 0.000000, bitrate: 123 kb/s";
@@ -281,10 +1468,63 @@ mod tests {
     }
 
     #[test]
-    fn percent_caps_at_99_until_end() {
-        let total = 10_000_000u64;
-        let out = 10_000_000u64;
-        assert_eq!(compute_percent(out, total, false), Some(99));
-        assert_eq!(compute_percent(out, total, true), Some(100));
+    fn output_path_follows_the_naming_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("clip.mov");
+        std::fs::write(&input, b"in").unwrap();
+
+        let naming = OutputNaming::default();
+        let first = build_output_path(&input, Preset::Speed, Container::Mp4, &naming).unwrap();
+        assert_eq!(first, dir.path().join("clip.tinythis.speed.mp4"));
+        std::fs::write(&first, b"out").unwrap();
+        assert_eq!(
+            build_output_path(&input, Preset::Speed, Container::Mp4, &naming).unwrap(),
+            dir.path().join("clip.tinythis.speed.2.mp4")
+        );
+
+        let out_dir = dir.path().join("out");
+        let naming = OutputNaming {
+            dir: Some(out_dir.clone()),
+            template: "{stem}-small".to_string(),
+            on_existing: OnExisting::Overwrite,
+        };
+        std::fs::create_dir(&out_dir).unwrap();
+        std::fs::write(out_dir.join("clip-small.mp4"), b"old").unwrap();
+        assert_eq!(
+            build_output_path(&input, Preset::Speed, Container::Mp4, &naming).unwrap(),
+            out_dir.join("clip-small.mp4")
+        );
+
+        assert_eq!(
+            build_output_path(&input, Preset::Speed, Container::Webm, &naming).unwrap(),
+            out_dir.join("clip-small.webm")
+        );
+        // animations bring their own extension, whatever the container.
+        assert_eq!(
+            build_output_path(&input, Preset::Gif, Container::Mkv, &naming).unwrap(),
+            out_dir.join("clip-small.gif")
+        );
+
+        // overwrite never means overwriting the input itself.
+        let input = dir.path().join("clip.mp4");
+        std::fs::write(&input, b"in").unwrap();
+        let naming = OutputNaming {
+            dir: None,
+            template: "{stem}".to_string(),
+            on_existing: OnExisting::Overwrite,
+        };
+        assert_eq!(
+            build_output_path(&input, Preset::Speed, Container::Mp4, &naming).unwrap(),
+            dir.path().join("clip.2.mp4")
+        );
+    }
+
+    #[test]
+    fn templates_must_name_a_file_per_input() {
+        assert!(OutputNaming::validate_template(OutputNaming::DEFAULT_TEMPLATE).is_ok());
+        assert!(OutputNaming::validate_template("{preset}").is_err());
+        assert!(OutputNaming::validate_template("..\\{stem}").is_err());
+        assert!(OutputNaming::validate_template("{stem}.{date}").is_err());
+        assert!(OutputNaming::validate_template("{stem}#1").is_err());
     }
 }
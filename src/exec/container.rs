@@ -0,0 +1,189 @@
+use std::path::Path;
+
+use crate::presets::encoder::{Codec, Encoder};
+
+/// The file format outputs are written in.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Container {
+    #[default]
+    Mp4,
+    Mkv,
+    Webm,
+    Mov,
+}
+
+impl Container {
+    /// Also the output's extension.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "mkv",
+            Container::Webm => "webm",
+            Container::Mov => "mov",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "mp4" => Some(Container::Mp4),
+            "mkv" | "matroska" => Some(Container::Mkv),
+            "webm" => Some(Container::Webm),
+            "mov" => Some(Container::Mov),
+            _ => None,
+        }
+    }
+
+    /// The name ffmpeg's muxer goes by, for when it can't tell from the extension.
+    pub fn muxer(self) -> &'static str {
+        match self {
+            Container::Mkv => "matroska",
+            c => c.as_str(),
+        }
+    }
+
+    /// Whether `path` already is this kind of file, going by its extension.
+    pub fn matches_path(self, path: &Path) -> bool {
+        path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+            e.eq_ignore_ascii_case(self.as_str())
+                || (self == Container::Mp4 && e.eq_ignore_ascii_case("m4v"))
+        })
+    }
+
+    /// Whether video from `codec` can be written into this container.
+    pub fn holds(self, codec: Codec) -> bool {
+        match self {
+            Container::Mp4 | Container::Mkv => true,
+            Container::Mov => codec != Codec::Av1,
+            Container::Webm => codec == Codec::Av1,
+        }
+    }
+
+    /// Fails with the reason when `encoder`'s output can't go into this container.
+    pub fn check(self, encoder: Encoder) -> Result<(), String> {
+        if self.holds(encoder.codec) {
+            return Ok(());
+        }
+        Err(match self {
+            Container::Webm => format!(
+                "webm only holds av1 (or copied vp8/vp9) video, not {}; set codec = 'av1' in options.toml",
+                encoder.codec.as_str()
+            ),
+            _ => format!(
+                "{} can't hold {} video; use mp4 or mkv",
+                self.as_str(),
+                encoder.codec.as_str()
+            ),
+        })
+    }
+
+    /// Whether the remux preset can copy a video stream of this (probed) codec in untouched.
+    pub fn copies_video(self, codec: &str) -> bool {
+        match self {
+            Container::Mp4 => matches!(codec, "h264" | "hevc" | "av1" | "vp9" | "mpeg4"),
+            Container::Mov => matches!(codec, "h264" | "hevc" | "mpeg4" | "prores" | "mjpeg"),
+            Container::Mkv => matches!(
+                codec,
+                "h264"
+                    | "hevc"
+                    | "av1"
+                    | "vp9"
+                    | "vp8"
+                    | "mpeg4"
+                    | "mpeg2video"
+                    | "prores"
+                    | "mjpeg"
+            ),
+            Container::Webm => matches!(codec, "av1" | "vp9" | "vp8"),
+        }
+    }
+
+    /// Whether an audio stream of this (probed) codec can be copied in untouched.
+    pub fn copies_audio(self, codec: &str) -> bool {
+        match self {
+            Container::Mp4 => matches!(
+                codec,
+                "aac" | "mp3" | "ac3" | "eac3" | "alac" | "opus" | "flac"
+            ),
+            Container::Mov => matches!(
+                codec,
+                "aac" | "mp3" | "ac3" | "eac3" | "alac" | "pcm_s16le" | "pcm_s24le"
+            ),
+            Container::Mkv => matches!(
+                codec,
+                "aac"
+                    | "mp3"
+                    | "ac3"
+                    | "eac3"
+                    | "alac"
+                    | "opus"
+                    | "flac"
+                    | "vorbis"
+                    | "dts"
+                    | "truehd"
+                    | "pcm_s16le"
+                    | "pcm_s24le"
+            ),
+            Container::Webm => matches!(codec, "opus" | "vorbis"),
+        }
+    }
+
+    /// The encoder for audio that isn't copied.
+    pub fn audio_encoder(self) -> &'static str {
+        match self {
+            Container::Webm => "libopus",
+            _ => "aac",
+        }
+    }
+
+    /// mp4 and mov keep their index at the end unless told to move it up front.
+    pub fn faststart(self) -> bool {
+        matches!(self, Container::Mp4 | Container::Mov)
+    }
+
+    /// What kept text subtitles are converted to; `None` when every subtitle stream (and the
+    /// attached fonts) is copied as-is.
+    pub fn text_subtitle_codec(self) -> Option<&'static str> {
+        match self {
+            Container::Mp4 | Container::Mov => Some("mov_text"),
+            Container::Webm => Some("webvtt"),
+            Container::Mkv => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webm_takes_only_av1_and_mov_no_av1() {
+        let av1 = Encoder {
+            codec: Codec::Av1,
+            ..Encoder::CPU
+        };
+        assert!(Container::Webm.check(av1).is_ok());
+        assert!(
+            Container::Webm
+                .check(Encoder::CPU)
+                .unwrap_err()
+                .contains("av1")
+        );
+        assert!(Container::Mov.check(av1).is_err());
+        assert!(Container::Mkv.check(av1).is_ok());
+        assert!(Container::Mp4.check(Encoder::NVENC).is_ok());
+
+        assert!(Container::Webm.copies_audio("opus"));
+        assert!(!Container::Webm.copies_audio("aac"));
+        assert!(Container::Mkv.copies_video("mpeg2video"));
+        assert!(!Container::Mp4.copies_video("mpeg2video"));
+    }
+
+    #[test]
+    fn parses_names_and_matches_extensions() {
+        assert_eq!(Container::parse(" MKV "), Some(Container::Mkv));
+        assert_eq!(Container::parse("avi"), None);
+        assert!(Container::Mp4.matches_path(Path::new("a.M4V")));
+        assert!(Container::Webm.matches_path(Path::new("a.webm")));
+        assert!(!Container::Mkv.matches_path(Path::new("a.mp4")));
+    }
+}
@@ -0,0 +1,399 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::exec::compress::EncodeSettings;
+use crate::exec::probe::MediaInfo;
+use crate::presets::SEARCH_CRFS;
+
+const SAMPLE_SECONDS: u64 = 4;
+const SAMPLE_COUNT: u64 = 3;
+const CACHE_FILE_NAME: &str = "crf_cache.txt";
+/// Searches kept in the cache; the oldest go first.
+const CACHE_ENTRIES: usize = 500;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrfSearch {
+    pub crf: u8,
+    /// mean score of the samples at the closest crf that was measured.
+    pub vmaf: f64,
+    pub cached: bool,
+}
+
+/// Finds the highest crf whose samples still score `target` vmaf against the source.
+///
+/// Short samples are cut from the input, encoded at a few crfs and scored; the crf is
+/// interpolated between the two measurements around the target. The samples come from the part
+/// the output keeps (`trim_secs`) at the height it ends up (`max_height`). Results are cached
+/// per file, preset, encoder, trim, height and target so re-runs skip the search.
+pub fn search(
+    ffmpeg: &Path,
+    input: &Path,
+    settings: &EncodeSettings,
+    info: &MediaInfo,
+    target: f64,
+) -> Result<CrfSearch> {
+    let key = cache_key(input, settings, target)?;
+    if let Some(hit) = load_cached(&key) {
+        return Ok(hit);
+    }
+
+    let dir = tempfile::Builder::new()
+        .prefix("tinythis_vmaf_")
+        .tempdir()?;
+
+    let duration_us =
+        crate::exec::compress::output_length_us(Some(info), settings.trim_secs).unwrap_or(0);
+    let scale = settings.scale_filter();
+    let mut references = Vec::<PathBuf>::new();
+    for (n, (start_us, len_us)) in sample_windows(duration_us).into_iter().enumerate() {
+        let reference = dir.path().join(format!("ref{n}.mkv"));
+        extract_reference(
+            ffmpeg,
+            input,
+            start_us,
+            len_us,
+            scale.as_deref(),
+            &reference,
+        )?;
+        references.push(reference);
+    }
+
+    let mut points = Vec::<(u8, f64)>::new();
+    for crf in SEARCH_CRFS {
+        let mut total = 0.0;
+        for (n, reference) in references.iter().enumerate() {
+            let candidate = dir.path().join(format!("crf{crf}_{n}.mp4"));
+            encode_candidate(ffmpeg, reference, &candidate, settings, crf)?;
+            total += crate::exec::metrics::vmaf(ffmpeg, reference, &candidate)?;
+        }
+        points.push((crf, total / references.len() as f64));
+
+        // the scores only drop from here on, so one point below the target is enough.
+        if points.last().is_some_and(|&(_, score)| score < target) {
+            break;
+        }
+    }
+
+    let crf = interpolate_crf(&points, target);
+    let vmaf = points
+        .iter()
+        .min_by_key(|(c, _)| c.abs_diff(crf))
+        .map(|&(_, s)| s)
+        .unwrap_or(0.0);
+    let result = CrfSearch {
+        crf,
+        vmaf,
+        cached: false,
+    };
+
+    // a failed cache write only costs a repeat search next time.
+    let _ = store_cached(&key, &result);
+    Ok(result)
+}
+
+/// `(start, length)` of the samples in microseconds, spread over the middle of the input.
+fn sample_windows(duration_us: u64) -> Vec<(u64, u64)> {
+    let sample_us = SAMPLE_SECONDS * 1_000_000;
+    if duration_us <= sample_us * SAMPLE_COUNT * 2 {
+        // short inputs are measured whole (or the first few seconds if unknown).
+        let len = if duration_us == 0 {
+            sample_us * SAMPLE_COUNT
+        } else {
+            duration_us
+        };
+        return vec![(0, len)];
+    }
+
+    (1..=SAMPLE_COUNT)
+        .map(|i| {
+            let center = duration_us / (SAMPLE_COUNT + 1) * i;
+            (center - sample_us / 2, sample_us)
+        })
+        .collect()
+}
+
+/// Picks the crf where the measured scores cross `target`, rounding toward quality.
+///
+/// `points` are `(crf, score)` in ascending crf order.
+fn interpolate_crf(points: &[(u8, f64)], target: f64) -> u8 {
+    let Some(&(first_crf, first_score)) = points.first() else {
+        return SEARCH_CRFS[0];
+    };
+    if first_score < target {
+        return first_crf;
+    }
+
+    for pair in points.windows(2) {
+        let ((lo_crf, lo_score), (hi_crf, hi_score)) = (pair[0], pair[1]);
+        if hi_score < target {
+            let t = (lo_score - target) / (lo_score - hi_score);
+            let crf = lo_crf as f64 + t * (hi_crf - lo_crf) as f64;
+            return crf.floor() as u8;
+        }
+    }
+
+    points.last().map(|&(c, _)| c).unwrap_or(first_crf)
+}
+
+fn extract_reference(
+    ffmpeg: &Path,
+    input: &Path,
+    start_us: u64,
+    len_us: u64,
+    scale: Option<&str>,
+    out: &Path,
+) -> Result<()> {
+    // lossless, so the candidates are scored against exactly the frames they were made from;
+    // scaled here, so they are encoded and scored at the output's size.
+    let mut args = vec![
+        OsString::from("-hide_banner"),
+        OsString::from("-nostdin"),
+        OsString::from("-y"),
+        OsString::from("-ss"),
+        OsString::from(format!("{start_us}us")),
+        OsString::from("-t"),
+        OsString::from(format!("{len_us}us")),
+        OsString::from("-i"),
+        input.as_os_str().to_owned(),
+        OsString::from("-map"),
+        OsString::from("0:v:0"),
+        OsString::from("-an"),
+    ];
+    if let Some(scale) = scale {
+        args.extend([OsString::from("-vf"), OsString::from(scale)]);
+    }
+    args.extend([
+        OsString::from("-c:v"),
+        OsString::from("libx264"),
+        OsString::from("-preset"),
+        OsString::from("ultrafast"),
+        OsString::from("-qp"),
+        OsString::from("0"),
+        OsString::from("-pix_fmt"),
+        OsString::from("yuv420p"),
+        out.as_os_str().to_owned(),
+    ]);
+    crate::process::run::run_capture(ffmpeg, &args)?;
+    Ok(())
+}
+
+fn encode_candidate(
+    ffmpeg: &Path,
+    reference: &Path,
+    out: &Path,
+    settings: &EncodeSettings,
+    crf: u8,
+) -> Result<()> {
+    let encoder = settings.encoder;
+    let mut args = vec![
+        OsString::from("-hide_banner"),
+        OsString::from("-nostdin"),
+        OsString::from("-y"),
+    ];
+    args.extend(encoder.input_args());
+    args.extend([
+        OsString::from("-i"),
+        reference.as_os_str().to_owned(),
+        OsString::from("-an"),
+    ]);
+    if let Some(filter) = encoder.video_filter() {
+        args.extend([OsString::from("-vf"), OsString::from(filter)]);
+    }
+    args.extend(encoder.video_args(settings.preset, Some(crf)));
+    if let Some(pix_fmt) = encoder.pix_fmt() {
+        args.extend([OsString::from("-pix_fmt"), OsString::from(pix_fmt)]);
+    }
+    args.push(out.as_os_str().to_owned());
+    crate::process::run::run_capture(ffmpeg, &args)?;
+    Ok(())
+}
+
+/// The crf an earlier search found for these settings, without searching.
+pub fn cached(input: &Path, settings: &EncodeSettings, target: f64) -> Option<CrfSearch> {
+    load_cached(&cache_key(input, settings, target).ok()?)
+}
+
+fn cache_key(input: &Path, settings: &EncodeSettings, target: f64) -> Result<String> {
+    let meta = std::fs::metadata(input)?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = std::fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf());
+
+    let text = |v: Option<u32>| v.map_or("-".to_string(), |v| v.to_string());
+    Ok(format!(
+        "{}|{}|{modified}|{}|{}|{}|{}|{target}",
+        path.display(),
+        meta.len(),
+        settings.preset.as_str(),
+        settings.encoder.ffmpeg_name(),
+        text(settings.trim_secs),
+        text(settings.max_height),
+    ))
+}
+
+fn cache_path() -> Result<PathBuf> {
+    Ok(crate::paths::app_root_dir()?.join(CACHE_FILE_NAME))
+}
+
+fn load_cached(key: &str) -> Option<CrfSearch> {
+    let text = std::fs::read_to_string(cache_path().ok()?).ok()?;
+    text.lines().rev().find_map(|l| parse_cache_line(l, key))
+}
+
+fn store_cached(key: &str, result: &CrfSearch) -> Result<()> {
+    use std::io::Write;
+
+    let path = cache_path()?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let old = std::fs::read_to_string(&path).unwrap_or_default();
+    let text = with_entry(&old, key, &format_cache_line(key, result), CACHE_ENTRIES);
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    tmp.as_file_mut().write_all(text.as_bytes())?;
+    tmp.persist(&path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// The cache with `line` last, replacing `key`'s older entry, and at most `max` entries.
+fn with_entry(text: &str, key: &str, line: &str, max: usize) -> String {
+    let others = text
+        .lines()
+        .filter(|l| l.splitn(3, '\t').nth(2) != Some(key))
+        .collect::<Vec<_>>();
+    let skip = (others.len() + 1).saturating_sub(max);
+    let mut out = String::new();
+    for l in others.into_iter().skip(skip).chain([line]) {
+        out.push_str(l);
+        out.push('\n');
+    }
+    out
+}
+
+/// One cache entry per line: `<crf>\t<vmaf>\t<key>`; the key goes last since it holds a path.
+fn format_cache_line(key: &str, result: &CrfSearch) -> String {
+    format!("{}\t{:.2}\t{key}", result.crf, result.vmaf)
+}
+
+fn parse_cache_line(line: &str, key: &str) -> Option<CrfSearch> {
+    let mut parts = line.splitn(3, '\t');
+    let crf = parts.next()?.parse().ok()?;
+    let vmaf = parts.next()?.parse().ok()?;
+    (parts.next()? == key).then_some(CrfSearch {
+        crf,
+        vmaf,
+        cached: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_between_the_points_around_the_target() {
+        let points = [(16, 97.0), (21, 95.0), (26, 91.0)];
+        // 93 sits halfway between crf 21 and 26.
+        assert_eq!(interpolate_crf(&points, 93.0), 23);
+        // everything scores above the target: the highest crf tried wins.
+        assert_eq!(interpolate_crf(&points, 90.0), 26);
+        // even the best crf misses the target: take it anyway.
+        assert_eq!(interpolate_crf(&points, 98.0), 16);
+    }
+
+    #[test]
+    fn samples_spread_over_long_inputs_and_cover_short_ones() {
+        assert_eq!(sample_windows(10_000_000), vec![(0, 10_000_000)]);
+        assert_eq!(sample_windows(0), vec![(0, 12_000_000)]);
+
+        let windows = sample_windows(100_000_000);
+        assert_eq!(
+            windows,
+            vec![
+                (23_000_000, 4_000_000),
+                (48_000_000, 4_000_000),
+                (73_000_000, 4_000_000)
+            ]
+        );
+    }
+
+    #[test]
+    fn cache_lines_round_trip_and_match_only_their_key() {
+        let result = CrfSearch {
+            crf: 24,
+            vmaf: 93.4,
+            cached: false,
+        };
+        let line = format_cache_line("C:\\a.mp4|10|5|balanced|libx264|93", &result);
+        assert_eq!(line, "24\t93.40\tC:\\a.mp4|10|5|balanced|libx264|93");
+
+        let hit = parse_cache_line(&line, "C:\\a.mp4|10|5|balanced|libx264|93").unwrap();
+        assert_eq!((hit.crf, hit.cached), (24, true));
+        assert!(parse_cache_line(&line, "C:\\a.mp4|10|5|balanced|h264_nvenc|93").is_none());
+    }
+
+    #[test]
+    fn cache_keeps_one_entry_per_key_and_drops_the_oldest() {
+        let entry = |crf: u8, key: &str| {
+            let r = CrfSearch {
+                crf,
+                vmaf: 93.0,
+                cached: false,
+            };
+            format_cache_line(key, &r)
+        };
+        let text = with_entry("", "a", &entry(20, "a"), 3);
+        let text = with_entry(&text, "b", &entry(21, "b"), 3);
+        let text = with_entry(&text, "a", &entry(22, "a"), 3);
+        assert_eq!(text, format!("{}\n{}\n", entry(21, "b"), entry(22, "a")));
+
+        let text = with_entry(&text, "c", &entry(23, "c"), 3);
+        let text = with_entry(&text, "d", &entry(24, "d"), 3);
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.lines().all(|l| parse_cache_line(l, "b").is_none()));
+        assert_eq!(text.lines().last(), Some(entry(24, "d").as_str()));
+    }
+
+    #[test]
+    fn cache_keys_tell_trims_and_heights_apart() {
+        use crate::exec::compress::{HwDecode, Preserve};
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("a.mp4");
+        std::fs::write(&input, b"x").unwrap();
+        let full = EncodeSettings {
+            preset: crate::presets::Preset::Balanced,
+            encoder: crate::presets::encoder::Encoder::CPU,
+            container: crate::exec::container::Container::Mp4,
+            preserve: Preserve::default(),
+            min_savings: 20,
+            target_vmaf: Some(93.0),
+            crf: None,
+            measure: false,
+            min_quality: None,
+            cpu_fallback: false,
+            hw_decode: HwDecode::default(),
+            trim_secs: None,
+            max_height: None,
+            gif: crate::exec::gif::GifSettings::default(),
+            split: None,
+        };
+        let key = |s: &EncodeSettings| cache_key(&input, s, 93.0).unwrap();
+
+        let trimmed = EncodeSettings {
+            trim_secs: Some(30),
+            ..full
+        };
+        let smaller = EncodeSettings {
+            max_height: Some(720),
+            ..full
+        };
+        assert!(key(&full).ends_with("|balanced|libx264|-|-|93"));
+        assert!(key(&trimmed).ends_with("|30|-|93"));
+        assert!(key(&smaller).ends_with("|-|720|93"));
+    }
+}
@@ -0,0 +1,317 @@
+use std::ffi::OsString;
+
+use crate::presets::Preset;
+
+/// Options tinythis sets itself and reads back: progress, overwriting, input and log level.
+const RESERVED: [&str; 8] = [
+    "-progress",
+    "-y",
+    "-n",
+    "-i",
+    "-stdin",
+    "-nostdin",
+    "-loglevel",
+    "-v",
+];
+
+/// Options that take no value, so a word after one is a stray output path rather than its value.
+const NO_VALUE: [&str; 22] = [
+    "-an",
+    "-vn",
+    "-sn",
+    "-dn",
+    "-shortest",
+    "-copyts",
+    "-start_at_zero",
+    "-copytb",
+    "-re",
+    "-hide_banner",
+    "-nostats",
+    "-stats",
+    "-accurate_seek",
+    "-noaccurate_seek",
+    "-ignore_unknown",
+    "-copy_unknown",
+    "-benchmark",
+    "-benchmark_all",
+    "-xerror",
+    "-bitexact",
+    "-dump",
+    "-hex",
+];
+
+/// ffmpeg options added to every encode, from `--ffmpeg-args` or options.toml.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ExtraArgs {
+    /// go right before `-i`, e.g. `-thread_queue_size 1024`.
+    pub input: Vec<String>,
+    /// go right before the output path, so they win over tinythis's own, e.g. `-tune film`.
+    pub output: Vec<String>,
+}
+
+impl ExtraArgs {
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty() && self.output.is_empty()
+    }
+
+    /// These, then `later`'s.
+    pub fn then(&self, later: &ExtraArgs) -> ExtraArgs {
+        ExtraArgs {
+            input: [&self.input[..], &later.input[..]].concat(),
+            output: [&self.output[..], &later.output[..]].concat(),
+        }
+    }
+
+    /// Inserts the options into a command from `build_ffmpeg_args`.
+    pub fn apply(&self, args: &mut Vec<OsString>) {
+        let out_at = args.len().saturating_sub(1);
+        args.splice(out_at..out_at, self.output.iter().map(OsString::from));
+        let in_at = args.iter().position(|a| a == "-i").unwrap_or(0);
+        args.splice(in_at..in_at, self.input.iter().map(OsString::from));
+    }
+}
+
+/// Extra options per preset; auto adds the ones of the preset it picks for each file.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PresetArgs {
+    pub quality: ExtraArgs,
+    pub balanced: ExtraArgs,
+    pub speed: ExtraArgs,
+    pub remux: ExtraArgs,
+    pub gif: ExtraArgs,
+    pub webp: ExtraArgs,
+}
+
+impl PresetArgs {
+    /// Auto is resolved before encoding; on its own it counts as balanced, like `HwDecode`.
+    pub fn for_preset(&self, preset: Preset) -> &ExtraArgs {
+        match preset {
+            Preset::Quality => &self.quality,
+            Preset::Speed => &self.speed,
+            Preset::Remux => &self.remux,
+            Preset::Gif => &self.gif,
+            Preset::Webp => &self.webp,
+            Preset::Balanced | Preset::Auto => &self.balanced,
+        }
+    }
+
+    pub fn for_preset_mut(&mut self, preset: Preset) -> &mut ExtraArgs {
+        match preset {
+            Preset::Quality => &mut self.quality,
+            Preset::Speed => &mut self.speed,
+            Preset::Remux => &mut self.remux,
+            Preset::Gif => &mut self.gif,
+            Preset::Webp => &mut self.webp,
+            Preset::Balanced | Preset::Auto => &mut self.balanced,
+        }
+    }
+
+    /// Every preset's options followed by `extra` (the cli's, which apply whatever the preset).
+    pub fn then_all(&self, extra: &ExtraArgs) -> PresetArgs {
+        PresetArgs {
+            quality: self.quality.then(extra),
+            balanced: self.balanced.then(extra),
+            speed: self.speed.then(extra),
+            remux: self.remux.then(extra),
+            gif: self.gif.then(extra),
+            webp: self.webp.then(extra),
+        }
+    }
+}
+
+/// Rejects options that would break progress, the output or the error report, and stray words
+/// ffmpeg would take as another output file.
+pub fn validate(args: &[String]) -> Result<(), String> {
+    let mut after_option = false;
+    for a in args {
+        if a.starts_with('-') && a.len() > 1 {
+            if RESERVED.contains(&a.as_str()) {
+                return Err(format!(
+                    "`{a}` is set by tinythis and can't be passed through"
+                ));
+            }
+            after_option = !NO_VALUE.contains(&a.as_str());
+        } else if after_option {
+            // the option's value.
+            after_option = false;
+        } else {
+            return Err(format!(
+                "`{a}` isn't an option or an option's value; outputs are named by tinythis"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The opposite of `split`: quotes the args that need it so they split back the same.
+pub fn join(args: &[String]) -> String {
+    args.iter()
+        .map(|a| {
+            let plain = !a.is_empty()
+                && !a
+                    .chars()
+                    .any(|c| c.is_whitespace() || c == '"' || c == '\'');
+            if plain {
+                return a.clone();
+            }
+            // single quotes keep everything but themselves; those go in double quotes.
+            a.split('\'')
+                .map(|part| format!("'{part}'"))
+                .collect::<Vec<_>>()
+                .join("\"'\"")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Splits a line like a shell does: whitespace separates, single or double quotes group.
+pub fn split(s: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    for c in s.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => cur.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut cur));
+                    in_arg = false;
+                }
+            }
+            None => {
+                cur.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err("unclosed quote".to_string());
+    }
+    if in_arg {
+        args.push(cur);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_like_a_shell() {
+        assert_eq!(
+            split(r#"-vf "scale=1280:-2, hqdn3d" -tune  film -metadata title='a "b"' """#).unwrap(),
+            [
+                "-vf",
+                "scale=1280:-2, hqdn3d",
+                "-tune",
+                "film",
+                "-metadata",
+                "title=a \"b\"",
+                ""
+            ]
+        );
+        assert!(split("").unwrap().is_empty());
+        assert!(split("-vf 'scale").is_err());
+    }
+
+    #[test]
+    fn join_splits_back_the_same() {
+        let args = strings(&[
+            "-vf",
+            "scale=1280:-2, hqdn3d",
+            "-metadata",
+            r#"title=it's "b""#,
+            "",
+            "-tune",
+        ]);
+        let line = join(&args);
+        assert!(line.starts_with("-vf 'scale=1280:-2, hqdn3d' -metadata "));
+        assert_eq!(split(&line).unwrap(), args);
+        assert_eq!(join(&[]), "");
+    }
+
+    #[test]
+    fn rejects_what_tinythis_controls_and_stray_paths() {
+        assert!(
+            validate(&strings(&[
+                "-tune",
+                "film",
+                "-an",
+                "-x264-params",
+                "aq-mode=3"
+            ]))
+            .is_ok()
+        );
+        assert!(validate(&strings(&["-ss", "-5"])).is_ok());
+        assert!(validate(&strings(&["-y"])).unwrap_err().contains("`-y`"));
+        assert!(validate(&strings(&["-progress", "x"])).is_err());
+        assert!(validate(&strings(&["-loglevel", "quiet"])).is_err());
+        assert!(validate(&strings(&["-i", "b.mp4"])).is_err());
+        assert!(validate(&strings(&["out.mp4"])).is_err());
+        assert!(validate(&strings(&["-tune", "film", "out.mp4"])).is_err());
+        assert!(validate(&strings(&["-an", "extra.mp4"])).is_err());
+        assert!(validate(&strings(&["-sn", "out.mkv"])).is_err());
+    }
+
+    #[test]
+    fn places_input_and_output_options_and_adds_per_preset() {
+        let mut args = [
+            "-hide_banner",
+            "-hwaccel",
+            "cuda",
+            "-i",
+            "in.mov",
+            "-c:v",
+            "x",
+            "out.mp4",
+        ]
+        .map(OsString::from)
+        .to_vec();
+        let extra = ExtraArgs {
+            input: strings(&["-thread_queue_size", "1024"]),
+            output: strings(&["-tune", "film"]),
+        };
+        extra.apply(&mut args);
+        assert_eq!(
+            args,
+            [
+                "-hide_banner",
+                "-hwaccel",
+                "cuda",
+                "-thread_queue_size",
+                "1024",
+                "-i",
+                "in.mov",
+                "-c:v",
+                "x",
+                "-tune",
+                "film",
+                "out.mp4"
+            ]
+        );
+
+        let mut per_preset = PresetArgs::default();
+        per_preset.for_preset_mut(Preset::Quality).output = strings(&["-tune", "grain"]);
+        let cli = ExtraArgs {
+            input: Vec::new(),
+            output: strings(&["-an"]),
+        };
+        let all = per_preset.then_all(&cli);
+        assert_eq!(
+            all.for_preset(Preset::Quality).output,
+            ["-tune", "grain", "-an"]
+        );
+        assert_eq!(all.for_preset(Preset::Auto).output, ["-an"]);
+    }
+}
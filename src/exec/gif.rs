@@ -0,0 +1,210 @@
+use std::ffi::OsString;
+
+use crate::presets::Preset;
+
+/// gif frames last whole hundredths of a second, and players slow faster ones down.
+pub const MAX_FPS: u32 = 50;
+pub const SMALLEST_WIDTH: u32 = 16;
+
+/// Frame rate the size target lowers to before it starts shrinking the picture.
+const FIRST_MIN_FPS: u32 = 10;
+/// Where the size target gives up.
+const MIN_FPS: u32 = 5;
+const MIN_WIDTH: u32 = 160;
+
+/// How `paletteuse` spreads the error of mapping colours onto the 256-colour palette.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Dither {
+    /// an ordered pattern; compresses best.
+    #[default]
+    Bayer,
+    FloydSteinberg,
+    Sierra,
+    /// flat bands of colour, smallest of all.
+    None,
+}
+
+impl Dither {
+    /// Also the name `paletteuse` knows it by.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Dither::Bayer => "bayer",
+            Dither::FloydSteinberg => "floyd_steinberg",
+            Dither::Sierra => "sierra2_4a",
+            Dither::None => "none",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "bayer" => Some(Dither::Bayer),
+            "floyd_steinberg" | "floyd" => Some(Dither::FloydSteinberg),
+            "sierra2_4a" | "sierra" => Some(Dither::Sierra),
+            "none" => Some(Dither::None),
+            _ => None,
+        }
+    }
+}
+
+/// How the gif and webp presets turn a clip into an animation.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct GifSettings {
+    pub fps: u32,
+    /// widest the animation gets; narrower inputs keep their width.
+    pub width: u32,
+    /// times the animation plays; 0 loops forever.
+    pub plays: u32,
+    pub dither: Dither,
+    /// lower the fps, then the width, until the output is at most this many bytes.
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for GifSettings {
+    fn default() -> Self {
+        Self {
+            fps: 15,
+            width: 480,
+            plays: 0,
+            dither: Dither::Bayer,
+            max_bytes: None,
+        }
+    }
+}
+
+impl GifSettings {
+    /// The next try when the output is over `max_bytes`: fewer frames down to 10 fps, then a
+    /// narrower picture, then fewer frames again; `None` once there's nothing left to give.
+    pub fn smaller(&self) -> Option<Self> {
+        let step = |v: u32, floor: u32| (v * 4 / 5).clamp(floor, v - 1);
+        let mut next = *self;
+        if self.fps > FIRST_MIN_FPS {
+            next.fps = step(self.fps, FIRST_MIN_FPS);
+        } else if self.width > MIN_WIDTH {
+            next.width = step(self.width, MIN_WIDTH);
+        } else if self.fps > MIN_FPS {
+            next.fps = step(self.fps, MIN_FPS);
+        } else {
+            return None;
+        }
+        Some(next)
+    }
+}
+
+/// Everything between the input and the output path for `Preset::Gif` (one palette for the
+/// whole clip from `palettegen`, applied by `paletteuse`) or `Preset::Webp`.
+pub fn output_args(preset: Preset, gif: &GifSettings, max_height: Option<u32>) -> Vec<OsString> {
+    let mut filters = vec![
+        format!("fps={}", gif.fps),
+        format!("scale=min(iw\\,{}):-1:flags=lanczos", gif.width),
+    ];
+    if let Some(h) = max_height {
+        filters.push(format!("scale=-1:min(ih\\,{h}):flags=lanczos"));
+    }
+    let chain = filters.join(",");
+
+    let mut args = Vec::<OsString>::new();
+    if preset == Preset::Webp {
+        args.extend(
+            [
+                "-vf",
+                &chain,
+                "-c:v",
+                "libwebp",
+                "-lossless",
+                "0",
+                "-quality",
+                "75",
+                "-compression_level",
+                "4",
+                "-loop",
+                &gif.plays.to_string(),
+            ]
+            .map(OsString::from),
+        );
+        return args;
+    }
+
+    // the gif muxer counts repeats after the first play, with -1 for none.
+    let loops = match gif.plays {
+        0 => 0,
+        1 => -1,
+        n => i64::from(n) - 1,
+    };
+    let graph = format!(
+        "{chain},split[a][b];[a]palettegen=stats_mode=diff[p];\
+[b][p]paletteuse=dither={}:diff_mode=rectangle",
+        gif.dither.as_str()
+    );
+    args.extend(["-vf", &graph, "-loop", &loops.to_string()].map(OsString::from));
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joined(args: &[OsString]) -> String {
+        args.iter()
+            .map(|v| v.to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn gifs_build_a_palette_and_webps_encode_directly() {
+        let gif = GifSettings {
+            plays: 1,
+            dither: Dither::FloydSteinberg,
+            ..GifSettings::default()
+        };
+        let s = joined(&output_args(Preset::Gif, &gif, Some(360)));
+        assert_eq!(
+            s,
+            "-vf fps=15,scale=min(iw\\,480):-1:flags=lanczos,scale=-1:min(ih\\,360):flags=lanczos,\
+split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=dither=floyd_steinberg:\
+diff_mode=rectangle -loop -1"
+        );
+        let forever = joined(&output_args(Preset::Gif, &GifSettings::default(), None));
+        assert!(forever.ends_with("-loop 0"));
+        let thrice = GifSettings {
+            plays: 3,
+            ..GifSettings::default()
+        };
+        assert!(joined(&output_args(Preset::Gif, &thrice, None)).ends_with("-loop 2"));
+
+        let s = joined(&output_args(Preset::Webp, &thrice, None));
+        assert!(s.starts_with("-vf fps=15,scale=min(iw\\,480):-1:flags=lanczos -c:v libwebp"));
+        assert!(!s.contains("palette"));
+        assert!(s.ends_with("-loop 3"));
+    }
+
+    #[test]
+    fn size_target_drops_fps_then_width_then_fps_to_a_floor() {
+        let mut g = GifSettings {
+            fps: 15,
+            width: 200,
+            ..GifSettings::default()
+        };
+        let mut steps = Vec::new();
+        while let Some(next) = g.smaller() {
+            steps.push((next.fps, next.width));
+            g = next;
+        }
+        assert_eq!(
+            steps,
+            [
+                (12, 200),
+                (10, 200),
+                (10, 160),
+                (8, 160),
+                (6, 160),
+                (5, 160)
+            ]
+        );
+        assert_eq!(
+            Dither::parse("Floyd-Steinberg"),
+            Some(Dither::FloydSteinberg)
+        );
+        assert_eq!(Dither::parse("ordered"), None);
+    }
+}
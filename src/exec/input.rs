@@ -1,11 +1,72 @@
 use std::path::Path;
 
+use crate::exec::probe::{MediaInfo, StreamKind};
+
+/// Inputs taken on their extension alone; anything else is probed unless `input.sniff` is off.
+pub const VIDEO_EXTENSIONS: [&str; 17] = [
+    "mp4", "mov", "mkv", "webm", "avi", "wmv", "flv", "m4v", "mpg", "mpeg", "ts", "m2ts", "mts",
+    "3gp", "vob", "ogv", "asx",
+];
+
+/// Codecs of "video" streams that are really one picture, like an image or an mp3's cover art.
+const STILL_CODECS: [&str; 5] = ["mjpeg", "png", "bmp", "tiff", "webp"];
+
+/// Whether `path` has one of the `VIDEO_EXTENSIONS`.
 pub fn is_supported_video(path: &Path) -> bool {
     let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
         return false;
     };
-    matches!(
-        ext.to_ascii_lowercase().as_str(),
-        "mp4" | "mov" | "avi" | "webm" | "ogv" | "asx" | "mpeg" | "m4v" | "wmv" | "mpg"
-    )
+    VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())
+}
+
+/// Whether ffmpeg can open `path` and finds a moving video stream in it, whatever it's called.
+pub fn sniff(ffmpeg: &Path, path: &Path) -> bool {
+    let found = crate::exec::probe::probe(ffmpeg, path).is_ok_and(|info| has_video(&info));
+    log::debug!("sniffed {}: video {found}", path.display());
+    found
+}
+
+/// Whether a probed file has a moving video stream, so it's worth taking as an input.
+pub fn has_video(info: &MediaInfo) -> bool {
+    info.streams_of(StreamKind::Video)
+        .any(|v| !STILL_CODECS.contains(&v.codec.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_list_covers_common_containers_case_insensitively() {
+        for name in [
+            "a.mkv", "b.FLV", "c.m2ts", "d.3gp", "e.MTS", "f.vob", "g.mp4",
+        ] {
+            assert!(is_supported_video(Path::new(name)), "{name}");
+        }
+        assert!(!is_supported_video(Path::new("notes.txt")));
+        assert!(!is_supported_video(Path::new("recording")));
+        assert!(is_supported_video(Path::new("a.ts")));
+    }
+
+    #[test]
+    fn cover_art_and_audio_are_not_video() {
+        use crate::exec::probe::StreamInfo;
+
+        let stream = |kind, codec: &str| StreamInfo {
+            kind,
+            codec: codec.to_string(),
+            ..Default::default()
+        };
+        let mut info = MediaInfo {
+            streams: vec![
+                stream(StreamKind::Audio, "mp3"),
+                stream(StreamKind::Video, "mjpeg"),
+            ],
+            ..Default::default()
+        };
+        assert!(!has_video(&info));
+        info.streams.push(stream(StreamKind::Video, "h264"));
+        assert!(has_video(&info));
+        assert!(!has_video(&MediaInfo::default()));
+    }
 }
@@ -0,0 +1,222 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Result;
+
+/// Folder in the app folder that keeps one log per encode.
+const LOG_DIR_NAME: &str = "logs";
+
+/// Logs kept in the folder; the oldest are deleted as new ones start.
+const MAX_LOGS: usize = 50;
+
+static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Appends every job's log to `path` instead of the log folder (`--log-file`).
+pub fn set_log_file(path: PathBuf) {
+    let _ = LOG_FILE.set(path);
+}
+
+pub fn log_dir() -> Result<PathBuf> {
+    Ok(crate::paths::app_root_dir()?.join(LOG_DIR_NAME))
+}
+
+/// Where the encode of `input` logs: the `--log-file`, or a new file in the log folder.
+pub fn path_for(input: &Path) -> Result<PathBuf> {
+    if let Some(p) = LOG_FILE.get() {
+        return Ok(p.clone());
+    }
+    let dir = log_dir()?;
+    rotate(&dir, MAX_LOGS - 1)?;
+    Ok(path_in(&dir, input, SystemTime::now()))
+}
+
+/// `<unix millis>-<input stem>.log`; the padded time keeps the names in start order.
+fn path_in(dir: &Path, input: &Path, now: SystemTime) -> PathBuf {
+    let ms = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    dir.join(format!("{ms:013}-{stem}.log"))
+}
+
+/// Deletes the oldest logs in `dir` until at most `keep` are left.
+fn rotate(dir: &Path, keep: usize) -> Result<()> {
+    let mut logs = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "log"))
+            .collect::<Vec<_>>(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    logs.sort();
+    let excess = logs.len().saturating_sub(keep);
+    for old in &logs[..excess] {
+        // one still open elsewhere goes next time.
+        let _ = std::fs::remove_file(old);
+    }
+    Ok(())
+}
+
+/// One ffmpeg run's log, appended to line by line.
+pub struct JobLog {
+    file: File,
+    /// put in front of every line, so runs sharing a file can be told apart.
+    tag: Option<String>,
+}
+
+impl JobLog {
+    pub fn line(&mut self, line: &str) -> std::io::Result<()> {
+        let text = match &self.tag {
+            Some(tag) => format!("[{tag}] {line}\n"),
+            None => format!("{line}\n"),
+        };
+        // one write per line, so files encoding at once don't split each other's lines.
+        self.file.write_all(text.as_bytes())
+    }
+}
+
+/// Opens `path` for one ffmpeg run, appending, headed by the command line. In the `--log-file`,
+/// which files encoding at once share, every line is tagged with the run's output name.
+pub fn open(path: &Path, ffmpeg: &Path, args: &[OsString]) -> Result<JobLog> {
+    let shared = LOG_FILE.get().is_some_and(|p| p == path);
+    let tag = args
+        .last()
+        .filter(|_| shared)
+        .and_then(|out| Path::new(out).file_name())
+        .map(|name| name.to_string_lossy().into_owned());
+    open_tagged(path, tag, ffmpeg, args)
+}
+
+fn open_tagged(
+    path: &Path,
+    tag: Option<String>,
+    ffmpeg: &Path,
+    args: &[OsString],
+) -> Result<JobLog> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let mut log = JobLog { file, tag };
+    log.line(&format!(
+        "$ {}",
+        crate::process::run::command_line(ffmpeg, args)
+    ))?;
+    Ok(log)
+}
+
+/// The whole log, line by line, for the viewer.
+pub fn read_lines(path: &Path) -> Result<Vec<String>> {
+    let bytes = std::fs::read(path)?;
+    Ok(String::from_utf8_lossy(&bytes)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn names_sort_in_start_order_and_rotation_drops_the_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = &Path::new("in").join("my clip.mov");
+        let early = path_in(dir.path(), input, UNIX_EPOCH + Duration::from_millis(999));
+        let late = path_in(
+            dir.path(),
+            input,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        );
+        assert_eq!(
+            early.file_name().unwrap().to_string_lossy(),
+            "0000000000999-my clip.log"
+        );
+        assert!(early < late);
+
+        for i in 0..5u64 {
+            let p = path_in(dir.path(), input, UNIX_EPOCH + Duration::from_secs(i));
+            std::fs::write(p, b"x").unwrap();
+        }
+        std::fs::write(dir.path().join("notes.txt"), b"x").unwrap();
+        rotate(dir.path(), 2).unwrap();
+
+        let mut left = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                "0000000003000-my clip.log",
+                "0000000004000-my clip.log",
+                "notes.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn appends_each_run_under_its_command_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("sub").join("job.log");
+        let args = [OsString::from("-i"), OsString::from("a.mp4")];
+        let mut f = open(&log, Path::new("ffmpeg"), &args).unwrap();
+        f.line("frame=1").unwrap();
+        drop(f);
+        open(&log, Path::new("ffmpeg"), &[]).unwrap();
+
+        assert_eq!(
+            read_lines(&log).unwrap(),
+            ["$ ffmpeg -i a.mp4", "frame=1", "$ ffmpeg"]
+        );
+    }
+
+    #[test]
+    fn a_shared_log_tags_each_line_with_its_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("run.log");
+        let args = [
+            OsString::from("-i"),
+            OsString::from("a.mp4"),
+            OsString::from("a.small.mp4"),
+        ];
+        let mut a = open_tagged(
+            &log,
+            Some("a.small.mp4".to_string()),
+            Path::new("ffmpeg"),
+            &args,
+        )
+        .unwrap();
+        let mut b = open_tagged(
+            &log,
+            Some("b.small.mp4".to_string()),
+            Path::new("ffmpeg"),
+            &[],
+        )
+        .unwrap();
+        a.line("frame=1").unwrap();
+        b.line("frame=2").unwrap();
+
+        assert_eq!(
+            read_lines(&log).unwrap(),
+            [
+                "[a.small.mp4] $ ffmpeg -i a.mp4 a.small.mp4",
+                "[b.small.mp4] $ ffmpeg",
+                "[a.small.mp4] frame=1",
+                "[b.small.mp4] frame=2"
+            ]
+        );
+    }
+}
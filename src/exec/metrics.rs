@@ -0,0 +1,310 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use crate::error::{Result, TinythisError};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Metric {
+    Vmaf,
+    Ssim,
+    Psnr,
+}
+
+impl Metric {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Metric::Vmaf => "vmaf",
+            Metric::Ssim => "ssim",
+            Metric::Psnr => "psnr",
+        }
+    }
+}
+
+/// Quality of an output compared to its input; vmaf is `None` without libvmaf.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Scores {
+    pub ssim: Option<f64>,
+    pub psnr: Option<f64>,
+    pub vmaf: Option<f64>,
+}
+
+impl Scores {
+    pub fn get(&self, metric: Metric) -> Option<f64> {
+        match metric {
+            Metric::Vmaf => self.vmaf,
+            Metric::Ssim => self.ssim,
+            Metric::Psnr => self.psnr,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(v) = self.ssim {
+            parts.push(format!("ssim {v:.4}"));
+        }
+        if let Some(v) = self.psnr {
+            parts.push(format!("psnr {v:.2} dB"));
+        }
+        if let Some(v) = self.vmaf {
+            parts.push(format!("vmaf {v:.2}"));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Smallest acceptable score for one metric, written as `vmaf=93`, `ssim=0.97` or `psnr=38`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct QualityThreshold {
+    pub metric: Metric,
+    pub min: f64,
+}
+
+impl QualityThreshold {
+    pub fn parse(s: &str) -> std::result::Result<Self, String> {
+        let err = || format!("expected <vmaf|ssim|psnr>=<score>, got `{s}`");
+        let (metric, min) = s.split_once(['=', ':']).ok_or_else(err)?;
+        let metric = match metric.trim().to_ascii_lowercase().as_str() {
+            "vmaf" => Metric::Vmaf,
+            "ssim" => Metric::Ssim,
+            "psnr" => Metric::Psnr,
+            _ => return Err(err()),
+        };
+        let min = min.trim().parse::<f64>().map_err(|_| err())?;
+        Ok(Self { metric, min })
+    }
+
+    /// Whether `scores` fall below the threshold; a missing score never counts as below.
+    pub fn is_below(&self, scores: &Scores) -> bool {
+        scores.get(self.metric).is_some_and(|v| v < self.min)
+    }
+}
+
+impl std::fmt::Display for QualityThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.metric.as_str(), self.min)
+    }
+}
+
+/// Whether this ffmpeg build ships the libvmaf filter.
+pub fn has_libvmaf(ffmpeg: &Path) -> bool {
+    crate::exec::caps::detect(ffmpeg).is_ok_and(|c| c.has_filter("libvmaf"))
+}
+
+/// Scores `distorted` against `reference` with ssim and psnr, plus vmaf when `with_vmaf`.
+///
+/// Frames are re-timed (and resampled to `fps` when given) and the output is scaled to
+/// the reference size, the same graph `tests/vmaf.rs` uses. An output trimmed to `trim_secs` is
+/// compared with the same stretch of the reference.
+pub fn measure(
+    ffmpeg: &Path,
+    reference: &Path,
+    distorted: &Path,
+    fps: Option<f64>,
+    trim_secs: Option<u32>,
+    with_vmaf: bool,
+) -> Result<Scores> {
+    let fps = fps.map(|v| format!(",fps={v}")).unwrap_or_default();
+    let branches = if with_vmaf { 3 } else { 2 };
+    let mut filter = format!(
+        "[0:v]setpts=PTS-STARTPTS{fps},format=yuv420p[dist];\
+[1:v]setpts=PTS-STARTPTS{fps},format=yuv420p[ref];\
+[dist][ref]scale2ref[dist2][ref2];\
+[dist2]split={branches}[d0][d1]{d2};[ref2]split={branches}[r0][r1]{r2};\
+[d0][r0]ssim;[d1][r1]psnr",
+        d2 = if with_vmaf { "[d2]" } else { "" },
+        r2 = if with_vmaf { "[r2]" } else { "" },
+    );
+    if with_vmaf {
+        filter.push_str(&format!(";[d2][r2]libvmaf=n_threads={}", vmaf_threads()));
+    }
+
+    let stderr = run_filter(
+        ffmpeg,
+        &filter_args(reference, distorted, trim_secs, &filter),
+    )?;
+    let scores = Scores {
+        ssim: parse_ssim(&stderr),
+        psnr: parse_psnr(&stderr),
+        vmaf: if with_vmaf {
+            parse_vmaf_score(&stderr)
+        } else {
+            None
+        },
+    };
+    if scores.ssim.is_none() && scores.psnr.is_none() {
+        return Err(TinythisError::ProcessFailed {
+            program: ffmpeg.display().to_string(),
+            code: None,
+            stderr: stderr.trim().to_string(),
+        });
+    }
+    Ok(scores)
+}
+
+/// Scores `distorted` against `reference` with ffmpeg's libvmaf filter (0-100).
+///
+/// Both inputs must have the same resolution and frame count.
+pub fn vmaf(ffmpeg: &Path, reference: &Path, distorted: &Path) -> Result<f64> {
+    let filter = format!(
+        "[0:v]setpts=PTS-STARTPTS[dist];[1:v]setpts=PTS-STARTPTS[ref];\
+[dist][ref]libvmaf=n_threads={}",
+        vmaf_threads()
+    );
+    let stderr = run_filter(ffmpeg, &filter_args(reference, distorted, None, &filter))?;
+    parse_vmaf_score(&stderr).ok_or_else(|| TinythisError::ProcessFailed {
+        program: ffmpeg.display().to_string(),
+        code: None,
+        stderr: stderr.trim().to_string(),
+    })
+}
+
+fn vmaf_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Arguments running `filter` over `[0]` = distorted and `[1]` = reference, the reference cut to
+/// its first `reference_secs`.
+fn filter_args(
+    reference: &Path,
+    distorted: &Path,
+    reference_secs: Option<u32>,
+    filter: &str,
+) -> Vec<OsString> {
+    let mut args = vec![
+        OsString::from("-hide_banner"),
+        OsString::from("-nostdin"),
+        OsString::from("-nostats"),
+        OsString::from("-i"),
+        distorted.as_os_str().to_owned(),
+    ];
+    if let Some(secs) = reference_secs {
+        args.extend([OsString::from("-t"), OsString::from(secs.to_string())]);
+    }
+    args.extend([
+        OsString::from("-i"),
+        reference.as_os_str().to_owned(),
+        OsString::from("-lavfi"),
+        OsString::from(filter),
+        OsString::from("-f"),
+        OsString::from("null"),
+        OsString::from("-"),
+    ]);
+    args
+}
+
+/// Runs a `filter_args` command and returns ffmpeg's stderr.
+fn run_filter(ffmpeg: &Path, args: &[OsString]) -> Result<String> {
+    let output = crate::process::run::command(ffmpeg, args).output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    if stderr.contains("No such filter: 'libvmaf'") {
+        return Err(TinythisError::InvalidArgs(
+            "this ffmpeg build has no libvmaf filter; vmaf scoring needs it".to_string(),
+        ));
+    }
+    if !output.status.success() {
+        return Err(TinythisError::ProcessFailed {
+            program: ffmpeg.display().to_string(),
+            code: output.status.code(),
+            stderr: stderr.trim().to_string(),
+        });
+    }
+    Ok(stderr)
+}
+
+fn parse_vmaf_score(stderr: &str) -> Option<f64> {
+    // example: "[Parsed_libvmaf_2 @ 0000020d] VMAF score: 93.412345"
+    stderr
+        .lines()
+        .rev()
+        .find_map(|l| l.split_once("VMAF score: "))
+        .and_then(|(_, v)| v.trim().parse().ok())
+}
+
+fn parse_ssim(stderr: &str) -> Option<f64> {
+    // example: "[Parsed_ssim_6 @ 0x55] SSIM Y:0.990 (20.0) U:0.995 (23.0) V:0.994 (22.4) All:0.9917 (20.8)"
+    let line = stderr.lines().rev().find(|l| l.contains("] SSIM "))?;
+    let (_, rest) = line.split_once("All:")?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+fn parse_psnr(stderr: &str) -> Option<f64> {
+    // example: "[Parsed_psnr_7 @ 0x55] PSNR y:41.20 u:45.01 v:44.87 average:42.13 min:38.90 max:47.02"
+    let line = stderr.lines().rev().find(|l| l.contains("] PSNR "))?;
+    let (_, rest) = line.split_once("average:")?;
+    let v = rest.split_whitespace().next()?;
+    // identical frames report "inf".
+    if v == "inf" {
+        return Some(f64::INFINITY);
+    }
+    v.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR: &str = "\
+[libvmaf @ 0x55] version 3.0.0
+[Parsed_ssim_6 @ 0x56] SSIM Y:0.990 (20.0) U:0.995 (23.0) V:0.994 (22.4) All:0.991700 (20.8)
+[Parsed_psnr_7 @ 0x57] PSNR y:41.20 u:45.01 v:44.87 average:42.13 min:38.90 max:47.02
+[Parsed_libvmaf_8 @ 0x58] VMAF score: 93.412345
+";
+
+    #[test]
+    fn parses_scores_from_filter_log_lines() {
+        assert_eq!(parse_vmaf_score(STDERR), Some(93.412345));
+        assert_eq!(parse_ssim(STDERR), Some(0.9917));
+        assert_eq!(parse_psnr(STDERR), Some(42.13));
+        assert_eq!(parse_vmaf_score("Error opening input"), None);
+    }
+
+    #[test]
+    fn trimmed_outputs_are_scored_against_the_same_stretch() {
+        let joined = |secs| {
+            filter_args(Path::new("in.mp4"), Path::new("out.mp4"), secs, "ssim")
+                .iter()
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(
+            joined(Some(30)),
+            "-hide_banner -nostdin -nostats -i out.mp4 -t 30 -i in.mp4 -lavfi ssim -f null -"
+        );
+        assert!(!joined(None).contains("-t "));
+    }
+
+    #[test]
+    fn thresholds_parse_and_only_flag_measured_scores() {
+        let t = QualityThreshold::parse("vmaf=93").unwrap();
+        assert_eq!(t.metric, Metric::Vmaf);
+        assert_eq!(t.to_string(), "vmaf=93");
+        assert_eq!(
+            QualityThreshold::parse("SSIM:0.97").unwrap().metric,
+            Metric::Ssim
+        );
+        assert!(QualityThreshold::parse("vmaf").is_err());
+        assert!(QualityThreshold::parse("butteraugli=3").is_err());
+
+        let scores = Scores {
+            ssim: Some(0.98),
+            psnr: Some(40.0),
+            vmaf: None,
+        };
+        assert!(
+            QualityThreshold::parse("ssim=0.99")
+                .unwrap()
+                .is_below(&scores)
+        );
+        assert!(
+            !QualityThreshold::parse("psnr=38")
+                .unwrap()
+                .is_below(&scores)
+        );
+        assert!(!t.is_below(&scores));
+        assert_eq!(scores.summary(), "ssim 0.9800, psnr 40.00 dB");
+    }
+}
@@ -1,2 +1,16 @@
+pub mod caps;
 pub mod compress;
+pub mod container;
+pub mod crf_search;
+pub mod extra_args;
+pub mod gif;
 pub mod input;
+pub mod joblog;
+pub mod metrics;
+pub mod pool;
+pub mod probe;
+pub mod progress;
+pub mod queue;
+pub mod report;
+pub mod split;
+pub mod watch;
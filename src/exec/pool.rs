@@ -0,0 +1,118 @@
+use std::sync::Mutex;
+
+/// Most files encoded at once; every encode already spreads over several cores.
+pub const MAX_JOBS: u8 = 8;
+
+/// Runs `work` on each item, up to `jobs` at a time, starting them in order.
+///
+/// Once an item fails no new one starts, and the first error comes back once the running ones
+/// end, next to the results of every item that finished (in item order).
+pub fn run<T, R, E>(
+    jobs: u8,
+    items: Vec<T>,
+    work: impl Fn(T) -> Result<R, E> + Sync,
+) -> (Vec<R>, Option<E>)
+where
+    T: Send,
+    R: Send,
+    E: Send,
+{
+    let workers = usize::from(jobs.max(1)).min(items.len());
+    if workers <= 1 {
+        let mut done = Vec::with_capacity(items.len());
+        for item in items {
+            match work(item) {
+                Ok(r) => done.push(r),
+                Err(e) => return (done, Some(e)),
+            }
+        }
+        return (done, None);
+    }
+
+    let n = items.len();
+    let pending = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new((0..n).map(|_| None).collect::<Vec<Option<R>>>());
+    let failed = Mutex::new(None::<E>);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    if failed.lock().unwrap().is_some() {
+                        return;
+                    }
+                    let Some((i, item)) = pending.lock().unwrap().next() else {
+                        return;
+                    };
+                    match work(item) {
+                        Ok(r) => results.lock().unwrap()[i] = Some(r),
+                        Err(e) => {
+                            failed.lock().unwrap().get_or_insert(e);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    let done = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect();
+    (done, failed.into_inner().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn runs_at_most_jobs_at_once_and_keeps_the_order() {
+        let (now, most) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let out = run(3, (0..12).collect(), |i: u32| {
+            let n = now.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(n, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            now.fetch_sub(1, Ordering::SeqCst);
+            Ok::<_, ()>(i * 2)
+        });
+        assert_eq!(out.1, None);
+        assert_eq!(out.0, (0..12).map(|i| i * 2).collect::<Vec<_>>());
+        assert!(most.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[test]
+    fn stops_starting_items_after_a_failure() {
+        let started = AtomicUsize::new(0);
+        let res = run(1, (0..5).collect(), |i: u32| {
+            started.fetch_add(1, Ordering::SeqCst);
+            if i == 1 {
+                Err(format!("{i} failed"))
+            } else {
+                Ok(i)
+            }
+        });
+        assert_eq!(res, (vec![0], Some("1 failed".to_string())));
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+
+        let res = run(2, (0..5).collect(), |i: u32| {
+            if i == 0 { Err("first") } else { Ok(i) }
+        });
+        assert_eq!(res.1, Some("first"));
+    }
+
+    #[test]
+    fn keeps_what_finished_before_a_failure() {
+        let (done, failed) = run(2, (0..4).collect(), |i: u32| {
+            if i == 3 {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                return Err("last");
+            }
+            Ok(i)
+        });
+        assert_eq!(done, [0, 1, 2]);
+        assert_eq!(failed, Some("last"));
+    }
+}
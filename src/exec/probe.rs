@@ -0,0 +1,277 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use crate::error::{Result, TinythisError};
+
+#[derive(Debug, Clone, Default)]
+pub struct MediaInfo {
+    pub duration_us: Option<u64>,
+    pub bitrate_kbps: Option<u64>,
+    pub creation_time: Option<String>,
+    pub streams: Vec<StreamInfo>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StreamInfo {
+    pub index: usize,
+    pub kind: StreamKind,
+    pub codec: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<f64>,
+    pub bitrate_kbps: Option<u64>,
+    /// pixel format of a video stream, e.g. `yuv420p`.
+    pub pix_fmt: Option<String>,
+    /// degrees from the display matrix, for phone videos recorded sideways.
+    pub rotation: Option<f64>,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum StreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    #[default]
+    Other,
+}
+
+impl MediaInfo {
+    pub fn streams_of(&self, kind: StreamKind) -> impl Iterator<Item = &StreamInfo> {
+        self.streams.iter().filter(move |s| s.kind == kind)
+    }
+}
+
+/// Reads container and stream info from the banner `ffmpeg -i` prints.
+///
+/// ffmpeg exits with an error when no output is given, so the exit code is
+/// ignored and the stderr is parsed as long as it describes an input.
+pub fn probe(ffmpeg: &Path, input: &Path) -> Result<MediaInfo> {
+    let args = [
+        OsString::from("-hide_banner"),
+        OsString::from("-nostdin"),
+        OsString::from("-i"),
+        input.as_os_str().to_owned(),
+    ];
+    let output = crate::process::run::command(ffmpeg, &args).output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    if !stderr.contains("Input #0") {
+        return Err(TinythisError::ProcessFailed {
+            program: ffmpeg.display().to_string(),
+            code: output.status.code(),
+            stderr: stderr.trim().to_string(),
+        });
+    }
+
+    Ok(parse_input_banner(&stderr))
+}
+
+fn parse_input_banner(stderr: &str) -> MediaInfo {
+    let mut info = MediaInfo::default();
+    let mut in_input = false;
+
+    for line in stderr.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("Input #0") {
+            in_input = true;
+            continue;
+        }
+        if !in_input {
+            continue;
+        }
+        if trimmed.starts_with("Input #") || trimmed.starts_with("Output #") {
+            break;
+        }
+
+        if let Some(stream) = parse_stream_line(trimmed) {
+            info.streams.push(stream);
+            continue;
+        }
+
+        // example: "displaymatrix: rotation of -90.00 degrees", under the stream's side data.
+        if let Some((_, deg)) = trimmed.split_once("rotation of ")
+            && let Some(stream) = info.streams.last_mut()
+        {
+            stream.rotation = deg
+                .trim_end_matches("degrees")
+                .trim()
+                .parse()
+                .ok()
+                .filter(|d: &f64| *d != 0.0);
+            continue;
+        }
+
+        if info.duration_us.is_none()
+            && let Some(us) = crate::exec::compress::parse_duration_us_from_stderr_line(trimmed)
+        {
+            info.duration_us = Some(us);
+            info.bitrate_kbps = trimmed
+                .split_once("bitrate: ")
+                .and_then(|(_, b)| parse_kbps(b));
+            continue;
+        }
+
+        // global metadata is listed before the first stream.
+        if info.streams.is_empty()
+            && info.creation_time.is_none()
+            && let Some(v) = metadata_value(trimmed, "creation_time")
+        {
+            info.creation_time = Some(v.to_string());
+        }
+    }
+
+    info
+}
+
+fn parse_stream_line(line: &str) -> Option<StreamInfo> {
+    // example: "Stream #0:1[0x2](eng): Audio: aac (LC) (mp4a / 0x6134706D), 48000 Hz, ..."
+    let rest = line.strip_prefix("Stream #0:")?;
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    let index = rest[..digits].parse::<usize>().ok()?;
+
+    let (_, desc) = rest.split_once(": ")?;
+    let (kind, desc) = desc.split_once(": ").unwrap_or((desc, ""));
+    let kind = match kind.trim() {
+        "Video" => StreamKind::Video,
+        "Audio" => StreamKind::Audio,
+        "Subtitle" => StreamKind::Subtitle,
+        _ => StreamKind::Other,
+    };
+
+    let codec = desc
+        .split([' ', ','])
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let mut stream = StreamInfo {
+        index,
+        kind,
+        codec,
+        ..StreamInfo::default()
+    };
+
+    if kind == StreamKind::Video {
+        // the pixel format follows the codec: "h264 (High) (...), yuv420p(tv, bt709), ..."
+        stream.pix_fmt = desc
+            .split(',')
+            .nth(1)
+            .and_then(|p| p.trim().split('(').next())
+            .filter(|p| !p.is_empty() && !p.contains(' '))
+            .map(str::to_string);
+    }
+
+    // parts like "yuv420p(tv, bt709)" also get split here; they just don't match anything.
+    for part in desc.split(',').map(str::trim) {
+        if let Some((w, h)) = parse_dimensions(part) {
+            stream.width.get_or_insert(w);
+            stream.height.get_or_insert(h);
+        } else if let Some(fps) = part.strip_suffix(" fps") {
+            stream.fps = parse_si(fps);
+        } else if stream.bitrate_kbps.is_none() {
+            stream.bitrate_kbps = parse_kbps(part);
+        }
+    }
+
+    Some(stream)
+}
+
+fn parse_dimensions(part: &str) -> Option<(u32, u32)> {
+    // example: "1920x1080 [SAR 1:1 DAR 16:9]"
+    let token = part.split(' ').next()?;
+    let (w, h) = token.split_once('x')?;
+    let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+    (w > 0 && h > 0).then_some((w, h))
+}
+
+fn parse_kbps(s: &str) -> Option<u64> {
+    let v = s.trim().split(' ').collect::<Vec<_>>();
+    match v.as_slice() {
+        [n, "kb/s", ..] => n.parse().ok(),
+        _ => None,
+    }
+}
+
+fn parse_si(s: &str) -> Option<f64> {
+    let s = s.trim();
+    match s.strip_suffix('k') {
+        Some(n) => n.parse::<f64>().ok().map(|v| v * 1000.0),
+        None => s.parse().ok(),
+    }
+}
+
+fn metadata_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let (k, v) = line.split_once(':')?;
+    if k.trim() != key {
+        return None;
+    }
+    Some(v.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BANNER: &str = "\
+Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'in.mov':
+  Metadata:
+    major_brand     : qt
+    creation_time   : 2024-05-01T10:20:30.000000Z
+  Duration: 00:00:08.05, start: 0.000000, bitrate: 4321 kb/s
+  Stream #0:0[0x1](und): Video: h264 (High) (avc1 / 0x31637661), yuv420p(progressive), 1920x1080, 4000 kb/s, 30 fps, 30 tbr, 15360 tbn (default)
+      Metadata:
+        creation_time   : 2024-05-01T10:20:31.000000Z
+  Stream #0:1[0x2](eng): Audio: aac (LC) (mp4a / 0x6134706D), 48000 Hz, stereo, fltp, 128 kb/s (default)
+  Stream #0:2(eng): Subtitle: subrip
+  Stream #0:3: Data: none (tmcd / 0x64636D74)
+At least one output file must be specified";
+
+    #[test]
+    fn parses_streams_and_global_creation_time() {
+        let info = parse_input_banner(BANNER);
+        assert_eq!(
+            info.creation_time.as_deref(),
+            Some("2024-05-01T10:20:30.000000Z")
+        );
+
+        let kinds = info.streams.iter().map(|s| s.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                StreamKind::Video,
+                StreamKind::Audio,
+                StreamKind::Subtitle,
+                StreamKind::Other
+            ]
+        );
+        assert_eq!(info.duration_us, Some(8_050_000));
+        assert_eq!(info.bitrate_kbps, Some(4321));
+        assert_eq!(info.streams[0].codec, "h264");
+        assert_eq!(info.streams[0].width, Some(1920));
+        assert_eq!(info.streams[0].height, Some(1080));
+        assert_eq!(info.streams[0].fps, Some(30.0));
+        assert_eq!(info.streams[0].bitrate_kbps, Some(4000));
+        assert_eq!(info.streams[0].pix_fmt.as_deref(), Some("yuv420p"));
+        assert_eq!(info.streams[0].rotation, None);
+        assert_eq!(info.streams[1].bitrate_kbps, Some(128));
+        assert_eq!(info.streams[1].codec, "aac");
+        assert_eq!(info.streams[2].codec, "subrip");
+        assert_eq!(info.streams[2].index, 2);
+    }
+
+    #[test]
+    fn parses_rotation_side_data() {
+        let banner = "\
+Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'phone.mp4':
+  Duration: 00:00:05.00, start: 0.000000, bitrate: 9000 kb/s
+  Stream #0:0[0x1](eng): Video: hevc (Main 10) (hvc1 / 0x31637668), yuv420p10le(tv, bt2020nc/bt2020/arib-std-b67), 1920x1080, 8900 kb/s, 30 fps (default)
+      Side data:
+        displaymatrix: rotation of -90.00 degrees
+  Stream #0:1[0x2](eng): Audio: aac (LC) (mp4a / 0x6134706D), 44100 Hz, stereo, fltp, 96 kb/s (default)
+";
+        let info = parse_input_banner(banner);
+        assert_eq!(info.streams[0].pix_fmt.as_deref(), Some("yuv420p10le"));
+        assert_eq!(info.streams[0].rotation, Some(-90.0));
+        assert_eq!(info.streams[1].rotation, None);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::exec::report::format_bytes;
+
+/// Where an encode is, from one block of ffmpeg's `-progress` output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EncodeProgress {
+    pub percent: Option<u8>,
+    /// how much of the output has been written, in media time.
+    pub out_us: u64,
+    /// expected output length; 0 while unknown.
+    pub total_us: u64,
+    pub fps: Option<f64>,
+    /// media time encoded per second of wall time (ffmpeg's `1.5x`).
+    pub speed: Option<f64>,
+    pub bitrate_kbps: Option<f64>,
+    pub size_bytes: Option<u64>,
+}
+
+impl EncodeProgress {
+    /// Media time still to encode in this file, when the length is known.
+    pub fn remaining_us(&self) -> Option<u64> {
+        (self.total_us > 0).then(|| self.total_us.saturating_sub(self.out_us))
+    }
+
+    pub fn eta_secs(&self) -> Option<u64> {
+        eta_secs(self.remaining_us()?, self.speed)
+    }
+
+    /// `120 fps  3.10x  2450 kbit/s  12.3 MB  eta 0:42`, leaving out what ffmpeg hasn't said.
+    pub fn stats_line(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(fps) = self.fps {
+            parts.push(format!("{fps:.0} fps"));
+        }
+        if let Some(speed) = self.speed {
+            parts.push(format!("{speed:.2}x"));
+        }
+        if let Some(kbps) = self.bitrate_kbps {
+            parts.push(format!("{kbps:.0} kbit/s"));
+        }
+        if let Some(bytes) = self.size_bytes {
+            parts.push(format_bytes(bytes));
+        }
+        if let Some(eta) = self.eta_secs() {
+            parts.push(format!("eta {}", format_clock(eta)));
+        }
+        parts.join("  ")
+    }
+}
+
+/// Wall time to encode `media_us` more at `speed`; unknown until ffmpeg reports a speed.
+pub fn eta_secs(media_us: u64, speed: Option<f64>) -> Option<u64> {
+    let speed = speed.filter(|s| *s > 0.0)?;
+    Some((media_us as f64 / 1_000_000.0 / speed).round() as u64)
+}
+
+/// Time left for a whole batch: this file's rest plus `later_us` of input still queued, at the
+/// current speed, so long files weigh more than short ones.
+pub fn batch_eta_secs(current: &EncodeProgress, later_us: u64) -> Option<u64> {
+    eta_secs(current.remaining_us()? + later_us, current.speed)
+}
+
+/// `m:ss`, or `h:mm:ss` from an hour up.
+pub fn format_clock(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+/// Collects `key=value` lines from `-progress pipe:1` into a snapshot per block.
+#[derive(Debug, Default)]
+pub struct ProgressParser {
+    current: EncodeProgress,
+}
+
+impl ProgressParser {
+    /// Feeds one line; a block's closing `progress=` line hands back the snapshot.
+    pub fn feed(&mut self, line: &str, total_us: u64) -> Option<EncodeProgress> {
+        let (key, val) = line.split_once('=')?;
+        let val = val.trim();
+        let p = &mut self.current;
+        match key.trim() {
+            // both are microseconds; `out_time_ms` is misnamed in ffmpeg.
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = val.parse() {
+                    p.out_us = us;
+                }
+            }
+            "fps" => p.fps = val.parse().ok(),
+            "speed" => p.speed = val.trim_end_matches('x').trim().parse().ok(),
+            "bitrate" => p.bitrate_kbps = val.trim_end_matches("kbits/s").trim().parse().ok(),
+            "total_size" => p.size_bytes = val.parse().ok(),
+            "progress" => {
+                let seen_end = val == "end";
+                p.total_us = total_us;
+                p.percent = compute_percent(p.out_us, total_us, seen_end);
+                if seen_end {
+                    p.percent = Some(100);
+                }
+                return Some(p.clone());
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+fn compute_percent(out_us: u64, total_us: u64, seen_end: bool) -> Option<u8> {
+    if total_us == 0 {
+        return None;
+    }
+    let raw = ((out_us as u128) * 100u128) / (total_us as u128);
+    let mut pct = raw.min(100) as u8;
+    if !seen_end {
+        pct = pct.min(99);
+    }
+    Some(pct)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: &str = "frame=240
+fps=119.52
+stream_0_0_q=28.0
+bitrate=2450.3kbits/s
+total_size=3145728
+out_time_us=4000000
+out_time_ms=4000000
+out_time=00:00:04.000000
+dup_frames=0
+drop_frames=0
+speed=2.01x
+progress=continue
+";
+
+    #[test]
+    fn parses_a_progress_block_into_stats() {
+        let mut parser = ProgressParser::default();
+        let mut snapshots = BLOCK
+            .lines()
+            .filter_map(|l| parser.feed(l, 10_000_000))
+            .collect::<Vec<_>>();
+        assert_eq!(snapshots.len(), 1);
+
+        let p = snapshots.remove(0);
+        assert_eq!(p.percent, Some(40));
+        assert_eq!(p.out_us, 4_000_000);
+        assert_eq!(p.fps, Some(119.52));
+        assert_eq!(p.speed, Some(2.01));
+        assert_eq!(p.bitrate_kbps, Some(2450.3));
+        assert_eq!(p.size_bytes, Some(3_145_728));
+        // 6s of media left at 2.01x.
+        assert_eq!(p.eta_secs(), Some(3));
+        assert_eq!(
+            p.stats_line(),
+            "120 fps  2.01x  2450 kbit/s  3.0 MB  eta 0:03"
+        );
+    }
+
+    #[test]
+    fn early_blocks_leave_unknown_stats_out() {
+        let mut parser = ProgressParser::default();
+        for l in [
+            "bitrate=N/A",
+            "total_size=N/A",
+            "out_time_us=N/A",
+            "speed=N/A",
+        ] {
+            assert_eq!(parser.feed(l, 0), None);
+        }
+        let p = parser.feed("progress=continue", 0).unwrap();
+        assert_eq!(p, EncodeProgress::default());
+        assert_eq!(p.stats_line(), "");
+
+        let end = parser.feed("progress=end", 0).unwrap();
+        assert_eq!(end.percent, Some(100));
+    }
+
+    #[test]
+    fn batch_eta_weighs_the_files_still_queued() {
+        let p = EncodeProgress {
+            out_us: 30_000_000,
+            total_us: 60_000_000,
+            speed: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(p.eta_secs(), Some(15));
+        assert_eq!(batch_eta_secs(&p, 3_600_000_000), Some(1815));
+        assert_eq!(format_clock(1815), "30:15");
+        assert_eq!(format_clock(3725), "1:02:05");
+        assert_eq!(batch_eta_secs(&EncodeProgress::default(), 1), None);
+    }
+
+    #[test]
+    fn percent_caps_at_99_until_end() {
+        let total = 10_000_000u64;
+        assert_eq!(compute_percent(5_000_000, total, false), Some(50));
+        assert_eq!(compute_percent(10_000_000, total, false), Some(99));
+        assert_eq!(compute_percent(10_000_000, total, true), Some(100));
+        assert_eq!(compute_percent(1, 0, false), None);
+    }
+}
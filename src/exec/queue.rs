@@ -0,0 +1,680 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use fs2::FileExt;
+
+use crate::error::Result;
+use crate::exec::compress::{EncodeSettings, FileOverrides, HwDecode, Preserve};
+use crate::exec::container::Container;
+use crate::exec::extra_args::{self, ExtraArgs};
+use crate::exec::gif::{Dither, GifSettings};
+use crate::exec::metrics::QualityThreshold;
+use crate::exec::split::SplitLimit;
+use crate::presets::Preset;
+use crate::presets::encoder::Encoder;
+
+const QUEUE_FILE_NAME: &str = "queue.txt";
+const LOCK_FILE_NAME: &str = "queue.lock";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ItemStatus {
+    Pending,
+    /// was being encoded when the batch stopped; its output is incomplete.
+    Running,
+    Done,
+    Skipped,
+    Failed,
+}
+
+impl ItemStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ItemStatus::Pending => "pending",
+            ItemStatus::Running => "running",
+            ItemStatus::Done => "done",
+            ItemStatus::Skipped => "skipped",
+            ItemStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(ItemStatus::Pending),
+            "running" => Some(ItemStatus::Running),
+            "done" => Some(ItemStatus::Done),
+            "skipped" => Some(ItemStatus::Skipped),
+            "failed" => Some(ItemStatus::Failed),
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(self, ItemStatus::Done | ItemStatus::Skipped)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueItem {
+    pub input: PathBuf,
+    /// set once the item starts, so a partial output can be found after a crash.
+    pub output: Option<PathBuf>,
+    pub status: ItemStatus,
+    /// what the review screen set for this file alone.
+    pub overrides: FileOverrides,
+}
+
+/// Held by the one batch that owns `queue.txt`; released when the last clone drops.
+#[derive(Debug, Clone)]
+pub struct QueueLock {
+    _file: Arc<File>,
+}
+
+impl PartialEq for QueueLock {
+    // there is a single lock file per app folder.
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// A batch written to `queue.txt` in the app folder as it runs, so it can be resumed.
+///
+/// Only what can't be recovered from options.toml is kept: the preset, the encoder and the
+/// cli-only knobs. Nothing is written until `claim` takes the queue for this batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Queue {
+    pub preset: Preset,
+    pub encoder: Encoder,
+    pub container: Container,
+    pub min_savings: u8,
+    pub target_vmaf: Option<f64>,
+    /// the animation settings, for the gif and webp presets.
+    pub gif: Option<GifSettings>,
+    pub split: Option<SplitLimit>,
+    pub measure: bool,
+    pub min_quality: Option<QualityThreshold>,
+    pub preserve: Preserve,
+    pub cpu_fallback: bool,
+    pub hw_decode: HwDecode,
+    /// `--ffmpeg-input-args`, `--ffmpeg-args` and whatever followed `--`; options.toml's are
+    /// read again on resume.
+    pub extra_args: ExtraArgs,
+    pub items: Vec<QueueItem>,
+    lock: Option<QueueLock>,
+}
+
+impl Queue {
+    pub fn new(settings: &EncodeSettings, inputs: &[PathBuf]) -> Self {
+        let mut queue = Self {
+            preset: settings.preset,
+            encoder: settings.encoder,
+            container: settings.container,
+            min_savings: settings.min_savings,
+            target_vmaf: None,
+            gif: None,
+            split: None,
+            measure: false,
+            min_quality: None,
+            preserve: Preserve::default(),
+            cpu_fallback: false,
+            hw_decode: HwDecode::default(),
+            extra_args: ExtraArgs::default(),
+            items: inputs
+                .iter()
+                .map(|input| QueueItem {
+                    input: input.clone(),
+                    output: None,
+                    status: ItemStatus::Pending,
+                    overrides: FileOverrides::default(),
+                })
+                .collect(),
+            lock: None,
+        };
+        queue.set_settings(settings);
+        queue
+    }
+
+    /// Keeps the batch's settings, e.g. after a resume overrode some of them.
+    pub fn set_settings(&mut self, settings: &EncodeSettings) {
+        self.preset = settings.preset;
+        self.encoder = settings.encoder;
+        self.container = settings.container;
+        self.min_savings = settings.min_savings;
+        self.target_vmaf = settings.target_vmaf;
+        self.gif = settings.preset.is_animated().then_some(settings.gif);
+        self.split = settings.split;
+        self.measure = settings.measure;
+        self.min_quality = settings.min_quality;
+        self.preserve = settings.preserve;
+        self.cpu_fallback = settings.cpu_fallback;
+        self.hw_decode = settings.hw_decode;
+    }
+
+    /// Takes `queue.txt` for this batch. `false` while another tinythis runs one; this batch
+    /// then isn't recorded, rather than replacing that one's queue.
+    pub fn claim(&mut self) -> Result<bool> {
+        if self.lock.is_none() {
+            self.lock = lock_in(&crate::paths::app_root_dir()?)?;
+        }
+        Ok(self.lock.is_some())
+    }
+
+    /// Indices of the items still to do, failed ones included.
+    pub fn unfinished(&self) -> Vec<usize> {
+        (0..self.items.len())
+            .filter(|&i| !self.items[i].status.is_finished())
+            .collect()
+    }
+
+    /// Deletes outputs left behind by items that were running when the batch stopped.
+    pub fn discard_partial(&mut self) {
+        for item in &mut self.items {
+            if item.status.is_finished() {
+                continue;
+            }
+            if let Some(out) = item.output.take()
+                && out.is_file()
+            {
+                let _ = std::fs::remove_file(out);
+            }
+            item.status = ItemStatus::Pending;
+        }
+    }
+
+    /// Names item `idx`'s output with `name` and marks it running, in one step so that files
+    /// encoding at once never get the same name: `name` is given the outputs other items have.
+    pub fn start(
+        &mut self,
+        idx: usize,
+        name: impl FnOnce(&[PathBuf]) -> Result<PathBuf>,
+    ) -> Result<PathBuf> {
+        let taken = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != idx)
+            .filter_map(|(_, item)| item.output.clone())
+            .collect::<Vec<_>>();
+        let output = name(&taken)?;
+        self.mark(idx, ItemStatus::Running, Some(&output));
+        Ok(output)
+    }
+
+    /// Records a status change and writes the queue out.
+    ///
+    /// A failed write only costs the ability to resume, so the batch carries on.
+    pub fn mark(&mut self, idx: usize, status: ItemStatus, output: Option<&Path>) {
+        let item = &mut self.items[idx];
+        item.status = status;
+        if let Some(out) = output {
+            item.output = Some(out.to_path_buf());
+        }
+        let _ = self.save();
+    }
+
+    pub fn load() -> Result<Option<Self>> {
+        let app_root = crate::paths::app_root_dir()?;
+        load_from_app_root(&app_root)
+    }
+
+    /// Does nothing for a queue that wasn't claimed.
+    pub fn save(&self) -> Result<()> {
+        if self.lock.is_none() {
+            return Ok(());
+        }
+        let app_root = crate::paths::app_root_dir()?;
+        save_to_app_root(&app_root, self)
+    }
+
+    /// Forgets the batch once it is over (or the user declined to resume it).
+    pub fn remove(&self) -> Result<()> {
+        if self.lock.is_none() {
+            return Ok(());
+        }
+        let p = queue_file(&crate::paths::app_root_dir()?);
+        match std::fs::remove_file(p) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn queue_file(app_root: &Path) -> PathBuf {
+    app_root.join(QUEUE_FILE_NAME)
+}
+
+fn lock_in(app_root: &Path) -> Result<Option<QueueLock>> {
+    std::fs::create_dir_all(app_root)?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(app_root.join(LOCK_FILE_NAME))?;
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(Some(QueueLock {
+            _file: Arc::new(file),
+        })),
+        Err(e) if e.kind() == fs2::lock_contended_error().kind() => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn load_from_app_root(app_root: &Path) -> Result<Option<Queue>> {
+    match std::fs::read_to_string(queue_file(app_root)) {
+        Ok(s) => Ok(parse_queue(&s)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_to_app_root(app_root: &Path, q: &Queue) -> Result<()> {
+    std::fs::create_dir_all(app_root)?;
+    let p = queue_file(app_root);
+
+    let mut tmp = tempfile::NamedTempFile::new_in(app_root)?;
+    tmp.as_file_mut().write_all(format_queue(q).as_bytes())?;
+    tmp.as_file_mut().flush()?;
+    tmp.persist(&p).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// `key = value` header lines, then one `item = status<TAB>output<TAB>input` line per file, each
+/// followed by an `overrides = ` line when the file has any.
+fn format_queue(q: &Queue) -> String {
+    let mut s = format!(
+        "preset = {}\nencoder = {}\ncontainer = {}\nmin_savings = {}\n",
+        q.preset.as_str(),
+        q.encoder.ffmpeg_name(),
+        q.container.as_str(),
+        q.min_savings
+    );
+    if let Some(t) = q.target_vmaf {
+        s.push_str(&format!("target_vmaf = {t}\n"));
+    }
+    if let Some(g) = q.gif {
+        s.push_str(&format!(
+            "gif = {} {} {} {} {}\n",
+            g.fps,
+            g.width,
+            g.plays,
+            g.dither.as_str(),
+            g.max_bytes.map_or("-".to_string(), |b| b.to_string())
+        ));
+    }
+    match q.split {
+        Some(SplitLimit::Size(bytes)) => s.push_str(&format!("split = size {bytes}\n")),
+        Some(SplitLimit::Duration(secs)) => s.push_str(&format!("split = duration {secs}\n")),
+        None => {}
+    }
+    if let Some(t) = q.min_quality {
+        s.push_str(&format!("min_quality = {t}\n"));
+    }
+    let p = &q.preserve;
+    let kept = [
+        ("metadata", p.metadata),
+        ("chapters", p.chapters),
+        ("creation_time", p.creation_time),
+        ("rotation", p.rotation),
+        ("subtitles", p.subtitles),
+        ("timestamps", p.timestamps),
+    ];
+    let h = &q.hw_decode;
+    let decoded = [
+        ("quality", h.quality),
+        ("balanced", h.balanced),
+        ("speed", h.speed),
+    ];
+    let names = |flags: &[(&str, bool)]| {
+        flags
+            .iter()
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    s.push_str(&format!(
+        "measure = {}\npreserve = {}\ncpu_fallback = {}\nhw_decode = {}\n",
+        q.measure,
+        names(&kept),
+        q.cpu_fallback,
+        names(&decoded)
+    ));
+    s.push_str(&format!(
+        "ffmpeg_input_args = {}\nffmpeg_args = {}\n",
+        extra_args::join(&q.extra_args.input),
+        extra_args::join(&q.extra_args.output)
+    ));
+    for item in &q.items {
+        s.push_str(&format!(
+            "item = {}\t{}\t{}\n",
+            item.status.as_str(),
+            item.output
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            item.input.display()
+        ));
+        let o = &item.overrides;
+        if !o.is_empty() {
+            let mut parts = Vec::new();
+            if let Some(p) = o.preset {
+                parts.push(format!("preset={}", p.as_str()));
+            }
+            if let Some(e) = o.encoder {
+                parts.push(format!("encoder={}", e.ffmpeg_name()));
+            }
+            if let Some(t) = o.trim_secs {
+                parts.push(format!("trim={t}"));
+            }
+            if let Some(h) = o.max_height {
+                parts.push(format!("height={h}"));
+            }
+            s.push_str(&format!("overrides = {}\n", parts.join(" ")));
+        }
+    }
+    s
+}
+
+/// `None` for a file that isn't a queue (or was written by an incompatible version).
+fn parse_queue(text: &str) -> Option<Queue> {
+    let mut preset = None;
+    let mut encoder = None;
+    // queues from before the choice existed were all mp4.
+    let mut container = Container::Mp4;
+    let mut min_savings = 20;
+    let mut target_vmaf = None;
+    let mut gif = None;
+    let mut split = None;
+    // as the defaults were before these were kept.
+    let mut measure = false;
+    let mut min_quality = None;
+    let mut preserve = Preserve::default();
+    let mut cpu_fallback = false;
+    let mut hw_decode = HwDecode::default();
+    let mut extra = ExtraArgs::default();
+    let mut items = Vec::new();
+
+    for line in text.lines() {
+        // empty lists are written as "name = " and may be trimmed to "name =".
+        let Some((k, v)) = line
+            .split_once(" = ")
+            .or_else(|| line.strip_suffix(" =").map(|k| (k, "")))
+        else {
+            continue;
+        };
+        match k {
+            "preset" => preset = Preset::parse(v),
+            "encoder" => encoder = Encoder::parse(v),
+            "container" => container = Container::parse(v)?,
+            "min_savings" => min_savings = v.parse().ok()?,
+            "target_vmaf" => target_vmaf = Some(v.parse().ok()?),
+            "gif" => gif = Some(parse_gif(v)?),
+            "split" => {
+                split = Some(match v.split_once(' ')? {
+                    ("size", n) => SplitLimit::Size(n.parse().ok()?),
+                    ("duration", n) => SplitLimit::Duration(n.parse().ok()?),
+                    _ => return None,
+                })
+            }
+            "measure" => measure = v.parse().ok()?,
+            "min_quality" => min_quality = Some(QualityThreshold::parse(v).ok()?),
+            "preserve" => {
+                preserve = Preserve::all(false);
+                for name in v.split_whitespace() {
+                    match name {
+                        "metadata" => preserve.metadata = true,
+                        "chapters" => preserve.chapters = true,
+                        "creation_time" => preserve.creation_time = true,
+                        "rotation" => preserve.rotation = true,
+                        "subtitles" => preserve.subtitles = true,
+                        "timestamps" => preserve.timestamps = true,
+                        _ => return None,
+                    }
+                }
+            }
+            "cpu_fallback" => cpu_fallback = v.parse().ok()?,
+            "hw_decode" => {
+                hw_decode = HwDecode::all(false);
+                for name in v.split_whitespace() {
+                    match name {
+                        "quality" => hw_decode.quality = true,
+                        "balanced" => hw_decode.balanced = true,
+                        "speed" => hw_decode.speed = true,
+                        _ => return None,
+                    }
+                }
+            }
+            "ffmpeg_input_args" => extra.input = extra_args::split(v).ok()?,
+            "ffmpeg_args" => extra.output = extra_args::split(v).ok()?,
+            "item" => {
+                let mut parts = v.splitn(3, '\t');
+                let status = ItemStatus::parse(parts.next()?)?;
+                let output = parts.next()?;
+                let input = parts.next()?;
+                items.push(QueueItem {
+                    input: PathBuf::from(input),
+                    output: (!output.is_empty()).then(|| PathBuf::from(output)),
+                    status,
+                    overrides: FileOverrides::default(),
+                });
+            }
+            "overrides" => items.last_mut()?.overrides = parse_overrides(v)?,
+            _ => {}
+        }
+    }
+
+    Some(Queue {
+        preset: preset?,
+        encoder: encoder?,
+        container,
+        min_savings,
+        target_vmaf,
+        gif,
+        split,
+        measure,
+        min_quality,
+        preserve,
+        cpu_fallback,
+        hw_decode,
+        extra_args: extra,
+        items,
+        lock: None,
+    })
+}
+
+/// `name=value` pairs for the overrides that are set, e.g. `preset=speed trim=30`.
+fn parse_overrides(v: &str) -> Option<FileOverrides> {
+    let mut o = FileOverrides::default();
+    for pair in v.split_whitespace() {
+        match pair.split_once('=')? {
+            ("preset", p) => o.preset = Some(Preset::parse(p)?),
+            ("encoder", e) => o.encoder = Some(Encoder::parse(e)?),
+            ("trim", t) => o.trim_secs = Some(t.parse().ok()?),
+            ("height", h) => o.max_height = Some(h.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some(o)
+}
+
+/// `fps width plays dither max_bytes`, with `-` for no size target.
+fn parse_gif(v: &str) -> Option<GifSettings> {
+    let mut parts = v.split(' ');
+    let mut next = || parts.next();
+    Some(GifSettings {
+        fps: next()?.parse().ok()?,
+        width: next()?.parse().ok()?,
+        plays: next()?.parse().ok()?,
+        dither: Dither::parse(next()?)?,
+        max_bytes: match next()? {
+            "-" => None,
+            b => Some(b.parse().ok()?),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Queue {
+        Queue {
+            preset: Preset::Quality,
+            encoder: Encoder::NVENC,
+            container: Container::Mkv,
+            min_savings: 30,
+            target_vmaf: Some(93.5),
+            gif: None,
+            split: Some(SplitLimit::Size(25 << 20)),
+            measure: true,
+            min_quality: Some(QualityThreshold::parse("ssim=0.97").unwrap()),
+            preserve: Preserve {
+                subtitles: true,
+                metadata: false,
+                ..Preserve::default()
+            },
+            cpu_fallback: true,
+            hw_decode: HwDecode {
+                speed: true,
+                ..HwDecode::default()
+            },
+            extra_args: ExtraArgs {
+                input: vec!["-thread_queue_size".to_string(), "1024".to_string()],
+                output: vec!["-metadata".to_string(), "title=it's a b".to_string()],
+            },
+            items: vec![
+                QueueItem {
+                    input: PathBuf::from("C:\\clips\\a b.mp4"),
+                    output: Some(PathBuf::from("C:\\clips\\a b.tinythis.quality.mp4")),
+                    status: ItemStatus::Done,
+                    overrides: FileOverrides::default(),
+                },
+                QueueItem {
+                    input: PathBuf::from("C:\\clips\\c.mov"),
+                    output: None,
+                    status: ItemStatus::Pending,
+                    overrides: FileOverrides {
+                        preset: Some(Preset::Speed),
+                        encoder: Some(Encoder::CPU),
+                        trim_secs: Some(30),
+                        max_height: Some(720),
+                    },
+                },
+            ],
+            lock: None,
+        }
+    }
+
+    #[test]
+    fn round_trips_through_the_queue_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(load_from_app_root(dir.path()).unwrap(), None);
+
+        let q = sample();
+        save_to_app_root(dir.path(), &q).unwrap();
+        assert_eq!(load_from_app_root(dir.path()).unwrap(), Some(q));
+
+        let q = Queue {
+            preset: Preset::Gif,
+            gif: Some(GifSettings {
+                dither: Dither::Sierra,
+                max_bytes: Some(8 << 20),
+                ..GifSettings::default()
+            }),
+            split: Some(SplitLimit::Duration(600)),
+            min_quality: None,
+            preserve: Preserve::all(false),
+            hw_decode: HwDecode::default(),
+            extra_args: ExtraArgs::default(),
+            ..sample()
+        };
+        save_to_app_root(dir.path(), &q).unwrap();
+        assert_eq!(load_from_app_root(dir.path()).unwrap(), Some(q));
+
+        assert_eq!(parse_queue("item = done\t\tC:\\a.mp4\n"), None);
+        assert_eq!(
+            parse_queue("preset = balanced\nencoder = libx264\nitem = weird\t\ta\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn files_encoding_at_once_get_different_outputs() {
+        use crate::exec::compress::{OnExisting, OutputNaming};
+
+        let dir = tempfile::tempdir().unwrap();
+        let inputs = [dir.path().join("clip.mov"), dir.path().join("clip.mkv")];
+        let mut q = sample();
+        q.items = inputs
+            .iter()
+            .map(|input| QueueItem {
+                input: input.clone(),
+                output: None,
+                status: ItemStatus::Pending,
+                overrides: FileOverrides::default(),
+            })
+            .collect();
+        let naming = OutputNaming {
+            on_existing: OnExisting::Overwrite,
+            ..OutputNaming::default()
+        };
+        let queue = std::sync::Mutex::new(q);
+        let (outputs, failed) = crate::exec::pool::run(2, vec![0, 1], |i| {
+            queue.lock().unwrap().start(i, |taken| {
+                crate::exec::compress::build_output_path_except(
+                    &inputs[i],
+                    Preset::Balanced,
+                    Container::Mp4,
+                    &naming,
+                    taken,
+                )
+            })
+        });
+        assert!(failed.is_none());
+
+        let first = dir.path().join("clip.tinythis.balanced.mp4");
+        let second = dir.path().join("clip.tinythis.balanced.2.mp4");
+        assert!(outputs == [first.clone(), second.clone()] || outputs == [second, first]);
+        let q = queue.into_inner().unwrap();
+        assert!(q.items.iter().all(|i| i.status == ItemStatus::Running));
+        assert_eq!(q.items[0].output.as_ref(), Some(&outputs[0]));
+        assert_eq!(q.items[1].output.as_ref(), Some(&outputs[1]));
+    }
+
+    #[test]
+    fn only_one_batch_holds_the_queue_at_a_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = lock_in(dir.path()).unwrap();
+        assert!(first.is_some());
+        assert!(lock_in(dir.path()).unwrap().is_none());
+        drop(first);
+        assert!(lock_in(dir.path()).unwrap().is_some());
+    }
+
+    #[test]
+    fn resume_restarts_unfinished_items_and_drops_partial_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let partial = dir.path().join("c.tinythis.quality.mp4");
+        std::fs::write(&partial, b"half").unwrap();
+
+        let mut q = sample();
+        q.items[1].status = ItemStatus::Running;
+        q.items[1].output = Some(partial.clone());
+        q.items.push(QueueItem {
+            input: PathBuf::from("d.mp4"),
+            output: None,
+            status: ItemStatus::Failed,
+            overrides: FileOverrides::default(),
+        });
+        assert_eq!(q.unfinished(), vec![1, 2]);
+
+        q.discard_partial();
+        assert!(!partial.exists());
+        assert_eq!(q.items[1].status, ItemStatus::Pending);
+        assert_eq!(q.items[1].output, None);
+        assert_eq!(q.items[0].status, ItemStatus::Done);
+        assert!(q.items[0].output.is_some());
+    }
+}
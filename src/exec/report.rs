@@ -0,0 +1,363 @@
+use std::path::{Path, PathBuf};
+
+use crate::exec::compress::FilePlan;
+use crate::exec::metrics::{QualityThreshold, Scores};
+use crate::json::Value;
+use crate::presets::Preset;
+use crate::presets::auto::AutoDecision;
+
+/// What happened to one input, for the end-of-batch summary.
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub input_bytes: u64,
+    /// `None` when the file was skipped.
+    pub output: Option<PathBuf>,
+    pub output_bytes: Option<u64>,
+    pub preset: Preset,
+    pub decision: Option<AutoDecision>,
+    pub crf: Option<u8>,
+    pub scores: Option<Scores>,
+    pub threshold: Option<QualityThreshold>,
+    /// the hardware encoder that failed to start before the file was encoded on the cpu.
+    pub fallback: Option<&'static str>,
+    /// what the output was split into, with sizes; `output_bytes` is their total.
+    pub parts: Vec<(PathBuf, u64)>,
+}
+
+impl FileReport {
+    pub fn new(
+        input: &Path,
+        output: Option<&Path>,
+        plan: &FilePlan,
+        scores: Option<Scores>,
+    ) -> Self {
+        let parts = plan
+            .parts
+            .iter()
+            .map(|p| (p.clone(), file_size(p).unwrap_or(0)))
+            .collect::<Vec<_>>();
+        let output_bytes = if parts.is_empty() {
+            output.and_then(file_size)
+        } else {
+            Some(parts.iter().map(|(_, bytes)| bytes).sum())
+        };
+        Self {
+            input: input.to_path_buf(),
+            input_bytes: file_size(input).unwrap_or(0),
+            output: output.map(Path::to_path_buf),
+            output_bytes,
+            preset: plan.settings.preset,
+            decision: plan.decision.clone(),
+            crf: plan.settings.crf,
+            scores,
+            threshold: plan.settings.min_quality,
+            fallback: plan.fallback.map(|e| e.ffmpeg_name()),
+            parts,
+        }
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        self.output.is_none()
+    }
+
+    pub fn is_below_threshold(&self) -> bool {
+        match (self.threshold, self.scores) {
+            (Some(t), Some(s)) => t.is_below(&s),
+            _ => false,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let scores = self.scores.map(|s| {
+            Value::object([
+                ("ssim", Value::from(s.ssim)),
+                ("psnr", Value::from(s.psnr)),
+                ("vmaf", Value::from(s.vmaf)),
+            ])
+        });
+        let parts = self
+            .parts
+            .iter()
+            .map(|(path, bytes)| {
+                Value::object([
+                    ("path", Value::from(path.display().to_string())),
+                    ("bytes", Value::from(*bytes)),
+                ])
+            })
+            .collect();
+        let decision = self.decision.as_ref().map(|d| {
+            Value::object([
+                ("action", Value::from(d.label())),
+                ("reason", Value::from(d.reason.as_str())),
+            ])
+        });
+
+        Value::object([
+            ("input", Value::from(self.input.display().to_string())),
+            ("input_bytes", Value::from(self.input_bytes)),
+            (
+                "output",
+                Value::from(self.output.as_ref().map(|p| p.display().to_string())),
+            ),
+            ("output_bytes", Value::from(self.output_bytes)),
+            ("parts", Value::Array(parts)),
+            ("skipped", Value::from(self.is_skipped())),
+            ("preset", Value::from(self.preset.as_str())),
+            ("auto", decision.unwrap_or(Value::Null)),
+            ("crf", Value::from(self.crf)),
+            ("scores", scores.unwrap_or(Value::Null)),
+            ("below_threshold", Value::from(self.is_below_threshold())),
+            ("cpu_fallback", Value::from(self.fallback)),
+        ])
+    }
+
+    pub fn summary_line(&self) -> String {
+        let name = file_name(&self.input);
+        let mut line = match (&self.output, self.output_bytes) {
+            (Some(out), Some(out_bytes)) => format!(
+                "{name} -> {}: {} -> {} ({})",
+                file_name(out),
+                format_bytes(self.input_bytes),
+                format_bytes(out_bytes),
+                format_change(self.input_bytes, out_bytes)
+            ),
+            (Some(out), None) => format!("{name} -> {}", file_name(out)),
+            (None, _) => format!("{name}: skipped"),
+        };
+
+        if !self.parts.is_empty() {
+            let parts = self
+                .parts
+                .iter()
+                .map(|(path, bytes)| format!("{} {}", file_name(path), format_bytes(*bytes)))
+                .collect::<Vec<_>>();
+            line.push_str(&format!(" [{} parts: {}]", parts.len(), parts.join(", ")));
+        }
+
+        if let Some(d) = &self.decision {
+            line.push_str(&format!(" [auto: {}, {}]", d.label(), d.reason));
+        }
+        if let Some(failed) = self.fallback {
+            line.push_str(&format!(" [cpu: {failed} failed to start]"));
+        }
+        if let Some(s) = &self.scores {
+            line.push_str(&format!(" [{}]", s.summary()));
+        }
+        if self.is_below_threshold()
+            && let Some(t) = self.threshold
+        {
+            line.push_str(&format!(" below {t}"));
+        }
+        line
+    }
+}
+
+pub fn summary_lines(reports: &[FileReport]) -> Vec<String> {
+    reports.iter().map(FileReport::summary_line).collect()
+}
+
+pub fn summary_json(reports: &[FileReport]) -> Value {
+    let below = reports.iter().filter(|r| r.is_below_threshold()).count() as u64;
+    Value::object([
+        (
+            "files",
+            Value::Array(reports.iter().map(FileReport::to_json).collect()),
+        ),
+        ("below_threshold", Value::from(below)),
+    ])
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = 1024.0 * 1024.0;
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+    let b = bytes as f64;
+
+    if b >= GB {
+        format!("{:.1} GB", b / GB)
+    } else if b >= MB {
+        format!("{:.1} MB", b / MB)
+    } else if b >= KB {
+        format!("{:.1} KB", b / KB)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+/// Reads a size like `8M`, `1.5mb`, `500k` or `123456` (bytes); units are binary, like
+/// `format_bytes`.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim().to_ascii_lowercase();
+    let s = s.strip_suffix('b').unwrap_or(&s).trim_end();
+    let (num, unit) = match s.char_indices().last()? {
+        (i, 'k') => (&s[..i], 1u64 << 10),
+        (i, 'm') => (&s[..i], 1 << 20),
+        (i, 'g') => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    let n: f64 = num.trim().parse().ok()?;
+    (n.is_finite() && n > 0.0).then(|| (n * unit as f64).round() as u64)
+}
+
+/// `bytes` in the shortest form `parse_size` reads back exactly.
+pub fn size_text(bytes: u64) -> String {
+    match bytes {
+        b if b % (1 << 30) == 0 => format!("{}G", b >> 30),
+        b if b % (1 << 20) == 0 => format!("{}M", b >> 20),
+        b if b % (1 << 10) == 0 => format!("{}k", b >> 10),
+        b => b.to_string(),
+    }
+}
+
+fn format_change(before: u64, after: u64) -> String {
+    if before == 0 {
+        return "n/a".to_string();
+    }
+    let pct = (after as f64 / before as f64 - 1.0) * 100.0;
+    format!("{pct:+.0}%")
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string_lossy().into_owned())
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|m| m.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets::auto::AutoAction;
+
+    #[test]
+    fn summary_line_shows_sizes_and_auto_reason() {
+        let r = FileReport {
+            input: PathBuf::from("a.mov"),
+            input_bytes: 10 * 1024 * 1024,
+            output: Some(PathBuf::from("a.tinythis.balanced.mp4")),
+            output_bytes: Some(4 * 1024 * 1024),
+            preset: Preset::Balanced,
+            decision: Some(AutoDecision {
+                action: AutoAction::Encode(Preset::Balanced),
+                reason: "moderate bitrate".to_string(),
+            }),
+            crf: None,
+            scores: None,
+            threshold: None,
+            fallback: None,
+            parts: Vec::new(),
+        };
+        assert_eq!(
+            r.summary_line(),
+            "a.mov -> a.tinythis.balanced.mp4: 10.0 MB -> 4.0 MB (-60%) [auto: balanced, moderate bitrate]"
+        );
+
+        let skipped = FileReport {
+            output: None,
+            output_bytes: None,
+            decision: Some(AutoDecision {
+                action: AutoAction::Skip,
+                reason: "no video stream".to_string(),
+            }),
+            ..r
+        };
+        assert!(skipped.is_skipped());
+        assert_eq!(
+            skipped.summary_line(),
+            "a.mov: skipped [auto: skip, no video stream]"
+        );
+
+        let split = FileReport {
+            output: Some(PathBuf::from("a.tinythis.balanced.mp4")),
+            output_bytes: Some(6 << 20),
+            decision: None,
+            parts: vec![
+                (PathBuf::from("a.tinythis.balanced.part1.mp4"), 4 << 20),
+                (PathBuf::from("a.tinythis.balanced.part2.mp4"), 2 << 20),
+            ],
+            ..skipped
+        };
+        assert_eq!(
+            split.summary_line(),
+            "a.mov -> a.tinythis.balanced.mp4: 10.0 MB -> 6.0 MB (-40%) \
+[2 parts: a.tinythis.balanced.part1.mp4 4.0 MB, a.tinythis.balanced.part2.mp4 2.0 MB]"
+        );
+        let v: serde_json::Value = serde_json::from_str(&split.to_json().to_string()).unwrap();
+        assert_eq!(v["parts"][1]["bytes"], 2 << 20);
+    }
+
+    #[test]
+    fn scores_and_threshold_show_in_summary_and_json() {
+        let r = FileReport {
+            input: PathBuf::from("a.mov"),
+            input_bytes: 1000,
+            output: Some(PathBuf::from("a.tinythis.speed.mp4")),
+            output_bytes: Some(500),
+            preset: Preset::Speed,
+            decision: None,
+            crf: Some(30),
+            scores: Some(Scores {
+                ssim: Some(0.95),
+                psnr: Some(36.5),
+                vmaf: Some(88.25),
+            }),
+            threshold: Some(QualityThreshold::parse("vmaf=90").unwrap()),
+            fallback: None,
+            parts: Vec::new(),
+        };
+        assert!(r.is_below_threshold());
+        assert_eq!(
+            r.summary_line(),
+            "a.mov -> a.tinythis.speed.mp4: 1000 B -> 500 B (-50%) \
+[ssim 0.9500, psnr 36.50 dB, vmaf 88.25] below vmaf=90"
+        );
+
+        let json = summary_json(std::slice::from_ref(&r)).to_string();
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["below_threshold"], 1);
+        assert_eq!(v["files"][0]["preset"], "speed");
+        assert_eq!(v["files"][0]["crf"], 30);
+        assert_eq!(v["files"][0]["scores"]["vmaf"], 88.25);
+        assert_eq!(v["files"][0]["auto"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn cpu_fallback_is_noted_in_summary_and_json() {
+        let r = FileReport {
+            input: PathBuf::from("a.mp4"),
+            input_bytes: 1000,
+            output: Some(PathBuf::from("a.tinythis.balanced.mp4")),
+            output_bytes: Some(400),
+            preset: Preset::Balanced,
+            decision: None,
+            crf: None,
+            scores: None,
+            threshold: None,
+            fallback: Some("h264_nvenc"),
+            parts: Vec::new(),
+        };
+        assert_eq!(
+            r.summary_line(),
+            "a.mp4 -> a.tinythis.balanced.mp4: 1000 B -> 400 B (-60%) [cpu: h264_nvenc failed to start]"
+        );
+        let v: serde_json::Value = serde_json::from_str(&r.to_json().to_string()).unwrap();
+        assert_eq!(v["cpu_fallback"], "h264_nvenc");
+    }
+
+    #[test]
+    fn sizes_parse_in_binary_units_and_print_back() {
+        assert_eq!(parse_size("8M"), Some(8 << 20));
+        assert_eq!(parse_size(" 1.5 mb "), Some(3 << 19));
+        assert_eq!(parse_size("500k"), Some(500 << 10));
+        assert_eq!(parse_size("123456"), Some(123_456));
+        assert_eq!(parse_size("0"), None);
+        assert_eq!(parse_size("lots"), None);
+        for bytes in [8 << 20, 3 << 19, 500 << 10, 123_456] {
+            assert_eq!(parse_size(&size_text(bytes)), Some(bytes));
+        }
+    }
+}
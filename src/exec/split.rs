@@ -0,0 +1,382 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, TinythisError};
+use crate::exec::compress::{FilePlan, OnExisting};
+use crate::exec::container::Container;
+
+/// Share of the size limit each part aims at: a cut lands on the first keyframe after its time.
+const SIZE_MARGIN: f64 = 0.9;
+/// Cuts a size split tries, each with shorter parts, before settling for what it has.
+const SIZE_TRIES: u32 = 4;
+
+/// How big each part of a split output may get.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SplitLimit {
+    /// bytes per part.
+    Size(u64),
+    /// seconds per part.
+    Duration(u32),
+}
+
+/// Reads a length like `600`, `90s`, `10m` or `1h30m` into seconds.
+pub fn parse_duration(s: &str) -> Option<u32> {
+    let s = s.trim().to_ascii_lowercase();
+    if let Ok(secs) = s.parse::<u32>() {
+        return (secs > 0).then_some(secs);
+    }
+    let mut total = 0u32;
+    let mut num = String::new();
+    for c in s.chars() {
+        let unit = match c {
+            '0'..='9' => {
+                num.push(c);
+                continue;
+            }
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let n: u32 = num.parse().ok()?;
+        num.clear();
+        total = total.checked_add(n.checked_mul(unit)?)?;
+    }
+    (num.is_empty() && total > 0).then_some(total)
+}
+
+/// `<stem>.part<n>.<ext>`, for a file next to `output`.
+fn part_name(output: &Path, n: &str) -> String {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    match output.extension() {
+        Some(ext) => format!("{stem}.part{n}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.part{n}"),
+    }
+}
+
+/// Parts an earlier split left next to `base`: `<stem>.part<n>.<ext>` for any number.
+fn existing_parts(base: &Path) -> Vec<PathBuf> {
+    let dir = base
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let prefix = format!(
+        "{}.part",
+        base.file_stem().unwrap_or_default().to_string_lossy()
+    );
+    let suffix = base
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let name = e.file_name();
+            let n = name
+                .to_str()
+                .and_then(|n| n.strip_prefix(&prefix))
+                .and_then(|n| n.strip_suffix(&suffix));
+            n.is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(|e| e.path())
+        .collect()
+}
+
+/// The path the parts of `output` are named after. With `Overwrite`, an earlier split's parts
+/// are deleted; otherwise the parts move to the first `<stem>.2.<ext>`, `.3`, ... without any.
+fn parts_base(input: &Path, output: &Path, on_existing: OnExisting) -> Result<PathBuf> {
+    let old = existing_parts(output);
+    if old.is_empty() {
+        return Ok(output.to_path_buf());
+    }
+    // an input named like a part is never deleted.
+    let input = std::fs::canonicalize(input).ok();
+    let has_input = old.iter().any(|p| std::fs::canonicalize(p).ok() == input);
+    if on_existing == OnExisting::Overwrite && !has_input {
+        for p in old {
+            std::fs::remove_file(p)?;
+        }
+        return Ok(output.to_path_buf());
+    }
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    for n in 2u32.. {
+        let base = output.with_file_name(match output.extension() {
+            Some(ext) => format!("{stem}.{n}.{}", ext.to_string_lossy()),
+            None => format!("{stem}.{n}"),
+        });
+        if existing_parts(&base).is_empty() {
+            return Ok(base);
+        }
+    }
+
+    unreachable!("the loop returns once it finds a free name")
+}
+
+/// How long each part runs, from the limit and the output's average bitrate; `None` when the
+/// whole output already fits.
+pub fn part_secs(limit: SplitLimit, bytes: u64, length_us: u64) -> Option<f64> {
+    let secs = length_us as f64 / 1_000_000.0;
+    match limit {
+        SplitLimit::Duration(d) => (secs > f64::from(d)).then_some(f64::from(d)),
+        SplitLimit::Size(max) => {
+            (bytes > max && secs > 0.0).then(|| secs * max as f64 / bytes as f64 * SIZE_MARGIN)
+        }
+    }
+}
+
+/// Copies `output` into parts of `secs` each, named after `base` and numbered from 1, with every
+/// part starting at zero; ffmpeg writes the names of the parts to `list`.
+pub fn split_args(
+    output: &Path,
+    base: &Path,
+    container: Container,
+    secs: f64,
+    list: &Path,
+) -> Vec<OsString> {
+    // the segment muxer fills in %d, so any % already in the name is doubled.
+    let name = base.file_name().unwrap_or_default().to_string_lossy();
+    let escaped = base.with_file_name(name.replace('%', "%%"));
+    let pattern = escaped.with_file_name(part_name(&escaped, "%d"));
+    // no -y: `parts_base` picked names no earlier file has.
+    let mut args = ["-hide_banner", "-nostdin", "-nostats", "-i"]
+        .map(OsString::from)
+        .to_vec();
+    args.push(output.as_os_str().to_owned());
+    args.extend(
+        [
+            "-map",
+            "0",
+            "-c",
+            "copy",
+            "-f",
+            "segment",
+            "-segment_time",
+            &format!("{secs:.3}"),
+            "-segment_format",
+            container.muxer(),
+            "-reset_timestamps",
+            "1",
+            "-segment_start_number",
+            "1",
+            "-segment_list_type",
+            "flat",
+        ]
+        .map(OsString::from),
+    );
+    if container.faststart() {
+        args.extend(["-segment_format_options", "movflags=+faststart"].map(OsString::from));
+    }
+    args.extend([OsString::from("-segment_list"), list.as_os_str().to_owned()]);
+    args.push(pattern.into_os_string());
+    args
+}
+
+/// Cuts `output` into numbered parts when it's over the plan's split limit, then removes it; the
+/// parts end up in `plan.parts`. Cuts land on keyframes, so each part plays on its own.
+///
+/// An earlier split's parts are handled like an existing output: kept (the new parts get another
+/// name) unless `on_existing` says to overwrite.
+pub fn split_output(
+    ffmpeg: &Path,
+    input: &Path,
+    output: &Path,
+    on_existing: OnExisting,
+    plan: &mut FilePlan,
+) -> Result<()> {
+    let Some(limit) = plan.settings.split else {
+        return Ok(());
+    };
+    let bytes = std::fs::metadata(output)?.len();
+    let length_us = match crate::exec::compress::output_length_us(
+        plan.info.as_ref(),
+        plan.settings.trim_secs,
+    ) {
+        Some(us) => us,
+        None => crate::exec::probe::probe(ffmpeg, output)?
+            .duration_us
+            .ok_or_else(|| {
+                TinythisError::InvalidArgs(format!(
+                    "{}: can't split, ffmpeg doesn't report its length",
+                    output.display()
+                ))
+            })?,
+    };
+    let Some(mut secs) = part_secs(limit, bytes, length_us) else {
+        log::info!("{} fits in one part", output.display());
+        return Ok(());
+    };
+
+    let base = parts_base(input, output, on_existing)?;
+    for attempt in 1..=SIZE_TRIES {
+        let parts = cut(ffmpeg, output, &base, plan.settings.container, secs)?;
+        let over = match limit {
+            SplitLimit::Size(max) => parts
+                .iter()
+                .any(|p| std::fs::metadata(p).is_ok_and(|m| m.len() > max)),
+            SplitLimit::Duration(_) => false,
+        };
+        if over && attempt < SIZE_TRIES {
+            log::info!(
+                "a part of {} came out too big, cutting shorter parts",
+                output.display()
+            );
+            for p in &parts {
+                let _ = std::fs::remove_file(p);
+            }
+            secs *= 0.8;
+            continue;
+        }
+        if over {
+            log::warn!(
+                "some parts of {} are still over the limit; keyframes are too far apart",
+                output.display()
+            );
+        }
+        log::info!("split {} into {} parts", output.display(), parts.len());
+        if plan.settings.preserve.timestamps {
+            for p in &parts {
+                crate::exec::compress::copy_file_times(input, p)?;
+            }
+        }
+        std::fs::remove_file(output)?;
+        plan.parts = parts;
+        break;
+    }
+    Ok(())
+}
+
+fn cut(
+    ffmpeg: &Path,
+    output: &Path,
+    base: &Path,
+    container: Container,
+    secs: f64,
+) -> Result<Vec<PathBuf>> {
+    let dir = tempfile::tempdir()?;
+    let list = dir.path().join("parts.txt");
+    let args = split_args(output, base, container, secs, &list);
+    log::debug!(
+        "splitting: {}",
+        crate::process::run::command_line(ffmpeg, &args)
+    );
+    crate::process::run::run_capture(ffmpeg, &args)?;
+    let parent = output.parent().unwrap_or_else(|| Path::new("."));
+    let names = std::fs::read_to_string(&list)?;
+    Ok(names
+        .lines()
+        .filter_map(|l| Path::new(l.trim()).file_name())
+        .map(|name| parent.join(name))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joined(args: &[OsString]) -> String {
+        args.iter()
+            .map(|v| v.to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn parts_are_sized_from_the_average_bitrate() {
+        // 100 MB over 400 s is 250 KB/s; 25 MB of that is 100 s, less the margin.
+        let secs = part_secs(SplitLimit::Size(25 << 20), 100 << 20, 400_000_000).unwrap();
+        assert!((secs - 90.0).abs() < 1e-9);
+        assert_eq!(
+            part_secs(SplitLimit::Size(25 << 20), 20 << 20, 400_000_000),
+            None
+        );
+        assert_eq!(part_secs(SplitLimit::Duration(600), 1, 400_000_000), None);
+        assert_eq!(
+            part_secs(SplitLimit::Duration(60), 1, 400_000_000),
+            Some(60.0)
+        );
+
+        assert_eq!(parse_duration("10m"), Some(600));
+        assert_eq!(parse_duration(" 1h30m "), Some(5400));
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("45"), Some(45));
+        assert_eq!(parse_duration("0"), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("10 minutes"), None);
+    }
+
+    #[test]
+    fn parts_are_numbered_copies_that_start_at_zero() {
+        let out = Path::new("/v/clip 100%.tinythis.balanced.mp4");
+        let s = joined(&split_args(
+            out,
+            out,
+            Container::Mp4,
+            90.0,
+            Path::new("/t/parts.txt"),
+        ));
+        assert_eq!(
+            s,
+            "-hide_banner -nostdin -nostats -i /v/clip 100%.tinythis.balanced.mp4 -map 0 -c copy \
+-f segment -segment_time 90.000 -segment_format mp4 -reset_timestamps 1 -segment_start_number 1 \
+-segment_list_type flat -segment_format_options movflags=+faststart -segment_list /t/parts.txt \
+/v/clip 100%%.tinythis.balanced.part%d.mp4"
+        );
+        let s = joined(&split_args(
+            Path::new("a.mkv"),
+            Path::new("a.2.mkv"),
+            Container::Mkv,
+            60.0,
+            Path::new("l"),
+        ));
+        assert!(s.contains("-i a.mkv "));
+        assert!(s.contains("-segment_format matroska"));
+        assert!(!s.contains("faststart"));
+        assert!(s.ends_with("a.2.part%d.mkv"));
+    }
+
+    #[test]
+    fn parts_do_not_overwrite_an_earlier_runs_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let touch = |name: &str| {
+            let p = dir.path().join(name);
+            std::fs::write(&p, b"x").unwrap();
+            p
+        };
+        let input = touch("a.mov");
+        let output = touch("a.tinythis.balanced.mp4");
+        assert_eq!(
+            parts_base(&input, &output, OnExisting::Rename).unwrap(),
+            output
+        );
+
+        let old = [
+            touch("a.tinythis.balanced.part1.mp4"),
+            touch("a.tinythis.balanced.part12.mp4"),
+        ];
+        touch("a.tinythis.balanced.2.part1.mp4");
+        touch("a.tinythis.balanced.3.partx.mp4");
+        assert_eq!(
+            parts_base(&input, &output, OnExisting::Rename).unwrap(),
+            dir.path().join("a.tinythis.balanced.3.mp4")
+        );
+        assert!(old.iter().all(|p| p.exists()));
+
+        assert_eq!(
+            parts_base(&input, &output, OnExisting::Overwrite).unwrap(),
+            output
+        );
+        assert!(old.iter().all(|p| !p.exists()));
+
+        // the input is never replaced, whatever it's called.
+        let input = touch("a.tinythis.balanced.part1.mp4");
+        assert_eq!(
+            parts_base(&input, &output, OnExisting::Overwrite).unwrap(),
+            dir.path().join("a.tinythis.balanced.3.mp4")
+        );
+        assert!(input.exists());
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+
+const STATE_FILE_NAME: &str = "watch_state.txt";
+
+/// Name of the subfolder originals are moved to with `--move-done`.
+const DONE_DIR_NAME: &str = "done";
+
+/// A video in the watched folder that tinythis didn't write itself.
+pub fn is_candidate(path: &Path) -> bool {
+    let is_output = path
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().contains(".tinythis."));
+    !is_output && crate::exec::input::is_supported_video(path)
+}
+
+/// Candidate files directly inside `dir` with their current size, sorted by path.
+pub fn scan(dir: &Path) -> Result<Vec<(PathBuf, u64)>> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        // files can vanish between listing and stat; they're picked up again if they return.
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_file() && is_candidate(&path) {
+            found.push((path, meta.len()));
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Waits for files to stop growing before they're handed to ffmpeg.
+#[derive(Debug)]
+pub struct Stability {
+    settle: Duration,
+    seen: HashMap<PathBuf, (u64, Instant)>,
+}
+
+impl Stability {
+    pub fn new(settle: Duration) -> Self {
+        Self {
+            settle,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Records `size` for `path` and reports whether it has stayed the same for the settle time.
+    pub fn observe(&mut self, path: &Path, size: u64, now: Instant) -> bool {
+        let entry = self.seen.entry(path.to_path_buf()).or_insert((size, now));
+        if entry.0 != size {
+            *entry = (size, now);
+        }
+        size > 0 && now.duration_since(entry.1) >= self.settle
+    }
+
+    pub fn forget(&mut self, path: &Path) {
+        self.seen.remove(path);
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Handled {
+    Done,
+    Skipped,
+    Failed,
+}
+
+impl Handled {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Handled::Done => "done",
+            Handled::Skipped => "skipped",
+            Handled::Failed => "failed",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "done" => Some(Handled::Done),
+            "skipped" => Some(Handled::Skipped),
+            "failed" => Some(Handled::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// Files every watcher has already dealt with, in `watch_state.txt` in the app folder.
+///
+/// The size is part of the key, so a file that is replaced with a different one is picked up
+/// again (a failed one too, once it changes).
+#[derive(Debug, Default, PartialEq)]
+pub struct WatchState {
+    handled: HashMap<PathBuf, (Handled, u64)>,
+}
+
+impl WatchState {
+    pub fn load() -> Result<Self> {
+        load_from_app_root(&crate::paths::app_root_dir()?)
+    }
+
+    pub fn is_handled(&self, path: &Path, size: u64) -> bool {
+        self.handled.get(path).is_some_and(|(_, s)| *s == size)
+    }
+
+    /// Records `path` and writes the state out; a failed write only risks handling it twice.
+    pub fn mark(&mut self, path: &Path, size: u64, how: Handled) {
+        self.handled.insert(path.to_path_buf(), (how, size));
+        if let Ok(app_root) = crate::paths::app_root_dir() {
+            let _ = save_to_app_root(&app_root, self);
+        }
+    }
+}
+
+fn load_from_app_root(app_root: &Path) -> Result<WatchState> {
+    match std::fs::read_to_string(app_root.join(STATE_FILE_NAME)) {
+        Ok(s) => Ok(parse_state(&s)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(WatchState::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_to_app_root(app_root: &Path, state: &WatchState) -> Result<()> {
+    std::fs::create_dir_all(app_root)?;
+    let mut tmp = tempfile::NamedTempFile::new_in(app_root)?;
+    tmp.as_file_mut()
+        .write_all(format_state(state).as_bytes())?;
+    tmp.as_file_mut().flush()?;
+    tmp.persist(app_root.join(STATE_FILE_NAME))
+        .map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// One `status<TAB>size<TAB>path` line per file, sorted so the file diffs cleanly.
+fn format_state(state: &WatchState) -> String {
+    let mut lines = state
+        .handled
+        .iter()
+        .map(|(p, (how, size))| format!("{}\t{size}\t{}\n", how.as_str(), p.display()))
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines.concat()
+}
+
+fn parse_state(text: &str) -> WatchState {
+    let handled = text
+        .lines()
+        .filter_map(|l| {
+            let mut parts = l.splitn(3, '\t');
+            let how = Handled::parse(parts.next()?)?;
+            let size = parts.next()?.parse().ok()?;
+            Some((PathBuf::from(parts.next()?), (how, size)))
+        })
+        .collect();
+    WatchState { handled }
+}
+
+/// Moves a handled original into `done/` next to it, numbering the name if it's taken.
+pub fn move_to_done(path: &Path) -> Result<PathBuf> {
+    let dir = path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(DONE_DIR_NAME);
+    std::fs::create_dir_all(&dir)?;
+
+    let name = path.file_name().unwrap_or_default();
+    let mut target = dir.join(name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut n = 2u32;
+    while target.exists() {
+        target = dir.join(format!("{stem}.{n}{ext}"));
+        n += 1;
+    }
+
+    std::fs::rename(path, &target)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_own_outputs_and_other_files() {
+        assert!(is_candidate(Path::new("C:\\in\\clip.mov")));
+        assert!(!is_candidate(Path::new(
+            "C:\\in\\clip.tinythis.balanced.mp4"
+        )));
+        assert!(!is_candidate(Path::new(
+            "C:\\in\\clip.tinythis.speed.2.mp4"
+        )));
+        assert!(!is_candidate(Path::new("C:\\in\\notes.txt")));
+    }
+
+    #[test]
+    fn files_are_ready_once_their_size_settles() {
+        let mut s = Stability::new(Duration::from_secs(5));
+        let p = Path::new("a.mp4");
+        let t0 = Instant::now();
+
+        assert!(!s.observe(p, 100, t0));
+        assert!(!s.observe(p, 200, t0 + Duration::from_secs(4)));
+        assert!(!s.observe(p, 200, t0 + Duration::from_secs(8)));
+        assert!(s.observe(p, 200, t0 + Duration::from_secs(9)));
+
+        // still empty: the copy hasn't started writing yet.
+        let e = Path::new("empty.mp4");
+        assert!(!s.observe(e, 0, t0));
+        assert!(!s.observe(e, 0, t0 + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn state_round_trips_and_keys_on_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = load_from_app_root(dir.path()).unwrap();
+        assert_eq!(state, WatchState::default());
+
+        let p = PathBuf::from("C:\\in\\a clip.mp4");
+        state.handled.insert(p.clone(), (Handled::Failed, 42));
+        save_to_app_root(dir.path(), &state).unwrap();
+
+        let loaded = load_from_app_root(dir.path()).unwrap();
+        assert_eq!(loaded, state);
+        assert!(loaded.is_handled(&p, 42));
+        assert!(!loaded.is_handled(&p, 43));
+    }
+
+    #[test]
+    fn moves_originals_into_done_without_overwriting() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.mp4");
+        std::fs::write(&a, b"1").unwrap();
+        assert_eq!(
+            move_to_done(&a).unwrap(),
+            dir.path().join("done").join("a.mp4")
+        );
+
+        std::fs::write(&a, b"2").unwrap();
+        assert_eq!(
+            move_to_done(&a).unwrap(),
+            dir.path().join("done").join("a.2.mp4")
+        );
+        assert!(!a.exists());
+    }
+}
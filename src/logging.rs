@@ -0,0 +1,166 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::error::Result;
+
+/// Overrides the level when no `-v`/`-q` is given: `off`, `error`, `warn`, `info`, `debug` or
+/// `trace`.
+pub const ENV_VAR: &str = "TINYTHIS_LOG";
+
+const TUI_LOG_FILE_NAME: &str = "tinythis.log";
+const TUI_OLD_LOG_FILE_NAME: &str = "tinythis.old.log";
+
+/// The level for `-v` (counted), `-q` and `TINYTHIS_LOG`; the flags win over the variable.
+pub fn level(verbose: u8, quiet: bool, env: Option<&str>) -> LevelFilter {
+    if quiet {
+        return LevelFilter::Error;
+    }
+    match verbose {
+        0 => env
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(LevelFilter::Warn),
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Where the tui logs, since it owns the terminal: `tinythis.log` in the app folder, with the
+/// previous session's kept as `tinythis.old.log`.
+pub fn tui_log_path() -> Result<PathBuf> {
+    Ok(crate::paths::app_root_dir()?.join(TUI_LOG_FILE_NAME))
+}
+
+/// Logs to stderr, or to `tui_log_path` for the tui. Only the first call takes effect.
+pub fn init(level: LevelFilter, tui: bool) -> Result<()> {
+    let sink = if tui {
+        let path = tui_log_path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let _ = std::fs::rename(&path, path.with_file_name(TUI_OLD_LOG_FILE_NAME));
+        Sink::File(File::create(path)?)
+    } else {
+        Sink::Stderr
+    };
+    if log::set_boxed_logger(Box::new(Logger {
+        level,
+        sink: Mutex::new(sink),
+    }))
+    .is_ok()
+    {
+        log::set_max_level(level);
+    }
+    Ok(())
+}
+
+enum Sink {
+    Stderr,
+    File(File),
+}
+
+struct Logger {
+    level: LevelFilter,
+    sink: Mutex<Sink>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // dependencies (http, tls) only get through with their warnings, unless tracing.
+        let ours = metadata.target().starts_with(env!("CARGO_CRATE_NAME"));
+        let cap = if ours || self.level == LevelFilter::Trace {
+            self.level
+        } else {
+            self.level.min(LevelFilter::Warn)
+        };
+        metadata.level() <= cap
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            timestamp(SystemTime::now()),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let Ok(mut sink) = self.sink.lock() else {
+            return;
+        };
+        let _ = match &mut *sink {
+            Sink::Stderr => std::io::stderr().write_all(line.as_bytes()),
+            Sink::File(f) => f.write_all(line.as_bytes()),
+        };
+    }
+
+    fn flush(&self) {
+        if let Ok(mut sink) = self.sink.lock() {
+            let _ = match &mut *sink {
+                Sink::Stderr => std::io::stderr().flush(),
+                Sink::File(f) => f.flush(),
+            };
+        }
+    }
+}
+
+/// `2026-03-01T09:05:07.042Z`, in utc.
+fn timestamp(now: SystemTime) -> String {
+    let d = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = d.as_secs();
+    let (y, m, day) = civil_from_days((secs / 86_400) as i64);
+    let (h, min, s) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    format!(
+        "{y:04}-{m:02}-{day:02}T{h:02}:{min:02}:{s:02}.{:03}Z",
+        d.subsec_millis()
+    )
+}
+
+/// Days since 1970-01-01 to a (year, month, day) date (Howard Hinnant's algorithm).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn flags_win_over_the_env_var() {
+        assert_eq!(level(0, false, None), LevelFilter::Warn);
+        assert_eq!(level(0, false, Some("debug")), LevelFilter::Debug);
+        assert_eq!(level(0, false, Some(" TRACE ")), LevelFilter::Trace);
+        assert_eq!(level(0, false, Some("loud")), LevelFilter::Warn);
+        assert_eq!(level(1, false, Some("off")), LevelFilter::Info);
+        assert_eq!(level(2, false, None), LevelFilter::Debug);
+        assert_eq!(level(5, false, None), LevelFilter::Trace);
+        assert_eq!(level(0, true, Some("trace")), LevelFilter::Error);
+    }
+
+    #[test]
+    fn timestamps_are_utc_with_millis() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let t = UNIX_EPOCH + Duration::from_millis(1_709_283_907_042);
+        assert_eq!(timestamp(t), "2024-03-01T09:05:07.042Z");
+        let leap = UNIX_EPOCH + Duration::from_secs(951_825_600);
+        assert_eq!(timestamp(leap), "2000-02-29T12:00:00.000Z");
+    }
+}
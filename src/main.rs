@@ -3,6 +3,8 @@ mod cli;
 mod confirm;
 mod error;
 mod exec;
+mod json;
+mod logging;
 mod options;
 mod paths;
 mod prefs;
@@ -27,6 +29,17 @@ fn real_main() -> crate::error::Result<()> {
     use std::io::IsTerminal;
 
     let cli = crate::cli::Cli::parse();
+    let tui = cli.inputs.is_empty() && cli.command.is_none();
+    let level = crate::logging::level(
+        cli.verbose,
+        cli.quiet,
+        std::env::var(crate::logging::ENV_VAR).ok().as_deref(),
+    );
+    crate::logging::init(level, tui)?;
+    log::debug!("tinythis {}", env!("CARGO_PKG_VERSION"));
+    if let Some(path) = &cli.log_file {
+        crate::exec::joblog::set_log_file(path.clone());
+    }
 
     if !cli.inputs.is_empty() {
         if cli.command.is_some() {
@@ -37,8 +50,8 @@ fn real_main() -> crate::error::Result<()> {
         return crate::cli::run_positional(&cli);
     }
 
-    match cli.command {
-        Some(command) => crate::cli::run(cli.gpu, cli.cpu, command),
+    match &cli.command {
+        Some(command) => crate::cli::run(&cli, command),
         None => {
             let mut initial_status: Option<String> = None;
             if cfg!(windows) {
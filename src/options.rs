@@ -2,11 +2,76 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use crate::error::{Result, TinythisError};
-
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+use crate::exec::compress::{HwDecode, OnExisting, OutputNaming, Preserve};
+use crate::exec::container::Container;
+use crate::exec::extra_args::PresetArgs;
+use crate::exec::gif::{Dither, GifSettings};
+use crate::exec::metrics::QualityThreshold;
+use crate::presets::Preset;
+use crate::presets::encoder::{Codec, EncoderPref};
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Options {
+    /// preset the tui starts on and plain `tinythis <files>` uses.
+    pub preset: Preset,
     pub gpu: bool,
+    /// which encoder `gpu` picks (or which cpu encoder when it's off); unset means any gpu.
+    pub encoder: Option<EncoderPref>,
+    /// what `gpu`/`cpu` encode to; a fixed `encoder` brings its own.
+    pub codec: Codec,
+    /// what outputs are written as.
+    pub container: Container,
+    /// retry on the cpu when the gpu encoder fails to start instead of stopping the batch.
+    pub cpu_fallback: bool,
+    /// files encoded at once.
+    pub jobs: u8,
     pub path_optout: bool,
+    pub preserve: Preserve,
+    pub hw_decode: HwDecode,
+    pub auto_min_savings: u8,
+    pub measure: bool,
+    pub min_quality: Option<QualityThreshold>,
+    pub output: OutputNaming,
+    /// defaults for the gif and webp presets.
+    pub gif: GifSettings,
+    /// probe inputs without a known video extension instead of refusing them.
+    pub sniff_inputs: bool,
+    /// look for a new release when the tui starts.
+    pub update_check: bool,
+    /// the user's own ffmpeg options, per preset.
+    pub extra_args: PresetArgs,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            preset: Preset::Balanced,
+            gpu: false,
+            encoder: None,
+            codec: Codec::H264,
+            container: Container::Mp4,
+            cpu_fallback: false,
+            jobs: 1,
+            path_optout: false,
+            preserve: Preserve::default(),
+            hw_decode: HwDecode::default(),
+            auto_min_savings: 20,
+            measure: false,
+            min_quality: None,
+            output: OutputNaming::default(),
+            gif: GifSettings::default(),
+            sniff_inputs: true,
+            update_check: true,
+            extra_args: PresetArgs::default(),
+        }
+    }
+}
+
+impl Options {
+    /// The encoder preference with the gpu switch (from here or a cli flag) applied.
+    pub fn encoder_pref(&self, gpu: bool) -> EncoderPref {
+        self.encoder.unwrap_or(EncoderPref::Gpu).with_gpu(gpu)
+    }
 }
 
 pub fn load() -> Result<Options> {
@@ -14,6 +79,11 @@ pub fn load() -> Result<Options> {
     load_from_app_root(&app_root)
 }
 
+/// Where options.toml lives, whether or not it exists yet.
+pub fn options_path() -> Result<PathBuf> {
+    Ok(options_file(&crate::paths::app_root_dir()?))
+}
+
 pub fn set_gpu(gpu: bool) -> Result<()> {
     update(|o| o.gpu = gpu).map(|_| ())
 }
@@ -51,16 +121,54 @@ fn load_from_app_root(app_root: &Path) -> Result<Options> {
 
     match std::fs::read_to_string(&p) {
         Ok(s) => {
+            log::debug!("loading {}", p.display());
             let parsed = parse_options_toml(&s)?;
+            apply(&mut o.preset, parsed.preset);
             if let Some(v) = parsed.gpu {
                 o.gpu = v;
             }
+            if parsed.encoder.is_some() {
+                o.encoder = parsed.encoder;
+            }
+            apply(&mut o.codec, parsed.codec);
+            apply(&mut o.container, parsed.container);
+            apply(&mut o.cpu_fallback, parsed.cpu_fallback);
+            apply(&mut o.jobs, parsed.jobs);
             if let Some(v) = parsed.path_optout {
                 o.path_optout = v;
                 saw_path_optout = true;
             }
+            apply(&mut o.preserve.metadata, parsed.preserve_metadata);
+            apply(&mut o.preserve.chapters, parsed.preserve_chapters);
+            apply(&mut o.preserve.creation_time, parsed.preserve_creation_time);
+            apply(&mut o.preserve.rotation, parsed.preserve_rotation);
+            apply(&mut o.preserve.subtitles, parsed.preserve_subtitles);
+            apply(&mut o.preserve.timestamps, parsed.preserve_timestamps);
+            apply(&mut o.hw_decode.quality, parsed.hw_decode_quality);
+            apply(&mut o.hw_decode.balanced, parsed.hw_decode_balanced);
+            apply(&mut o.hw_decode.speed, parsed.hw_decode_speed);
+            apply(&mut o.auto_min_savings, parsed.auto_min_savings);
+            apply(&mut o.measure, parsed.measure_enabled);
+            if parsed.measure_min_quality.is_some() {
+                o.min_quality = parsed.measure_min_quality;
+            }
+            if let Some(dir) = parsed.output_dir {
+                o.output.dir = (!dir.is_empty()).then(|| PathBuf::from(dir));
+            }
+            apply(&mut o.output.template, parsed.output_template);
+            apply(&mut o.output.on_existing, parsed.output_on_existing);
+            apply(&mut o.gif.fps, parsed.gif_fps);
+            apply(&mut o.gif.width, parsed.gif_width);
+            apply(&mut o.gif.plays, parsed.gif_plays);
+            apply(&mut o.gif.dither, parsed.gif_dither);
+            apply(&mut o.gif.max_bytes, parsed.gif_max_size);
+            apply(&mut o.sniff_inputs, parsed.input_sniff);
+            apply(&mut o.update_check, parsed.update_check);
+            o.extra_args = parsed.extra_args;
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::debug!("no {}, using defaults", p.display());
         }
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
         Err(e) => return Err(e.into()),
     }
 
@@ -77,16 +185,73 @@ fn load_from_app_root(app_root: &Path) -> Result<Options> {
     Ok(o)
 }
 
+fn apply<T>(dest: &mut T, parsed: Option<T>) {
+    if let Some(v) = parsed {
+        *dest = v;
+    }
+}
+
 fn save_to_app_root(app_root: &Path, o: &Options) -> Result<()> {
     std::fs::create_dir_all(app_root)?;
 
     let p = options_file(app_root);
     let dir = p.parent().unwrap_or(app_root);
 
+    let preserve = &o.preserve;
+    // literal (single-quoted) strings, so windows paths need no escaping.
     let content = format!(
-        "gpu = {}\npath.optout = {}\n",
-        if o.gpu { "true" } else { "false" },
-        if o.path_optout { "true" } else { "false" }
+        "preset = '{}'\ngpu = {}\n{}codec = '{}'\ncontainer = '{}'\ncpu_fallback = {}\njobs = {}\n\
+update_check = {}\n\
+path.optout = {}\n\
+preserve.metadata = {}\npreserve.chapters = {}\npreserve.creation_time = {}\n\
+preserve.rotation = {}\npreserve.subtitles = {}\npreserve.timestamps = {}\n\
+hw_decode.quality = {}\nhw_decode.balanced = {}\nhw_decode.speed = {}\n\
+auto.min_savings = {}\nmeasure.enabled = {}\n{}\
+output.dir = '{}'\noutput.template = '{}'\noutput.on_existing = '{}'\n\
+gif.fps = {}\ngif.width = {}\ngif.plays = {}\ngif.dither = '{}'\ngif.max_size = '{}'\n\
+input.sniff = {}\n{}",
+        o.preset.as_str(),
+        o.gpu,
+        o.encoder
+            .map(|e| format!("encoder = '{e}'\n"))
+            .unwrap_or_default(),
+        o.codec.as_str(),
+        o.container.as_str(),
+        o.cpu_fallback,
+        o.jobs,
+        o.update_check,
+        o.path_optout,
+        preserve.metadata,
+        preserve.chapters,
+        preserve.creation_time,
+        preserve.rotation,
+        preserve.subtitles,
+        preserve.timestamps,
+        o.hw_decode.quality,
+        o.hw_decode.balanced,
+        o.hw_decode.speed,
+        o.auto_min_savings,
+        o.measure,
+        o.min_quality
+            .map(|t| format!("measure.min_quality = '{t}'\n"))
+            .unwrap_or_default(),
+        o.output
+            .dir
+            .as_ref()
+            .map(|d| d.display().to_string())
+            .unwrap_or_default(),
+        o.output.template,
+        o.output.on_existing.as_str(),
+        o.gif.fps,
+        o.gif.width,
+        o.gif.plays,
+        o.gif.dither.as_str(),
+        o.gif
+            .max_bytes
+            .map(crate::exec::report::size_text)
+            .unwrap_or_default(),
+        o.sniff_inputs,
+        extra_args_lines(&o.extra_args),
     );
 
     let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
@@ -104,22 +269,107 @@ fn save_to_app_root(app_root: &Path, o: &Options) -> Result<()> {
     }
 }
 
+/// `extra_args.<preset>` and `extra_args.input.<preset>` lines for the presets that have any.
+fn extra_args_lines(extra: &PresetArgs) -> String {
+    let array = |args: &[String]| {
+        let items = args
+            .iter()
+            .map(|a| {
+                if a.contains('\'') {
+                    format!("\"{a}\"")
+                } else {
+                    format!("'{a}'")
+                }
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", items.join(", "))
+    };
+    let mut out = String::new();
+    for preset in EXTRA_ARGS_PRESETS {
+        let e = extra.for_preset(preset);
+        if !e.output.is_empty() {
+            out.push_str(&format!(
+                "extra_args.{} = {}\n",
+                preset.as_str(),
+                array(&e.output)
+            ));
+        }
+        if !e.input.is_empty() {
+            out.push_str(&format!(
+                "extra_args.input.{} = {}\n",
+                preset.as_str(),
+                array(&e.input)
+            ));
+        }
+    }
+    out
+}
+
+/// Auto takes the options of whichever preset it picks.
+const EXTRA_ARGS_PRESETS: [Preset; 6] = [
+    Preset::Quality,
+    Preset::Balanced,
+    Preset::Speed,
+    Preset::Remux,
+    Preset::Gif,
+    Preset::Webp,
+];
+
+/// The preset of an `extra_args.<preset>` key, and whether it's the `input.` one.
+fn extra_args_key(key: &str) -> Option<(Preset, bool)> {
+    let rest = key.strip_prefix("extra_args.")?;
+    let (input, name) = match rest.strip_prefix("input.") {
+        Some(name) => (true, name),
+        None => (false, rest),
+    };
+    let preset = Preset::parse(name)?;
+    EXTRA_ARGS_PRESETS
+        .contains(&preset)
+        .then_some((preset, input))
+}
+
 #[derive(Debug, Default)]
 struct ParsedOptions {
+    preset: Option<Preset>,
     gpu: Option<bool>,
+    encoder: Option<EncoderPref>,
+    codec: Option<Codec>,
+    container: Option<Container>,
+    cpu_fallback: Option<bool>,
+    jobs: Option<u8>,
     path_optout: Option<bool>,
+    preserve_metadata: Option<bool>,
+    preserve_chapters: Option<bool>,
+    preserve_creation_time: Option<bool>,
+    preserve_rotation: Option<bool>,
+    preserve_subtitles: Option<bool>,
+    preserve_timestamps: Option<bool>,
+    hw_decode_quality: Option<bool>,
+    hw_decode_balanced: Option<bool>,
+    hw_decode_speed: Option<bool>,
+    auto_min_savings: Option<u8>,
+    measure_enabled: Option<bool>,
+    measure_min_quality: Option<QualityThreshold>,
+    output_dir: Option<String>,
+    output_template: Option<String>,
+    output_on_existing: Option<OnExisting>,
+    gif_fps: Option<u32>,
+    gif_width: Option<u32>,
+    gif_plays: Option<u32>,
+    gif_dither: Option<Dither>,
+    /// `Some(None)` for an empty string, which turns the size target off.
+    gif_max_size: Option<Option<u64>>,
+    input_sniff: Option<bool>,
+    update_check: Option<bool>,
+    extra_args: PresetArgs,
 }
 
 fn parse_options_toml(s: &str) -> Result<ParsedOptions> {
     let mut out = ParsedOptions::default();
 
-    #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-    enum Section {
-        Root,
-        Path,
-        Other,
-    }
-    let mut section = Section::Root;
+    // keys are matched by their full dotted name, so `[path]\noptout` and
+    // `path.optout` at the root mean the same thing.
+    let mut section = String::new();
 
     for (idx, raw) in s.lines().enumerate() {
         let line = strip_inline_comment(raw).trim();
@@ -128,39 +378,167 @@ fn parse_options_toml(s: &str) -> Result<ParsedOptions> {
         }
 
         if line.starts_with('[') && line.ends_with(']') {
-            let name = line[1..line.len() - 1].trim();
-            section = match name {
-                "path" => Section::Path,
-                _ => Section::Other,
-            };
+            section = line[1..line.len() - 1].trim().to_string();
             continue;
         }
 
         let Some((k, v)) = line.split_once('=') else {
             continue;
         };
-        let key = k.trim();
-        let target = match (section, key) {
-            (Section::Root, "gpu") => Some("gpu"),
-            (Section::Root, "path.optout") => Some("path.optout"),
-            (Section::Path, "optout") => Some("path.optout"),
-            _ => None,
-        };
-        let Some(target) = target else {
-            continue;
+        let key = if section.is_empty() {
+            k.trim().to_string()
+        } else {
+            format!("{section}.{}", k.trim())
         };
-
         let val = strip_inline_comment(v).trim();
-        let b = parse_bool(val).ok_or_else(|| {
+        let line_no = idx + 1;
+
+        let invalid = |msg: String| {
             TinythisError::InvalidArgs(format!(
-                "invalid options.toml on line {}: expected boolean for `{target}`",
-                idx + 1
+                "invalid options.toml on line {line_no}: `{key}`: {msg}"
             ))
-        })?;
+        };
 
-        match target {
-            "gpu" => out.gpu = Some(b),
-            "path.optout" => out.path_optout = Some(b),
+        if let Some((preset, input)) = extra_args_key(&key) {
+            let args = expect_string_array(val, &key, line_no)?;
+            crate::exec::extra_args::validate(&args).map_err(invalid)?;
+            let e = out.extra_args.for_preset_mut(preset);
+            if input {
+                e.input = args;
+            } else {
+                e.output = args;
+            }
+            continue;
+        }
+
+        match key.as_str() {
+            "preset" => {
+                let v = expect_string(val, &key, line_no)?;
+                let p = Preset::parse(v).ok_or_else(|| {
+                    invalid(
+                        "expected quality, balanced, speed, remux, auto, gif or webp".to_string(),
+                    )
+                })?;
+                out.preset = Some(p)
+            }
+            "gpu" => out.gpu = Some(expect_bool(val, &key, line_no)?),
+            "codec" => {
+                let v = expect_string(val, &key, line_no)?;
+                let c = Codec::parse(v)
+                    .ok_or_else(|| invalid("expected h264, hevc or av1".to_string()))?;
+                out.codec = Some(c)
+            }
+            "container" => {
+                let v = expect_string(val, &key, line_no)?;
+                let c = Container::parse(v)
+                    .ok_or_else(|| invalid("expected mp4, mkv, webm or mov".to_string()))?;
+                out.container = Some(c)
+            }
+            "update_check" => out.update_check = Some(expect_bool(val, &key, line_no)?),
+            "input.sniff" => out.input_sniff = Some(expect_bool(val, &key, line_no)?),
+            "cpu_fallback" => out.cpu_fallback = Some(expect_bool(val, &key, line_no)?),
+            "jobs" => {
+                let v = expect_int(val, &key, line_no)?;
+                let max = crate::exec::pool::MAX_JOBS;
+                if !(1..=u64::from(max)).contains(&v) {
+                    return Err(invalid(format!("expected 1-{max}")));
+                }
+                out.jobs = Some(v as u8)
+            }
+            "encoder" => {
+                let v = expect_string(val, &key, line_no)?;
+                let e = EncoderPref::parse(v).map_err(|e| {
+                    TinythisError::InvalidArgs(format!(
+                        "invalid options.toml on line {line_no}: `{key}`: {e}"
+                    ))
+                })?;
+                out.encoder = Some(e)
+            }
+            "path.optout" => out.path_optout = Some(expect_bool(val, &key, line_no)?),
+            "preserve.metadata" => out.preserve_metadata = Some(expect_bool(val, &key, line_no)?),
+            "preserve.chapters" => out.preserve_chapters = Some(expect_bool(val, &key, line_no)?),
+            "preserve.creation_time" => {
+                out.preserve_creation_time = Some(expect_bool(val, &key, line_no)?)
+            }
+            "preserve.rotation" => out.preserve_rotation = Some(expect_bool(val, &key, line_no)?),
+            "preserve.subtitles" => out.preserve_subtitles = Some(expect_bool(val, &key, line_no)?),
+            "preserve.timestamps" => {
+                out.preserve_timestamps = Some(expect_bool(val, &key, line_no)?)
+            }
+            "hw_decode.quality" => out.hw_decode_quality = Some(expect_bool(val, &key, line_no)?),
+            "hw_decode.balanced" => out.hw_decode_balanced = Some(expect_bool(val, &key, line_no)?),
+            "hw_decode.speed" => out.hw_decode_speed = Some(expect_bool(val, &key, line_no)?),
+            "auto.min_savings" => {
+                let v = expect_int(val, &key, line_no)?;
+                if v > 100 {
+                    return Err(TinythisError::InvalidArgs(format!(
+                        "invalid options.toml on line {line_no}: `{key}` is a percentage (0-100)"
+                    )));
+                }
+                out.auto_min_savings = Some(v as u8)
+            }
+            "measure.enabled" => out.measure_enabled = Some(expect_bool(val, &key, line_no)?),
+            "measure.min_quality" => {
+                let v = expect_string(val, &key, line_no)?;
+                let t = QualityThreshold::parse(v).map_err(|e| {
+                    TinythisError::InvalidArgs(format!(
+                        "invalid options.toml on line {line_no}: `{key}`: {e}"
+                    ))
+                })?;
+                out.measure_min_quality = Some(t)
+            }
+            "output.dir" => out.output_dir = Some(expect_string(val, &key, line_no)?.to_string()),
+            "output.template" => {
+                let v = expect_string(val, &key, line_no)?;
+                OutputNaming::validate_template(v).map_err(invalid)?;
+                out.output_template = Some(v.to_string())
+            }
+            "output.on_existing" => {
+                let v = expect_string(val, &key, line_no)?;
+                let o = OnExisting::parse(v)
+                    .ok_or_else(|| invalid("expected rename or overwrite".to_string()))?;
+                out.output_on_existing = Some(o)
+            }
+            "gif.fps" => {
+                let v = expect_int(val, &key, line_no)?;
+                if !(1..=u64::from(crate::exec::gif::MAX_FPS)).contains(&v) {
+                    return Err(invalid(format!("expected 1-{}", crate::exec::gif::MAX_FPS)));
+                }
+                out.gif_fps = Some(v as u32)
+            }
+            "gif.width" => {
+                let v = expect_int(val, &key, line_no)?;
+                if !(u64::from(crate::exec::gif::SMALLEST_WIDTH)..=u64::from(u32::MAX)).contains(&v)
+                {
+                    return Err(invalid(format!(
+                        "expected at least {} px",
+                        crate::exec::gif::SMALLEST_WIDTH
+                    )));
+                }
+                out.gif_width = Some(v as u32)
+            }
+            "gif.plays" => {
+                let v = expect_int(val, &key, line_no)?;
+                let v = u32::try_from(v).map_err(|_| invalid("too many plays".to_string()))?;
+                out.gif_plays = Some(v)
+            }
+            "gif.dither" => {
+                let v = expect_string(val, &key, line_no)?;
+                let d = Dither::parse(v).ok_or_else(|| {
+                    invalid("expected bayer, floyd_steinberg, sierra or none".to_string())
+                })?;
+                out.gif_dither = Some(d)
+            }
+            "gif.max_size" => {
+                let v = expect_string(val, &key, line_no)?;
+                let size = match v.trim() {
+                    "" => None,
+                    v => Some(crate::exec::report::parse_size(v).ok_or_else(|| {
+                        invalid("expected a size like '8M' or '500k'".to_string())
+                    })?),
+                };
+                out.gif_max_size = Some(size)
+            }
             _ => {}
         }
     }
@@ -168,6 +546,65 @@ fn parse_options_toml(s: &str) -> Result<ParsedOptions> {
     Ok(out)
 }
 
+fn expect_bool(val: &str, key: &str, line_no: usize) -> Result<bool> {
+    parse_bool(val).ok_or_else(|| {
+        TinythisError::InvalidArgs(format!(
+            "invalid options.toml on line {line_no}: expected boolean for `{key}`"
+        ))
+    })
+}
+
+fn expect_int(val: &str, key: &str, line_no: usize) -> Result<u64> {
+    val.trim().parse::<u64>().map_err(|_| {
+        TinythisError::InvalidArgs(format!(
+            "invalid options.toml on line {line_no}: expected integer for `{key}`"
+        ))
+    })
+}
+
+/// A `"basic"` or `'literal'` string, taken as-is (escapes aren't interpreted).
+fn expect_string<'a>(val: &'a str, key: &str, line_no: usize) -> Result<&'a str> {
+    ['"', '\'']
+        .into_iter()
+        .find_map(|q| val.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+        .ok_or_else(|| {
+            TinythisError::InvalidArgs(format!(
+                "invalid options.toml on line {line_no}: expected quoted string for `{key}`"
+            ))
+        })
+}
+
+/// A one-line array of quoted strings, like `['-tune', 'film']`.
+fn expect_string_array(val: &str, key: &str, line_no: usize) -> Result<Vec<String>> {
+    let err = || {
+        TinythisError::InvalidArgs(format!(
+            "invalid options.toml on line {line_no}: expected an array of quoted strings for `{key}`"
+        ))
+    };
+    let mut rest = val
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or_else(err)?
+        .trim();
+    let mut items = Vec::new();
+    while !rest.is_empty() {
+        let q = rest
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(err)?;
+        let end = rest[1..].find(q).ok_or_else(err)? + 1;
+        items.push(rest[1..end].to_string());
+        rest = rest[end + 1..].trim_start();
+        if let Some(r) = rest.strip_prefix(',') {
+            rest = r.trim_start();
+        } else if !rest.is_empty() {
+            return Err(err());
+        }
+    }
+    Ok(items)
+}
+
 fn strip_inline_comment(s: &str) -> &str {
     match s.split_once('#') {
         Some((before, _)) => before,
@@ -214,6 +651,165 @@ mod tests {
         assert_eq!(a.gpu, None);
     }
 
+    #[test]
+    fn parses_preserve_switches_and_round_trips() {
+        let a = parse_options_toml("[preserve]\nsubtitles = true\ntimestamps = true\n").unwrap();
+        assert_eq!(a.preserve_subtitles, Some(true));
+        assert_eq!(a.preserve_timestamps, Some(true));
+        assert_eq!(a.preserve_metadata, None);
+
+        let b = parse_options_toml("[auto]\nmin_savings = 35\n").unwrap();
+        assert_eq!(b.auto_min_savings, Some(35));
+        assert!(parse_options_toml("auto.min_savings = 150\n").is_err());
+        assert!(parse_options_toml("auto.min_savings = lots\n").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let o = Options {
+            preserve: Preserve {
+                metadata: false,
+                rotation: true,
+                ..Preserve::default()
+            },
+            ..Options::default()
+        };
+        save_to_app_root(dir.path(), &o).unwrap();
+        assert_eq!(load_from_app_root(dir.path()).unwrap(), o);
+    }
+
+    #[test]
+    fn parses_measure_settings_and_round_trips() {
+        let a =
+            parse_options_toml("[measure]\nenabled = true\nmin_quality = \"vmaf=93\"\n").unwrap();
+        assert_eq!(a.measure_enabled, Some(true));
+        assert_eq!(
+            a.measure_min_quality,
+            Some(QualityThreshold::parse("vmaf=93").unwrap())
+        );
+        assert!(parse_options_toml("measure.min_quality = vmaf=93\n").is_err());
+        assert!(parse_options_toml("measure.min_quality = \"lpips=1\"\n").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let o = Options {
+            measure: true,
+            min_quality: Some(QualityThreshold::parse("ssim=0.97").unwrap()),
+            ..Options::default()
+        };
+        save_to_app_root(dir.path(), &o).unwrap();
+        assert_eq!(load_from_app_root(dir.path()).unwrap(), o);
+    }
+
+    #[test]
+    fn parses_encoder_choice_and_round_trips() {
+        let a = parse_options_toml("gpu = true\nencoder = \"qsv\"\n[hw_decode]\nspeed = true\n")
+            .unwrap();
+        assert_eq!(a.encoder, Some(EncoderPref::parse("h264_qsv").unwrap()));
+        assert_eq!(a.hw_decode_speed, Some(true));
+        assert_eq!(a.hw_decode_quality, None);
+        assert!(parse_options_toml("encoder = \"glide\"\n").is_err());
+        assert_eq!(parse_options_toml("jobs = 2\n").unwrap().jobs, Some(2));
+        assert!(parse_options_toml("jobs = 0\n").is_err());
+        assert!(parse_options_toml("jobs = 9\n").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let o = Options {
+            gpu: true,
+            encoder: Some(EncoderPref::parse("hevc_amf").unwrap()),
+            cpu_fallback: true,
+            jobs: 3,
+            hw_decode: HwDecode {
+                speed: true,
+                ..HwDecode::default()
+            },
+            ..Options::default()
+        };
+        save_to_app_root(dir.path(), &o).unwrap();
+        assert_eq!(load_from_app_root(dir.path()).unwrap(), o);
+
+        assert_eq!(Options::default().encoder_pref(false), EncoderPref::Cpu);
+        assert_eq!(Options::default().encoder_pref(true), EncoderPref::Gpu);
+        assert_eq!(o.encoder_pref(true), o.encoder.unwrap());
+    }
+
+    #[test]
+    fn parses_output_and_defaults_and_round_trips() {
+        let a = parse_options_toml(
+            "preset = \"speed\"\ncodec = 'hevc'\ncontainer = 'mkv'\nupdate_check = false\n\
+[output]\ndir = 'D:\\out'\ntemplate = '{stem}-{preset}'\non_existing = \"overwrite\"\n\
+[input]\nsniff = false\n[gif]\nfps = 12\ndither = 'floyd-steinberg'\nmax_size = '8M'\n",
+        )
+        .unwrap();
+        assert_eq!(a.preset, Some(Preset::Speed));
+        assert_eq!(a.codec, Some(Codec::Hevc));
+        assert_eq!(a.container, Some(Container::Mkv));
+        assert_eq!(a.update_check, Some(false));
+        assert_eq!(a.output_dir.as_deref(), Some("D:\\out"));
+        assert_eq!(a.output_template.as_deref(), Some("{stem}-{preset}"));
+        assert_eq!(a.output_on_existing, Some(OnExisting::Overwrite));
+        assert_eq!(a.input_sniff, Some(false));
+        assert_eq!(a.gif_fps, Some(12));
+        assert_eq!(a.gif_dither, Some(Dither::FloydSteinberg));
+        assert_eq!(a.gif_max_size, Some(Some(8 << 20)));
+        assert!(parse_options_toml("gif.fps = 0\n").is_err());
+        assert!(parse_options_toml("gif.max_size = 'big'\n").is_err());
+        assert!(parse_options_toml("output.template = '{preset}'\n").is_err());
+        assert!(parse_options_toml("output.template = '{stem}.{crf}'\n").is_err());
+        assert!(parse_options_toml("codec = 'mpeg2'\n").is_err());
+        assert!(parse_options_toml("container = 'avi'\n").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let o = Options {
+            preset: Preset::Remux,
+            codec: Codec::Av1,
+            container: Container::Webm,
+            sniff_inputs: false,
+            update_check: false,
+            gif: GifSettings {
+                fps: 10,
+                width: 320,
+                plays: 2,
+                dither: Dither::None,
+                max_bytes: Some(1536 << 10),
+            },
+            output: OutputNaming {
+                dir: Some(PathBuf::from("C:\\Users\\me\\Videos\\small")),
+                template: "{stem}_small".to_string(),
+                on_existing: OnExisting::Overwrite,
+            },
+            ..Options::default()
+        };
+        save_to_app_root(dir.path(), &o).unwrap();
+        assert_eq!(load_from_app_root(dir.path()).unwrap(), o);
+
+        // an empty folder means next to the input.
+        save_to_app_root(dir.path(), &Options::default()).unwrap();
+        assert_eq!(load_from_app_root(dir.path()).unwrap().output.dir, None);
+    }
+
+    #[test]
+    fn parses_extra_args_per_preset_and_round_trips() {
+        let a = parse_options_toml(
+            "[extra_args]\nquality = ['-tune', \"film\",]\nspeed = []\n\
+[extra_args.input]\nquality = ['-thread_queue_size', '1024']\n",
+        )
+        .unwrap();
+        let q = a.extra_args.for_preset(Preset::Quality);
+        assert_eq!(q.output, ["-tune", "film"]);
+        assert_eq!(q.input, ["-thread_queue_size", "1024"]);
+        assert!(a.extra_args.for_preset(Preset::Speed).is_empty());
+        assert!(parse_options_toml("extra_args.balanced = '-tune film'\n").is_err());
+        assert!(parse_options_toml("extra_args.balanced = ['-tune' 'film']\n").is_err());
+        assert!(parse_options_toml("extra_args.balanced = ['-y']\n").is_err());
+        assert!(parse_options_toml("extra_args.balanced = ['out.mp4']\n").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut o = Options::default();
+        o.extra_args.for_preset_mut(Preset::Remux).output =
+            vec!["-metadata".to_string(), "title=it's".to_string()];
+        o.extra_args.for_preset_mut(Preset::Balanced).input = vec!["-re".to_string()];
+        save_to_app_root(dir.path(), &o).unwrap();
+        assert_eq!(load_from_app_root(dir.path()).unwrap(), o);
+    }
+
     #[test]
     fn load_reads_legacy_path_optout_file_without_writing() {
         let dir = tempfile::tempdir().unwrap();
@@ -227,7 +823,8 @@ mod tests {
             o,
             Options {
                 gpu: false,
-                path_optout: true
+                path_optout: true,
+                ..Options::default()
             }
         );
 
@@ -0,0 +1,270 @@
+use std::path::Path;
+
+use crate::exec::container::Container;
+use crate::exec::probe::{MediaInfo, StreamInfo, StreamKind};
+
+use super::Preset;
+
+/// Rough bits per pixel each preset lands on for h264 output.
+const QUALITY_BPP: f64 = 0.10;
+const BALANCED_BPP: f64 = 0.06;
+const SPEED_BPP: f64 = 0.035;
+
+/// Sources above this (h264-equivalent) have enough headroom for the quality preset.
+const HIGH_BPP: f64 = 0.25;
+
+/// Inputs longer than this favour encode speed.
+const LONG_INPUT_US: u64 = 60 * 60 * 1_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoDecision {
+    pub action: AutoAction,
+    pub reason: String,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AutoAction {
+    Encode(Preset),
+    Skip,
+}
+
+impl AutoDecision {
+    pub fn label(&self) -> &'static str {
+        match self.action {
+            AutoAction::Encode(p) => p.as_str(),
+            AutoAction::Skip => "skip",
+        }
+    }
+}
+
+/// Picks what to do with one input from its probed bitrate-per-pixel and codecs.
+///
+/// `min_savings` is the smallest expected size reduction (percent) worth re-encoding for; below
+/// it, inputs not yet in `container` are remuxed into it when they can be.
+pub fn decide(
+    input: &Path,
+    info: &MediaInfo,
+    min_savings: u8,
+    container: Container,
+) -> AutoDecision {
+    let Some(video) = info.streams_of(StreamKind::Video).next() else {
+        return skip("no video stream".to_string());
+    };
+
+    let Some(bpp) = bits_per_pixel(info, video) else {
+        return encode(Preset::Balanced, "bitrate unknown".to_string());
+    };
+
+    // h264-equivalent bpp: newer codecs pack more detail per bit, older ones less.
+    let effective = bpp * codec_efficiency(&video.codec);
+    let codec = video.codec.as_str();
+
+    let preset = if effective >= HIGH_BPP {
+        Preset::Quality
+    } else if info.duration_us.unwrap_or(0) >= LONG_INPUT_US {
+        Preset::Speed
+    } else {
+        Preset::Balanced
+    };
+    let savings = expected_savings(bpp, effective, preset);
+
+    if savings < min_savings as f64 {
+        if !container.matches_path(input) && crate::exec::compress::can_remux(info, container) {
+            return encode(
+                Preset::Remux,
+                format!(
+                    "{codec} at {bpp:.3} bpp is already compact; remuxing into {}",
+                    container.as_str()
+                ),
+            );
+        }
+        return skip(format!(
+            "{codec} at {bpp:.3} bpp; expected savings {savings:.0}% < {min_savings}%"
+        ));
+    }
+
+    let why = match preset {
+        Preset::Quality => "high bitrate source",
+        Preset::Speed => "long input",
+        _ => "moderate bitrate",
+    };
+    encode(
+        preset,
+        format!("{why} ({codec} at {bpp:.3} bpp), ~{savings:.0}% smaller"),
+    )
+}
+
+fn encode(preset: Preset, reason: String) -> AutoDecision {
+    AutoDecision {
+        action: AutoAction::Encode(preset),
+        reason,
+    }
+}
+
+fn skip(reason: String) -> AutoDecision {
+    AutoDecision {
+        action: AutoAction::Skip,
+        reason,
+    }
+}
+
+fn bits_per_pixel(info: &MediaInfo, video: &StreamInfo) -> Option<f64> {
+    let (w, h, fps) = (video.width?, video.height?, video.fps?);
+    if fps <= 0.0 {
+        return None;
+    }
+
+    let kbps = match video.bitrate_kbps {
+        Some(v) => v,
+        None => {
+            let audio = info
+                .streams_of(StreamKind::Audio)
+                .map(|a| a.bitrate_kbps.unwrap_or(128))
+                .sum::<u64>();
+            info.bitrate_kbps?.saturating_sub(audio)
+        }
+    };
+    if kbps == 0 {
+        return None;
+    }
+
+    Some((kbps as f64 * 1000.0) / (w as f64 * h as f64 * fps))
+}
+
+fn codec_efficiency(codec: &str) -> f64 {
+    match codec {
+        "av1" => 2.0,
+        "hevc" | "vp9" => 1.6,
+        "h264" => 1.0,
+        // mpeg2, mpeg4 part 2, wmv and friends need far more bits than h264.
+        _ => 0.6,
+    }
+}
+
+/// Output can't carry more detail than the source had, so it lands at whichever is lower:
+/// the preset's target or the source's h264-equivalent bpp.
+fn expected_savings(bpp: f64, effective_bpp: f64, preset: Preset) -> f64 {
+    let target = match preset {
+        Preset::Quality => QUALITY_BPP,
+        Preset::Speed => SPEED_BPP,
+        _ => BALANCED_BPP,
+    };
+    ((1.0 - target.min(effective_bpp) / bpp) * 100.0).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(codec: &str, video_kbps: u64, duration_s: u64) -> MediaInfo {
+        MediaInfo {
+            duration_us: Some(duration_s * 1_000_000),
+            streams: vec![
+                StreamInfo {
+                    index: 0,
+                    kind: StreamKind::Video,
+                    codec: codec.to_string(),
+                    width: Some(1920),
+                    height: Some(1080),
+                    fps: Some(30.0),
+                    bitrate_kbps: Some(video_kbps),
+                    ..Default::default()
+                },
+                StreamInfo {
+                    index: 1,
+                    kind: StreamKind::Audio,
+                    codec: "aac".to_string(),
+                    bitrate_kbps: Some(128),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn high_bitrate_sources_get_quality() {
+        // ~0.48 bpp at 1080p30
+        let d = decide(
+            Path::new("a.mp4"),
+            &info("h264", 30_000, 60),
+            20,
+            Container::Mp4,
+        );
+        assert_eq!(d.action, AutoAction::Encode(Preset::Quality));
+    }
+
+    #[test]
+    fn moderate_sources_get_balanced_and_long_ones_speed() {
+        // ~0.12 bpp
+        let d = decide(
+            Path::new("a.mp4"),
+            &info("h264", 7_500, 60),
+            20,
+            Container::Mp4,
+        );
+        assert_eq!(d.action, AutoAction::Encode(Preset::Balanced));
+
+        let d = decide(
+            Path::new("a.mp4"),
+            &info("h264", 7_500, 2 * 60 * 60),
+            20,
+            Container::Mp4,
+        );
+        assert_eq!(d.action, AutoAction::Encode(Preset::Speed));
+    }
+
+    #[test]
+    fn compact_sources_are_skipped_or_remuxed() {
+        // ~0.04 bpp h264: nothing to gain
+        let d = decide(
+            Path::new("a.mp4"),
+            &info("h264", 2_500, 60),
+            20,
+            Container::Mp4,
+        );
+        assert_eq!(d.action, AutoAction::Skip);
+
+        let d = decide(
+            Path::new("a.mov"),
+            &info("h264", 2_500, 60),
+            20,
+            Container::Mp4,
+        );
+        assert_eq!(d.action, AutoAction::Encode(Preset::Remux));
+        let d = decide(
+            Path::new("a.mp4"),
+            &info("h264", 2_500, 60),
+            20,
+            Container::Mkv,
+        );
+        assert_eq!(d.action, AutoAction::Encode(Preset::Remux));
+        assert!(d.reason.ends_with("remuxing into mkv"));
+        // webm can't take h264 without re-encoding it.
+        let d = decide(
+            Path::new("a.mov"),
+            &info("h264", 2_500, 60),
+            20,
+            Container::Webm,
+        );
+        assert_eq!(d.action, AutoAction::Skip);
+
+        // the same bpp would be worth re-encoding as h264, but not as hevc
+        let d = decide(
+            Path::new("a.mp4"),
+            &info("hevc", 4_500, 60),
+            20,
+            Container::Mp4,
+        );
+        assert_eq!(d.action, AutoAction::Skip);
+    }
+
+    #[test]
+    fn inputs_without_video_are_skipped() {
+        let mut i = info("h264", 5_000, 60);
+        i.streams.remove(0);
+        let d = decide(Path::new("a.mp4"), &i, 20, Container::Mp4);
+        assert_eq!(d.action, AutoAction::Skip);
+        assert_eq!(d.label(), "skip");
+    }
+}
@@ -0,0 +1,619 @@
+use std::ffi::OsString;
+
+use crate::exec::caps::Capabilities;
+
+use super::Preset;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Backend {
+    Cpu,
+    Nvenc,
+    Qsv,
+    Amf,
+    Vaapi,
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Codec {
+    #[default]
+    H264,
+    Hevc,
+    Av1,
+}
+
+impl Codec {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Codec::H264 => "h264",
+            Codec::Hevc => "hevc",
+            Codec::Av1 => "av1",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "h264" | "avc" => Some(Codec::H264),
+            "hevc" | "h265" => Some(Codec::Hevc),
+            "av1" => Some(Codec::Av1),
+            _ => None,
+        }
+    }
+}
+
+/// One ffmpeg video encoder: where it runs and what it produces.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Encoder {
+    pub backend: Backend,
+    pub codec: Codec,
+}
+
+/// Hardware backends `gpu`/`auto` try, in order; h264 unless `codec` says otherwise.
+const HARDWARE_ORDER: [Backend; 4] = [Backend::Nvenc, Backend::Qsv, Backend::Amf, Backend::Vaapi];
+
+const VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
+impl Encoder {
+    pub const CPU: Encoder = Encoder {
+        backend: Backend::Cpu,
+        codec: Codec::H264,
+    };
+
+    pub const NVENC: Encoder = Encoder {
+        backend: Backend::Nvenc,
+        codec: Codec::H264,
+    };
+
+    /// The hardware encoders for `codec`, in the order `gpu` and `auto` try them.
+    pub fn hardware(codec: Codec) -> impl Iterator<Item = Encoder> {
+        HARDWARE_ORDER
+            .into_iter()
+            .map(move |backend| Encoder { backend, codec })
+    }
+
+    pub fn is_hardware(self) -> bool {
+        self.backend != Backend::Cpu
+    }
+
+    pub fn ffmpeg_name(self) -> &'static str {
+        match (self.backend, self.codec) {
+            (Backend::Cpu, Codec::H264) => "libx264",
+            (Backend::Cpu, Codec::Hevc) => "libx265",
+            (Backend::Cpu, Codec::Av1) => "libsvtav1",
+            (Backend::Nvenc, Codec::H264) => "h264_nvenc",
+            (Backend::Nvenc, Codec::Hevc) => "hevc_nvenc",
+            (Backend::Nvenc, Codec::Av1) => "av1_nvenc",
+            (Backend::Qsv, Codec::H264) => "h264_qsv",
+            (Backend::Qsv, Codec::Hevc) => "hevc_qsv",
+            (Backend::Qsv, Codec::Av1) => "av1_qsv",
+            (Backend::Amf, Codec::H264) => "h264_amf",
+            (Backend::Amf, Codec::Hevc) => "hevc_amf",
+            (Backend::Amf, Codec::Av1) => "av1_amf",
+            (Backend::Vaapi, Codec::H264) => "h264_vaapi",
+            (Backend::Vaapi, Codec::Hevc) => "hevc_vaapi",
+            (Backend::Vaapi, Codec::Av1) => "av1_vaapi",
+        }
+    }
+
+    /// Accepts ffmpeg names (`hevc_qsv`, `libx265`) and backend shorthands (`qsv` = h264).
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        let backend_only = match s.as_str() {
+            "cpu" | "x264" => Some(Backend::Cpu),
+            "nvenc" => Some(Backend::Nvenc),
+            "qsv" => Some(Backend::Qsv),
+            "amf" => Some(Backend::Amf),
+            "vaapi" => Some(Backend::Vaapi),
+            _ => None,
+        };
+        if let Some(backend) = backend_only {
+            return Some(Encoder {
+                backend,
+                codec: Codec::H264,
+            });
+        }
+
+        let all = [
+            Backend::Cpu,
+            Backend::Nvenc,
+            Backend::Qsv,
+            Backend::Amf,
+            Backend::Vaapi,
+        ];
+        let alias = match s.as_str() {
+            "x265" => "libx265",
+            "svtav1" => "libsvtav1",
+            other => other,
+        };
+        all.into_iter()
+            .flat_map(|backend| {
+                [Codec::H264, Codec::Hevc, Codec::Av1].map(|codec| Encoder { backend, codec })
+            })
+            .find(|e| e.ffmpeg_name() == alias)
+    }
+
+    /// Arguments that have to come before `-i`.
+    pub fn input_args(self) -> Vec<OsString> {
+        match self.backend {
+            Backend::Vaapi => vec![
+                OsString::from("-vaapi_device"),
+                OsString::from(VAAPI_DEVICE),
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Filter that gets frames to the encoder, for encoders that can't take system memory.
+    pub fn video_filter(self) -> Option<&'static str> {
+        match self.backend {
+            Backend::Vaapi => Some("format=nv12,hwupload"),
+            _ => None,
+        }
+    }
+
+    /// Pixel format to ask for; `None` when the filter already uploads frames to the gpu.
+    pub fn pix_fmt(self) -> Option<&'static str> {
+        match self.backend {
+            Backend::Vaapi => None,
+            Backend::Qsv => Some("nv12"),
+            _ => Some("yuv420p"),
+        }
+    }
+
+    /// Whether the matching hardware decoder handles a `codec` stream in `pix_fmt`.
+    ///
+    /// Anything else (4:2:2, 4:4:4, prores, ...) is decoded in software as before.
+    pub fn can_hw_decode(self, codec: &str, pix_fmt: Option<&str>) -> bool {
+        let codecs: &[&str] = match self.backend {
+            Backend::Cpu => return false,
+            Backend::Nvenc => &["h264", "hevc", "av1", "vp8", "vp9", "mpeg2video", "vc1"],
+            Backend::Qsv => &["h264", "hevc", "av1", "vp9", "mpeg2video", "vc1"],
+            Backend::Amf => &["h264", "hevc", "av1", "vp9", "mpeg2video", "vc1"],
+            Backend::Vaapi => &["h264", "hevc", "av1", "vp8", "vp9", "mpeg2video", "vc1"],
+        };
+        let eight_bit = matches!(pix_fmt, Some("yuv420p" | "yuvj420p" | "nv12"));
+        // 10-bit 4:2:0 only decodes in hardware for the newer codecs.
+        let ten_bit = pix_fmt == Some("yuv420p10le") && matches!(codec, "hevc" | "av1" | "vp9");
+        codecs.contains(&codec) && (eight_bit || ten_bit)
+    }
+
+    /// Arguments before `-i` that decode on the gpu; replaces [`Encoder::input_args`].
+    pub fn hw_decode_input_args(self) -> Vec<OsString> {
+        let args: &[&str] = match self.backend {
+            Backend::Cpu => &[],
+            Backend::Nvenc => &["-hwaccel", "cuda", "-hwaccel_output_format", "cuda"],
+            Backend::Qsv => &["-hwaccel", "qsv", "-hwaccel_output_format", "qsv"],
+            // amf reads system memory, so decoded frames are copied back.
+            Backend::Amf => &["-hwaccel", "d3d11va"],
+            Backend::Vaapi => &[
+                "-hwaccel",
+                "vaapi",
+                "-hwaccel_device",
+                VAAPI_DEVICE,
+                "-hwaccel_output_format",
+                "vaapi",
+            ],
+        };
+        args.iter().map(OsString::from).collect()
+    }
+
+    /// On-device format conversion for frames that stay on the gpu after decoding; `None`
+    /// when they come back to system memory and the usual `-pix_fmt` applies.
+    pub fn hw_decode_filter(self) -> Option<&'static str> {
+        match self.backend {
+            Backend::Nvenc => Some("scale_cuda=format=yuv420p"),
+            Backend::Qsv => Some("vpp_qsv=format=nv12"),
+            Backend::Vaapi => Some("scale_vaapi=format=nv12"),
+            Backend::Cpu | Backend::Amf => None,
+        }
+    }
+
+    /// Video encoding arguments for `preset`; `crf` replaces the preset's quality level.
+    pub fn video_args(self, preset: Preset, crf: Option<u8>) -> Vec<OsString> {
+        let mut args = vec![OsString::from("-c:v"), OsString::from(self.ffmpeg_name())];
+        match self.backend {
+            Backend::Cpu => args.extend(cpu_args(self.codec, preset, crf)),
+            Backend::Nvenc => args.extend(nvenc_args(self.codec, preset, crf)),
+            Backend::Qsv => args.extend(qsv_args(self.codec, preset, crf)),
+            Backend::Amf => args.extend(amf_args(preset, crf)),
+            Backend::Vaapi => args.extend(vaapi_args(preset, crf)),
+        }
+        args
+    }
+}
+
+/// What `--encoder` / `encoder` in options.toml asked for, before capabilities are known.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EncoderPref {
+    /// the first working hardware encoder, else cpu.
+    Auto,
+    Cpu,
+    /// the first working hardware encoder, or an error.
+    Gpu,
+    Fixed(Encoder),
+}
+
+impl EncoderPref {
+    pub fn parse(s: &str) -> std::result::Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(EncoderPref::Auto),
+            "gpu" => Ok(EncoderPref::Gpu),
+            // plain `cpu` keeps libx264, same as before encoders were selectable.
+            "cpu" => Ok(EncoderPref::Cpu),
+            other => Encoder::parse(other)
+                .map(EncoderPref::Fixed)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown encoder `{s}`; expected auto, cpu, gpu, nvenc, qsv, amf, vaapi \
+or an ffmpeg encoder name like hevc_qsv"
+                    )
+                }),
+        }
+    }
+
+    /// The same preference with the gpu switch applied, keeping a fixed encoder of the right kind.
+    pub fn with_gpu(self, gpu: bool) -> Self {
+        match self {
+            EncoderPref::Fixed(e) if e.is_hardware() == gpu => self,
+            EncoderPref::Auto if gpu => self,
+            _ if gpu => EncoderPref::Gpu,
+            _ => EncoderPref::Cpu,
+        }
+    }
+
+    /// Picks the encoder to use, or explains why the preferred one can't run here.
+    ///
+    /// `codec` is what `auto`, `cpu` and `gpu` produce; a fixed encoder brings its own.
+    pub fn resolve(
+        self,
+        codec: Codec,
+        caps: &Capabilities,
+    ) -> std::result::Result<Encoder, String> {
+        let first_hardware = || Encoder::hardware(codec).find(|e| caps.can_use(e.ffmpeg_name()));
+        let cpu = Encoder {
+            backend: Backend::Cpu,
+            codec,
+        };
+        let usable = |e: Encoder| match caps.unavailable_reason(e.ffmpeg_name()) {
+            Some(reason) => Err(reason),
+            None => Ok(e),
+        };
+
+        match self {
+            // libx264 is in every build; checking it would only trip over test doubles.
+            EncoderPref::Cpu if codec == Codec::H264 => Ok(Encoder::CPU),
+            EncoderPref::Cpu => usable(cpu),
+            EncoderPref::Auto => first_hardware().map_or_else(|| usable(cpu), Ok),
+            EncoderPref::Gpu => first_hardware().ok_or_else(|| {
+                format!(
+                    "no hardware {} encoder works on this machine (tried nvenc, qsv, amf, vaapi)",
+                    codec.as_str()
+                )
+            }),
+            EncoderPref::Fixed(e) => usable(e),
+        }
+    }
+
+    /// Best guess while capabilities are still being detected.
+    pub fn assume(self, codec: Codec) -> Encoder {
+        match self {
+            EncoderPref::Cpu => Encoder {
+                codec,
+                ..Encoder::CPU
+            },
+            EncoderPref::Auto | EncoderPref::Gpu => Encoder {
+                codec,
+                ..Encoder::NVENC
+            },
+            EncoderPref::Fixed(e) => e,
+        }
+    }
+}
+
+impl std::fmt::Display for EncoderPref {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncoderPref::Auto => f.write_str("auto"),
+            EncoderPref::Cpu => f.write_str("cpu"),
+            EncoderPref::Gpu => f.write_str("gpu"),
+            EncoderPref::Fixed(e) => f.write_str(e.ffmpeg_name()),
+        }
+    }
+}
+
+fn arg_pairs(pairs: &[(&str, String)]) -> Vec<OsString> {
+    pairs
+        .iter()
+        .flat_map(|(k, v)| [OsString::from(k), OsString::from(v)])
+        .collect()
+}
+
+fn cpu_args(codec: Codec, preset: Preset, crf: Option<u8>) -> Vec<OsString> {
+    // remux copies what it can; anything the container can't hold is re-encoded like balanced.
+    // auto is resolved per file before encoding and only lands here as a fallback.
+    let (speed, default_crf) = match (codec, preset) {
+        (Codec::H264, Preset::Quality) => ("slower", 18),
+        (Codec::H264, Preset::Speed) => ("veryfast", 28),
+        (Codec::H264, _) => ("medium", 23),
+        (Codec::Hevc, Preset::Quality) => ("slow", 20),
+        (Codec::Hevc, Preset::Speed) => ("veryfast", 30),
+        (Codec::Hevc, _) => ("medium", 25),
+        (Codec::Av1, Preset::Quality) => ("4", 26),
+        (Codec::Av1, Preset::Speed) => ("10", 38),
+        (Codec::Av1, _) => ("7", 32),
+    };
+    arg_pairs(&[
+        ("-preset", speed.to_string()),
+        ("-crf", crf.unwrap_or(default_crf).to_string()),
+    ])
+}
+
+fn nvenc_args(codec: Codec, preset: Preset, crf: Option<u8>) -> Vec<OsString> {
+    let (nvenc_preset, b_v, maxrate, bufsize, multipass, lookahead, bf) = match preset {
+        Preset::Quality => ("p7", 13, 19, 38, "fullres", "32", "3"),
+        Preset::Speed => ("p4", 4, 6, 12, "disabled", "16", "2"),
+        _ => ("p6", 8, 12, 24, "fullres", "32", "3"),
+    };
+    // hevc and av1 reach the same quality with fewer bits.
+    let scale = |mbps: u32| match codec {
+        Codec::H264 => format!("{mbps}M"),
+        Codec::Hevc => format!("{}k", mbps * 700),
+        Codec::Av1 => format!("{}k", mbps * 600),
+    };
+    // with a cq the average bitrate is left to the encoder; maxrate still caps peaks.
+    let b_v = if crf.is_some() {
+        "0".to_string()
+    } else {
+        scale(b_v)
+    };
+
+    let mut args = Vec::new();
+    match codec {
+        Codec::H264 => args.push(("-profile:v", "high".to_string())),
+        Codec::Hevc => args.push(("-profile:v", "main".to_string())),
+        Codec::Av1 => {}
+    }
+    args.extend([
+        ("-preset", nvenc_preset.to_string()),
+        ("-rc", "vbr".to_string()),
+        ("-tune", "hq".to_string()),
+        ("-multipass", multipass.to_string()),
+        ("-b:v", b_v),
+        ("-maxrate", scale(maxrate)),
+        ("-bufsize", scale(bufsize)),
+        ("-spatial-aq", "1".to_string()),
+        ("-temporal-aq", "1".to_string()),
+        ("-aq-strength", "8".to_string()),
+        ("-rc-lookahead", lookahead.to_string()),
+        ("-bf", bf.to_string()),
+    ]);
+    if codec != Codec::Av1 {
+        args.push(("-b_ref_mode", "middle".to_string()));
+    }
+    if let Some(cq) = crf {
+        args.push(("-cq", cq.to_string()));
+    }
+    arg_pairs(&args)
+}
+
+fn qsv_args(codec: Codec, preset: Preset, crf: Option<u8>) -> Vec<OsString> {
+    let (speed, quality) = match preset {
+        Preset::Quality => ("veryslow", 20),
+        Preset::Speed => ("veryfast", 28),
+        _ => ("medium", 24),
+    };
+    // global_quality without a bitrate selects icq, qsv's constant-quality mode.
+    let mut pairs = vec![
+        ("-preset", speed.to_string()),
+        ("-global_quality", crf.unwrap_or(quality).to_string()),
+    ];
+    // only h264_qsv has look-ahead (la_icq), and the depth only counts with it on.
+    if codec == Codec::H264 {
+        pairs.extend([
+            ("-look_ahead", "1".to_string()),
+            ("-look_ahead_depth", "40".to_string()),
+        ]);
+    }
+    arg_pairs(&pairs)
+}
+
+fn amf_args(preset: Preset, crf: Option<u8>) -> Vec<OsString> {
+    let (quality, qp) = match preset {
+        Preset::Quality => ("quality", 20),
+        Preset::Speed => ("speed", 28),
+        _ => ("balanced", 24),
+    };
+    let qp = crf.unwrap_or(qp);
+    arg_pairs(&[
+        ("-quality", quality.to_string()),
+        ("-rc", "cqp".to_string()),
+        ("-qp_i", qp.to_string()),
+        ("-qp_p", (qp + 2).to_string()),
+    ])
+}
+
+fn vaapi_args(preset: Preset, crf: Option<u8>) -> Vec<OsString> {
+    let (level, qp) = match preset {
+        Preset::Quality => (1, 20),
+        Preset::Speed => (7, 28),
+        _ => (4, 24),
+    };
+    arg_pairs(&[
+        ("-rc_mode", "CQP".to_string()),
+        ("-qp", crf.unwrap_or(qp).to_string()),
+        ("-compression_level", level.to_string()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joined(args: &[OsString]) -> String {
+        args.iter()
+            .map(|v| v.to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn caps(encoders: &[&str], usable: &[&str]) -> Capabilities {
+        Capabilities {
+            encoders: encoders.iter().map(|s| s.to_string()).collect(),
+            usable_hardware: usable.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_backend_shorthands_and_ffmpeg_names() {
+        assert_eq!(Encoder::parse("qsv").unwrap().ffmpeg_name(), "h264_qsv");
+        assert_eq!(
+            Encoder::parse("HEVC_AMF").unwrap().ffmpeg_name(),
+            "hevc_amf"
+        );
+        assert_eq!(Encoder::parse("x265").unwrap().ffmpeg_name(), "libx265");
+        assert_eq!(Encoder::parse("av1_vaapi").unwrap().codec, Codec::Av1);
+        assert!(Encoder::parse("h264_v4l2m2m").is_none());
+
+        assert_eq!(EncoderPref::parse("auto"), Ok(EncoderPref::Auto));
+        assert_eq!(EncoderPref::parse("cpu"), Ok(EncoderPref::Cpu));
+        assert_eq!(
+            EncoderPref::parse("hevc_nvenc").unwrap().to_string(),
+            "hevc_nvenc"
+        );
+        assert!(EncoderPref::parse("quantum").is_err());
+    }
+
+    #[test]
+    fn builds_per_backend_arguments_without_hardware() {
+        let qsv = Encoder::parse("hevc_qsv").unwrap();
+        let s = joined(&qsv.video_args(Preset::Quality, None));
+        assert_eq!(s, "-c:v hevc_qsv -preset veryslow -global_quality 20");
+        let s = joined(
+            &Encoder::parse("qsv")
+                .unwrap()
+                .video_args(Preset::Speed, None),
+        );
+        assert_eq!(
+            s,
+            "-c:v h264_qsv -preset veryfast -global_quality 28 -look_ahead 1 -look_ahead_depth 40"
+        );
+        assert_eq!(qsv.pix_fmt(), Some("nv12"));
+
+        let amf = Encoder::parse("amf").unwrap();
+        let s = joined(&amf.video_args(Preset::Speed, Some(30)));
+        assert_eq!(s, "-c:v h264_amf -quality speed -rc cqp -qp_i 30 -qp_p 32");
+
+        let vaapi = Encoder::parse("vaapi").unwrap();
+        assert_eq!(
+            joined(&vaapi.input_args()),
+            "-vaapi_device /dev/dri/renderD128"
+        );
+        assert_eq!(vaapi.video_filter(), Some("format=nv12,hwupload"));
+        assert_eq!(vaapi.pix_fmt(), None);
+        assert!(joined(&vaapi.video_args(Preset::Balanced, None)).contains("-qp 24"));
+
+        let nvenc_av1 = Encoder::parse("av1_nvenc").unwrap();
+        let s = joined(&nvenc_av1.video_args(Preset::Balanced, None));
+        assert!(s.contains("-b:v 4800k"));
+        assert!(!s.contains("-profile:v"));
+        assert!(!s.contains("b_ref_mode"));
+
+        let x264 = joined(&Encoder::CPU.video_args(Preset::Balanced, None));
+        assert_eq!(x264, "-c:v libx264 -preset medium -crf 23");
+    }
+
+    #[test]
+    fn hardware_decode_covers_common_420_sources_only() {
+        let nvenc = Encoder::NVENC;
+        assert!(nvenc.can_hw_decode("h264", Some("yuv420p")));
+        assert!(nvenc.can_hw_decode("hevc", Some("yuv420p10le")));
+        assert!(!nvenc.can_hw_decode("h264", Some("yuv420p10le")));
+        assert!(!nvenc.can_hw_decode("h264", Some("yuv422p")));
+        assert!(!nvenc.can_hw_decode("prores", Some("yuv420p")));
+        assert!(!nvenc.can_hw_decode("h264", None));
+        assert!(!Encoder::CPU.can_hw_decode("h264", Some("yuv420p")));
+
+        assert_eq!(
+            joined(&nvenc.hw_decode_input_args()),
+            "-hwaccel cuda -hwaccel_output_format cuda"
+        );
+        let amf = Encoder::parse("amf").unwrap();
+        assert_eq!(joined(&amf.hw_decode_input_args()), "-hwaccel d3d11va");
+        assert_eq!(amf.hw_decode_filter(), None);
+    }
+
+    #[test]
+    fn resolves_preferences_against_capabilities() {
+        let laptop = caps(
+            &["libx264", "h264_nvenc", "h264_qsv", "h264_amf"],
+            &["h264_qsv"],
+        );
+        assert_eq!(
+            EncoderPref::Auto
+                .resolve(Codec::H264, &laptop)
+                .unwrap()
+                .ffmpeg_name(),
+            "h264_qsv"
+        );
+        assert_eq!(
+            EncoderPref::Gpu
+                .resolve(Codec::H264, &laptop)
+                .unwrap()
+                .ffmpeg_name(),
+            "h264_qsv"
+        );
+        assert!(
+            EncoderPref::parse("nvenc")
+                .unwrap()
+                .resolve(Codec::H264, &laptop)
+                .is_err()
+        );
+
+        let desktop = caps(&["libx264"], &[]);
+        assert_eq!(
+            EncoderPref::Auto.resolve(Codec::H264, &desktop),
+            Ok(Encoder::CPU)
+        );
+        assert!(EncoderPref::Gpu.resolve(Codec::H264, &desktop).is_err());
+    }
+
+    #[test]
+    fn codec_picks_the_matching_encoder_for_each_preference() {
+        let laptop = caps(
+            &["libx264", "libx265", "h264_qsv", "hevc_qsv", "av1_qsv"],
+            &["h264_qsv", "hevc_qsv"],
+        );
+        let name = |pref: EncoderPref, codec| pref.resolve(codec, &laptop).map(|e| e.ffmpeg_name());
+        assert_eq!(name(EncoderPref::Gpu, Codec::Hevc), Ok("hevc_qsv"));
+        assert_eq!(name(EncoderPref::Cpu, Codec::Hevc), Ok("libx265"));
+        // av1_qsv is listed but failed its test encode, and there's no libsvtav1 to fall back on.
+        assert!(name(EncoderPref::Gpu, Codec::Av1).is_err());
+        assert_eq!(
+            name(EncoderPref::Auto, Codec::Av1),
+            Err("libsvtav1 is not included in this ffmpeg build".to_string())
+        );
+        // a fixed encoder ignores the codec option.
+        assert_eq!(
+            name(EncoderPref::parse("h264_qsv").unwrap(), Codec::Av1),
+            Ok("h264_qsv")
+        );
+        assert_eq!(Codec::parse("H265"), Some(Codec::Hevc));
+        assert_eq!(
+            EncoderPref::Cpu.assume(Codec::Av1).ffmpeg_name(),
+            "libsvtav1"
+        );
+    }
+
+    #[test]
+    fn gpu_switch_keeps_fixed_encoders_of_the_right_kind() {
+        let qsv = EncoderPref::parse("hevc_qsv").unwrap();
+        assert_eq!(qsv.with_gpu(true), qsv);
+        assert_eq!(qsv.with_gpu(false), EncoderPref::Cpu);
+
+        let x265 = EncoderPref::parse("libx265").unwrap();
+        assert_eq!(x265.with_gpu(false), x265);
+        assert_eq!(x265.with_gpu(true), EncoderPref::Gpu);
+        assert_eq!(EncoderPref::Auto.with_gpu(true), EncoderPref::Auto);
+        assert_eq!(EncoderPref::Auto.with_gpu(false), EncoderPref::Cpu);
+    }
+}
@@ -1,10 +1,17 @@
-use std::ffi::OsString;
+pub mod auto;
+pub mod encoder;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Preset {
     Quality,
     Balanced,
     Speed,
+    Remux,
+    Auto,
+    /// an animated gif instead of a video.
+    Gif,
+    /// an animated webp instead of a video.
+    Webp,
 }
 
 impl Preset {
@@ -13,84 +20,39 @@ impl Preset {
             Preset::Quality => "quality",
             Preset::Balanced => "balanced",
             Preset::Speed => "speed",
+            Preset::Remux => "remux",
+            Preset::Auto => "auto",
+            Preset::Gif => "gif",
+            Preset::Webp => "webp",
         }
     }
-}
 
-pub fn ffmpeg_video_args(preset: Preset, use_gpu: bool) -> Vec<OsString> {
-    if !use_gpu {
-        return match preset {
-            Preset::Quality => vec![
-                OsString::from("-c:v"),
-                OsString::from("libx264"),
-                OsString::from("-preset"),
-                OsString::from("slower"),
-                OsString::from("-crf"),
-                OsString::from("18"),
-            ],
-            Preset::Balanced => vec![
-                OsString::from("-c:v"),
-                OsString::from("libx264"),
-                OsString::from("-preset"),
-                OsString::from("medium"),
-                OsString::from("-crf"),
-                OsString::from("23"),
-            ],
-            Preset::Speed => vec![
-                OsString::from("-c:v"),
-                OsString::from("libx264"),
-                OsString::from("-preset"),
-                OsString::from("veryfast"),
-                OsString::from("-crf"),
-                OsString::from("28"),
-            ],
-        };
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "quality" => Some(Preset::Quality),
+            "balanced" => Some(Preset::Balanced),
+            "speed" => Some(Preset::Speed),
+            "remux" => Some(Preset::Remux),
+            "auto" => Some(Preset::Auto),
+            "gif" => Some(Preset::Gif),
+            "webp" => Some(Preset::Webp),
+            _ => None,
+        }
     }
 
-    let (nvenc_preset, b_v, maxrate, bufsize, multipass, lookahead, bf) = match preset {
-        Preset::Quality => ("p7", "13M", "19M", "38M", "fullres", "32", "3"),
-        Preset::Balanced => ("p6", "8M", "12M", "24M", "fullres", "32", "3"),
-        Preset::Speed => ("p4", "4M", "6M", "12M", "disabled", "16", "2"),
-    };
-
-    vec![
-        OsString::from("-c:v"),
-        OsString::from("h264_nvenc"),
-        OsString::from("-profile:v"),
-        OsString::from("high"),
-        OsString::from("-preset"),
-        OsString::from(nvenc_preset),
-        OsString::from("-rc"),
-        OsString::from("vbr"),
-        OsString::from("-tune"),
-        OsString::from("hq"),
-        OsString::from("-multipass"),
-        OsString::from(multipass),
-        OsString::from("-b:v"),
-        OsString::from(b_v),
-        OsString::from("-maxrate"),
-        OsString::from(maxrate),
-        OsString::from("-bufsize"),
-        OsString::from(bufsize),
-        OsString::from("-spatial-aq"),
-        OsString::from("1"),
-        OsString::from("-temporal-aq"),
-        OsString::from("1"),
-        OsString::from("-aq-strength"),
-        OsString::from("8"),
-        OsString::from("-rc-lookahead"),
-        OsString::from(lookahead),
-        OsString::from("-bf"),
-        OsString::from(bf),
-        OsString::from("-b_ref_mode"),
-        OsString::from("middle"),
-    ]
+    /// Whether outputs are animations (see `exec::gif`) rather than videos.
+    pub fn is_animated(self) -> bool {
+        matches!(self, Preset::Gif | Preset::Webp)
+    }
 }
 
+/// CRF/CQ values a `--target-vmaf` search tries, best quality first.
+pub const SEARCH_CRFS: [u8; 5] = [16, 21, 26, 31, 36];
+
 pub fn audio_bitrate(preset: Preset) -> &'static str {
     match preset {
         Preset::Quality => "160k",
-        Preset::Balanced => "128k",
+        Preset::Balanced | Preset::Remux | Preset::Auto | Preset::Gif | Preset::Webp => "128k",
         Preset::Speed => "96k",
     }
 }
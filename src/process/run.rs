@@ -1,11 +1,78 @@
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::path::Path;
 use std::process::Command;
 
 use crate::error::{Result, TinythisError};
 
+/// A `Command` running `program` with `args`, logging its command line.
+pub fn command<S: AsRef<OsStr>>(program: &Path, args: &[S]) -> Command {
+    log::debug!("run: {}", command_line(program, args));
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    cmd
+}
+
+/// `program` and `args` as one line that pastes back into a shell: cmd/powershell quoting on
+/// windows, sh quoting elsewhere.
+pub fn command_line<S: AsRef<OsStr>>(program: &Path, args: &[S]) -> String {
+    let quote = if cfg!(windows) {
+        quote_windows
+    } else {
+        quote_sh
+    };
+    std::iter::once(program.as_os_str())
+        .chain(args.iter().map(|a| a.as_ref()))
+        .map(|a| quote(&a.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether `arg` holds anything but letters, digits and the `safe` punctuation.
+fn needs_quotes(arg: &str, safe: &str) -> bool {
+    arg.is_empty() || !arg.chars().all(|c| c.is_alphanumeric() || safe.contains(c))
+}
+
+/// Double quotes, escaped the way windows programs split their command line.
+fn quote_windows(arg: &str) -> String {
+    // powershell splits unquoted commas into arrays.
+    if !needs_quotes(arg, "-_./\\:=+") {
+        return arg.to_string();
+    }
+    let mut out = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // backslashes before a quote escape each other, then one escapes the quote.
+                out.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                out.extend(std::iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            out.push(c);
+        }
+    }
+    // the closing quote would otherwise be escaped by trailing backslashes.
+    out.extend(std::iter::repeat_n('\\', backslashes * 2));
+    out.push('"');
+    out
+}
+
+/// Single quotes, with any single quote spelled `'\''`.
+fn quote_sh(arg: &str) -> String {
+    if !needs_quotes(arg, "-_.,/:=+@%") {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
 pub fn run_capture(program: &Path, args: &[OsString]) -> Result<std::process::Output> {
-    let output = Command::new(program).args(args).output()?;
+    let output = command(program, args).output()?;
     if output.status.success() {
         return Ok(output);
     }
@@ -17,3 +84,30 @@ pub fn run_capture(program: &Path, args: &[OsString]) -> Result<std::process::Ou
         stderr,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_only_what_a_shell_would_split() {
+        assert_eq!(quote_windows("-crf"), "-crf");
+        assert_eq!(quote_windows("C:\\clips\\a.mp4"), "C:\\clips\\a.mp4");
+        assert_eq!(quote_windows("my clip.mp4"), "\"my clip.mp4\"");
+        assert_eq!(
+            quote_windows("scale=-2:720,format=yuv420p"),
+            "\"scale=-2:720,format=yuv420p\""
+        );
+        assert_eq!(quote_windows("C:\\my dir\\"), "\"C:\\my dir\\\\\"");
+        assert_eq!(quote_windows("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote_windows(""), "\"\"");
+
+        assert_eq!(
+            quote_sh("scale=-2:720,format=yuv420p"),
+            "scale=-2:720,format=yuv420p"
+        );
+        assert_eq!(quote_sh("my clip.mp4"), "'my clip.mp4'");
+        assert_eq!(quote_sh("it's"), "'it'\\''s'");
+        assert_eq!(quote_sh("[0:v]"), "'[0:v]'");
+    }
+}
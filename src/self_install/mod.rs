@@ -199,6 +199,7 @@ mod windows_path {
         }
 
         entries.push(bin_dir.to_string_lossy().to_string());
+        log::info!("adding {} to the user path", bin_dir.display());
         write_user_path_entries(&entries, value_type)?;
         broadcast_env_change();
         Ok(true)
@@ -228,6 +229,7 @@ mod windows_path {
             return Ok(false);
         }
 
+        log::info!("removing {} from the user path", bin_dir.display());
         write_user_path_entries(&out, value_type)?;
         broadcast_env_change();
         Ok(true)
@@ -1,19 +1,57 @@
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
 
 use crossterm::event::KeyEvent;
 
 use crate::assets::ffmpeg::{FfmpegBinaries, FfmpegSource};
-use crate::exec::compress::SelectedFile;
+use crate::exec::caps::Capabilities;
+use crate::exec::compress::{
+    EncodeSettings, FileOverrides, HwDecode, OutputNaming, Preserve, SelectedFile,
+};
+use crate::exec::container::Container;
+use crate::exec::extra_args::{ExtraArgs, PresetArgs};
+use crate::exec::gif::GifSettings;
+use crate::exec::metrics::QualityThreshold;
+use crate::exec::probe::MediaInfo;
+use crate::exec::progress::EncodeProgress;
+use crate::exec::queue::Queue;
+use crate::exec::report::FileReport;
+use crate::options::Options;
 use crate::presets::Preset;
+use crate::presets::auto::AutoDecision;
+use crate::presets::encoder::{Codec, Encoder, EncoderPref};
 use crate::update::UpdateInfo;
 
+use super::browser::{BrowserAction, BrowserState};
+use super::logview::LogViewState;
+use super::settings::{Field, SettingsState};
+
 #[derive(Debug)]
 pub struct App {
     should_quit: bool,
     screen: Screen,
     preset: Preset,
     use_gpu: bool,
+    encoder_option: Option<EncoderPref>,
+    codec: Codec,
+    container: Container,
+    preserve: Preserve,
+    min_savings: u8,
+    measure: bool,
+    min_quality: Option<QualityThreshold>,
+    cpu_fallback: bool,
+    hw_decode: HwDecode,
+    naming: OutputNaming,
+    /// files encoded at once.
+    jobs: u8,
+    gif: GifSettings,
+    /// per-preset ffmpeg options from options.toml; not editable in the tui.
+    extra_args: PresetArgs,
+    /// ffmpeg args a resumed cli batch was started with; they go with its files.
+    batch_args: ExtraArgs,
+    update_check: bool,
+    /// probe added files without a known video extension.
+    sniff_inputs: bool,
     files: Vec<SelectedFile>,
     review_selected: Option<usize>,
     seen: std::collections::HashSet<String>,
@@ -21,33 +59,87 @@ pub struct App {
 
     progress: Option<Progress>,
     worker_rx: Option<Receiver<WorkerMsg>>,
+    reports: Vec<FileReport>,
     error: Option<String>,
+    /// ffmpeg's full output for the file being (or last) encoded.
+    job_log: Option<PathBuf>,
+    /// files encoding right now, in the order they started; the newest is on screen.
+    running: Vec<RunningFile>,
+
+    probe_queue: Vec<PathBuf>,
+    /// files without a known extension, waiting for their probe to find a video in them.
+    sniffing: Vec<SelectedFile>,
+    probe_tx: Sender<ProbeMsg>,
+    probe_rx: Receiver<ProbeMsg>,
+
+    gpu_unavailable: Option<String>,
+    caps: Option<Capabilities>,
+    caps_rx: Option<Receiver<Capabilities>>,
 
     update: Option<UpdateInfo>,
     update_rx: Option<Receiver<UpdateMsg>>,
     update_prompt_from: Screen,
 
+    resume: Option<Queue>,
+
+    settings: Option<SettingsState>,
+    browser: Option<BrowserState>,
+    log: Option<LogViewState>,
+    command_preview: Option<String>,
+
     ffmpeg: Option<FfmpegBinaries>,
     ffmpeg_source: Option<FfmpegSource>,
 }
 
 impl App {
     pub fn new() -> Self {
+        let (probe_tx, probe_rx) = std::sync::mpsc::channel::<ProbeMsg>();
         Self {
             should_quit: false,
             screen: Screen::Landing,
             preset: Preset::Balanced,
             use_gpu: false,
+            encoder_option: None,
+            codec: Codec::default(),
+            container: Container::default(),
+            preserve: Preserve::default(),
+            min_savings: 20,
+            measure: false,
+            min_quality: None,
+            cpu_fallback: false,
+            hw_decode: HwDecode::default(),
+            naming: OutputNaming::default(),
+            jobs: 1,
+            gif: GifSettings::default(),
+            extra_args: PresetArgs::default(),
+            batch_args: ExtraArgs::default(),
+            update_check: true,
+            sniff_inputs: true,
             files: Vec::new(),
             review_selected: None,
             seen: std::collections::HashSet::new(),
             status: None,
             progress: None,
             worker_rx: None,
+            reports: Vec::new(),
             error: None,
+            job_log: None,
+            running: Vec::new(),
+            probe_queue: Vec::new(),
+            sniffing: Vec::new(),
+            probe_tx,
+            probe_rx,
+            gpu_unavailable: None,
+            caps: None,
+            caps_rx: None,
             update: None,
             update_rx: None,
             update_prompt_from: Screen::Landing,
+            resume: None,
+            settings: None,
+            browser: None,
+            log: None,
+            command_preview: None,
             ffmpeg: None,
             ffmpeg_source: None,
         }
@@ -65,10 +157,57 @@ impl App {
         self.preset
     }
 
+    pub fn set_preset(&mut self, preset: Preset) {
+        self.preset = preset;
+    }
+
     pub fn use_gpu(&self) -> bool {
         self.use_gpu
     }
 
+    /// Takes on everything options.toml says except the preset, which only starts the session.
+    pub fn apply_options(&mut self, o: &Options) {
+        self.set_use_gpu(o.gpu);
+        self.set_encoder_option(o.encoder);
+        self.codec = o.codec;
+        self.container = o.container;
+        self.set_preserve(o.preserve);
+        self.set_min_savings(o.auto_min_savings);
+        self.set_measure(o.measure, o.min_quality);
+        self.set_cpu_fallback(o.cpu_fallback);
+        self.set_hw_decode(o.hw_decode);
+        self.naming = o.output.clone();
+        self.jobs = o.jobs;
+        self.gif = o.gif;
+        self.extra_args = o.extra_args.clone();
+        self.update_check = o.update_check;
+        self.sniff_inputs = o.sniff_inputs;
+        if let Some(caps) = self.caps.clone() {
+            self.on_caps(&caps);
+        }
+    }
+
+    pub fn naming(&self) -> &OutputNaming {
+        &self.naming
+    }
+
+    /// options.toml's per-preset ffmpeg args, then a resumed batch's.
+    pub fn extra_args(&self) -> PresetArgs {
+        self.extra_args.then_all(&self.batch_args)
+    }
+
+    pub fn batch_args(&self) -> &ExtraArgs {
+        &self.batch_args
+    }
+
+    pub fn jobs(&self) -> u8 {
+        self.jobs
+    }
+
+    pub fn update_check(&self) -> bool {
+        self.update_check
+    }
+
     pub fn set_use_gpu(&mut self, v: bool) {
         self.use_gpu = v;
     }
@@ -78,6 +217,86 @@ impl App {
         self.use_gpu
     }
 
+    /// The `encoder` option from options.toml; `g` still switches between its gpu and cpu side.
+    pub fn set_encoder_option(&mut self, v: Option<EncoderPref>) {
+        self.encoder_option = v;
+    }
+
+    fn encoder_pref(&self, gpu: bool) -> EncoderPref {
+        self.encoder_option
+            .unwrap_or(EncoderPref::Gpu)
+            .with_gpu(gpu)
+    }
+
+    /// The encoder the next run uses; a guess until capabilities have been detected.
+    pub fn encoder(&self) -> Encoder {
+        let pref = self.encoder_pref(self.use_gpu);
+        match &self.caps {
+            Some(caps) => pref.resolve(self.codec, caps).unwrap_or(Encoder::CPU),
+            None => pref.assume(self.codec),
+        }
+    }
+
+    pub fn set_preserve(&mut self, preserve: Preserve) {
+        self.preserve = preserve;
+    }
+
+    pub fn set_min_savings(&mut self, v: u8) {
+        self.min_savings = v;
+    }
+
+    /// Scoring after each encode; a threshold turns it on by itself.
+    pub fn set_measure(&mut self, measure: bool, min_quality: Option<QualityThreshold>) {
+        self.measure = measure || min_quality.is_some();
+        self.min_quality = min_quality;
+    }
+
+    pub fn set_cpu_fallback(&mut self, v: bool) {
+        self.cpu_fallback = v;
+    }
+
+    pub fn set_hw_decode(&mut self, v: HwDecode) {
+        self.hw_decode = v;
+    }
+
+    pub fn encode_settings(&self) -> EncodeSettings {
+        EncodeSettings {
+            preset: self.preset,
+            encoder: self.encoder(),
+            container: self.container,
+            preserve: self.preserve,
+            min_savings: self.min_savings,
+            target_vmaf: None,
+            crf: None,
+            measure: self.measure,
+            min_quality: self.min_quality,
+            cpu_fallback: self.cpu_fallback,
+            hw_decode: self.hw_decode,
+            trim_secs: None,
+            max_height: None,
+            gif: self.gif,
+            split: None,
+        }
+    }
+
+    /// What the auto preset would do with `file`, once it has been probed.
+    pub fn auto_decision(&self, file: &SelectedFile) -> Option<AutoDecision> {
+        if file.overrides.preset.unwrap_or(self.preset) != Preset::Auto {
+            return None;
+        }
+        let info = file.info.as_ref()?;
+        Some(crate::presets::auto::decide(
+            &file.path,
+            info,
+            self.min_savings,
+            self.container,
+        ))
+    }
+
+    pub fn reports(&self) -> &[FileReport] {
+        &self.reports
+    }
+
     pub fn files(&self) -> &[SelectedFile] {
         &self.files
     }
@@ -139,30 +358,46 @@ impl App {
 
     pub fn clear_files(&mut self) {
         self.files.clear();
+        self.sniffing.clear();
         self.seen.clear();
         self.review_selected = None;
+        self.batch_args = ExtraArgs::default();
     }
 
     pub fn next_preset(&mut self) {
         self.preset = match self.preset {
             Preset::Quality => Preset::Balanced,
             Preset::Balanced => Preset::Speed,
-            Preset::Speed => Preset::Quality,
+            Preset::Speed => Preset::Remux,
+            Preset::Remux => Preset::Auto,
+            Preset::Auto => Preset::Gif,
+            Preset::Gif => Preset::Webp,
+            Preset::Webp => Preset::Quality,
         };
     }
 
     pub fn prev_preset(&mut self) {
         self.preset = match self.preset {
-            Preset::Quality => Preset::Speed,
+            Preset::Quality => Preset::Webp,
             Preset::Balanced => Preset::Quality,
             Preset::Speed => Preset::Balanced,
+            Preset::Remux => Preset::Speed,
+            Preset::Auto => Preset::Remux,
+            Preset::Gif => Preset::Auto,
+            Preset::Webp => Preset::Gif,
         };
     }
 
+    /// Whether files without a known video extension get probed for one (needs ffmpeg).
+    fn sniffs(&self) -> bool {
+        self.sniff_inputs && self.ffmpeg.is_some()
+    }
+
     pub fn add_paths(&mut self, paths: Vec<PathBuf>) {
         let mut added = 0u32;
         let mut ignored_unsupported = 0u32;
         let mut ignored_invalid = 0u32;
+        let mut checking = 0u32;
 
         let prev_screen = self.screen;
         for p in paths {
@@ -183,20 +418,31 @@ impl App {
                 continue;
             }
 
-            if !is_supported_extension(&p) {
+            let known = crate::exec::input::is_supported_video(&p);
+            if !known && !self.sniffs() {
                 ignored_unsupported += 1;
                 continue;
             }
 
             self.seen.insert(key);
-            self.files.push(SelectedFile {
+            self.probe_queue.push(p.clone());
+            let file = SelectedFile {
                 path: p,
                 size_bytes: meta.len(),
-            });
-            added += 1;
+                info: None,
+                overrides: FileOverrides::default(),
+            };
+            // the rest is added by `drain_probes` once its probe finds a video.
+            if known {
+                self.files.push(file);
+                added += 1;
+            } else {
+                self.sniffing.push(file);
+                checking += 1;
+            }
         }
 
-        if added == 0 && ignored_invalid == 0 && ignored_unsupported == 0 {
+        if added == 0 && ignored_invalid == 0 && ignored_unsupported == 0 && checking == 0 {
             self.status = Some("no files".to_string());
             return;
         }
@@ -221,6 +467,9 @@ impl App {
         if ignored_invalid > 0 {
             parts.push(format!("ignored {ignored_invalid} invalid"));
         }
+        if checking > 0 {
+            parts.push(format!("checking {checking} for video"));
+        }
         self.status = Some(parts.join(", "));
 
         if added > 0 {
@@ -278,14 +527,148 @@ impl App {
         self.review_selected = Some(next);
     }
 
+    pub fn take_probe_queue(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.probe_queue)
+    }
+
+    pub fn probe_sender(&self) -> Sender<ProbeMsg> {
+        self.probe_tx.clone()
+    }
+
+    pub fn drain_probes(&mut self) {
+        let mut sniffed = 0u32;
+        let mut rejected = 0u32;
+        while let Ok(msg) = self.probe_rx.try_recv() {
+            if let Some(b) = self.browser.as_mut() {
+                b.set_duration(&msg.path, msg.info.as_ref().and_then(|i| i.duration_us));
+            }
+            let key = normalize_key(&msg.path);
+            if let Some(i) = self
+                .sniffing
+                .iter()
+                .position(|f| normalize_key(&f.path) == key)
+            {
+                let mut file = self.sniffing.remove(i);
+                if msg.info.as_ref().is_some_and(crate::exec::input::has_video) {
+                    file.info = msg.info;
+                    self.files.push(file);
+                    sniffed += 1;
+                } else {
+                    self.seen.remove(&key);
+                    rejected += 1;
+                }
+                continue;
+            }
+            if let Some(f) = self
+                .files
+                .iter_mut()
+                .find(|f| normalize_key(&f.path) == key)
+            {
+                f.info = msg.info;
+            }
+        }
+
+        if sniffed == 0 && rejected == 0 {
+            return;
+        }
+        let mut parts = Vec::<String>::new();
+        if sniffed > 0 {
+            parts.push(format!(
+                "added {sniffed} file{}",
+                if sniffed == 1 { "" } else { "s" }
+            ));
+            if self.screen == Screen::Landing {
+                self.review_selected = None;
+                self.screen = Screen::Review;
+            }
+        }
+        if rejected > 0 {
+            parts.push(format!("ignored {rejected} unsupported"));
+        }
+        self.status = Some(parts.join(", "));
+    }
+
+    fn selected_overrides(&mut self) -> Option<&mut FileOverrides> {
+        let Some(file) = self.review_selected.and_then(|i| self.files.get_mut(i)) else {
+            self.status = Some("select a file first (up/down)".to_string());
+            return None;
+        };
+        Some(&mut file.overrides)
+    }
+
+    pub fn cycle_file_preset(&mut self) {
+        const PRESETS: [Option<Preset>; 8] = [
+            None,
+            Some(Preset::Quality),
+            Some(Preset::Balanced),
+            Some(Preset::Speed),
+            Some(Preset::Remux),
+            Some(Preset::Auto),
+            Some(Preset::Gif),
+            Some(Preset::Webp),
+        ];
+        if let Some(o) = self.selected_overrides() {
+            o.preset = next_option(&PRESETS, o.preset);
+        }
+    }
+
+    /// Cycles through the cpu and every hardware encoder known to work here.
+    pub fn cycle_file_encoder(&mut self) {
+        let mut encoders = vec![None, Some(Encoder::CPU)];
+        if let Some(caps) = &self.caps {
+            encoders.extend(
+                Encoder::hardware(self.codec)
+                    .filter(|e| caps.can_use(e.ffmpeg_name()))
+                    .map(Some),
+            );
+        }
+        if let Some(o) = self.selected_overrides() {
+            o.encoder = next_option(&encoders, o.encoder);
+        }
+    }
+
+    pub fn cycle_file_trim(&mut self) {
+        const TRIMS: [Option<u32>; 5] = [None, Some(15), Some(30), Some(60), Some(300)];
+        if let Some(o) = self.selected_overrides() {
+            o.trim_secs = next_option(&TRIMS, o.trim_secs);
+        }
+    }
+
+    pub fn cycle_file_resolution(&mut self) {
+        const HEIGHTS: [Option<u32>; 4] = [None, Some(1080), Some(720), Some(480)];
+        if let Some(o) = self.selected_overrides() {
+            o.max_height = next_option(&HEIGHTS, o.max_height);
+        }
+    }
+
+    /// Copies the selected file's overrides onto every file.
+    pub fn apply_overrides_to_all(&mut self) {
+        let Some(overrides) = self.selected_overrides().copied() else {
+            return;
+        };
+        for f in &mut self.files {
+            f.overrides = overrides;
+        }
+        let n = self.files.len();
+        self.status = Some(format!(
+            "applied to {n} file{}",
+            if n == 1 { "" } else { "s" }
+        ));
+    }
+
     pub fn set_worker(&mut self, rx: Receiver<WorkerMsg>, total: usize) {
         self.worker_rx = Some(rx);
+        self.reports.clear();
+        self.job_log = None;
+        self.running.clear();
         self.progress = Some(Progress {
             idx: 0,
             total,
             current_name: String::new(),
+            others: 0,
             spinner_tick: 0,
-            percent: None,
+            stats: None,
+            batch_eta_secs: None,
         });
         self.screen = Screen::Compressing;
     }
@@ -297,16 +680,13 @@ impl App {
     }
 
     pub fn drain_worker(&mut self) {
-        loop {
-            let Some(rx) = self.worker_rx.as_ref() else {
-                break;
-            };
+        while let Some(rx) = self.worker_rx.as_ref() {
             match rx.try_recv() {
                 Ok(msg) => self.on_worker_msg(msg),
                 Err(TryRecvError::Empty) => break,
                 Err(TryRecvError::Disconnected) => {
                     self.error = Some("worker disconnected".to_string());
-                    self.screen = Screen::Error;
+                    self.finish(Screen::Error);
                     self.worker_rx = None;
                     break;
                 }
@@ -317,42 +697,138 @@ impl App {
     fn on_worker_msg(&mut self, msg: WorkerMsg) {
         match msg {
             WorkerMsg::Started { idx, total, name } => {
+                self.job_log = None;
+                self.running.push(RunningFile {
+                    idx,
+                    name: name.clone(),
+                    log: None,
+                });
+                let prev = self.progress.as_ref();
                 self.progress = Some(Progress {
                     idx,
                     total,
                     current_name: name,
-                    spinner_tick: self.progress.as_ref().map(|p| p.spinner_tick).unwrap_or(0),
-                    percent: None,
+                    others: self.running.len() - 1,
+                    spinner_tick: prev.map(|p| p.spinner_tick).unwrap_or(0),
+                    stats: None,
+                    // the last estimate holds until the next file reports a speed.
+                    batch_eta_secs: prev.and_then(|p| p.batch_eta_secs),
                 });
             }
-            WorkerMsg::Progress { percent } => {
+            WorkerMsg::Progress {
+                idx,
+                stats,
+                batch_eta_secs,
+            } => {
                 if let Some(p) = self.progress.as_mut() {
-                    p.percent = Some(percent);
+                    // files encoding alongside only show up once they're on screen.
+                    if p.idx == idx {
+                        p.stats = Some(stats);
+                    }
+                    p.batch_eta_secs = batch_eta_secs.or(p.batch_eta_secs);
+                }
+            }
+            WorkerMsg::Log { idx, path } => {
+                if let Some(r) = self.running.iter_mut().find(|r| r.idx == idx) {
+                    r.log = Some(path.clone());
+                }
+                if self.progress.as_ref().is_none_or(|p| p.idx == idx) {
+                    self.job_log = Some(path);
                 }
             }
             WorkerMsg::Error { message } => {
                 self.error = Some(message);
-                self.screen = Screen::Error;
+                self.finish(Screen::Error);
                 self.worker_rx = None;
             }
+            WorkerMsg::FileDone { idx, report } => {
+                self.reports.push(report);
+                self.running.retain(|r| r.idx != idx);
+                // with several files at once, the newest one still encoding takes the screen.
+                if let Some(p) = self.progress.as_mut() {
+                    p.others = self.running.len().saturating_sub(1);
+                    if p.idx == idx
+                        && let Some(next) = self.running.last()
+                    {
+                        p.idx = next.idx;
+                        p.current_name = next.name.clone();
+                        p.stats = None;
+                        self.job_log = next.log.clone();
+                    }
+                }
+            }
             WorkerMsg::Done => {
                 let n = self.files.len();
-                self.status = Some(format!("done: {n} file{}", if n == 1 { "" } else { "s" }));
-                self.screen = Screen::Done;
+                let skipped = self.reports.iter().filter(|r| r.is_skipped()).count();
+                let mut status = format!("done: {n} file{}", if n == 1 { "" } else { "s" });
+                if skipped > 0 {
+                    status.push_str(&format!(" ({skipped} skipped)"));
+                }
+                let fell_back = self.reports.iter().filter(|r| r.fallback.is_some()).count();
+                if fell_back > 0 {
+                    status.push_str(&format!(", {fell_back} on cpu after the gpu failed"));
+                }
+                self.status = Some(status);
+                self.finish(Screen::Done);
                 self.worker_rx = None;
             }
         }
     }
 
+    /// Shows where the batch ended up, without pulling the user out of the log they're reading.
+    fn finish(&mut self, screen: Screen) {
+        match self.log.as_mut() {
+            Some(log) if self.screen == Screen::Log => log.from = screen,
+            _ => self.screen = screen,
+        }
+    }
+
+    /// Why the gpu encoder can't be used here, once capability detection has finished.
+    pub fn gpu_unavailable(&self) -> Option<&str> {
+        self.gpu_unavailable.as_deref()
+    }
+
+    pub fn set_caps_rx(&mut self, rx: Receiver<Capabilities>) {
+        self.caps_rx = Some(rx);
+    }
+
+    pub fn drain_caps(&mut self) {
+        let Some(rx) = self.caps_rx.as_ref() else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(caps) => {
+                self.on_caps(&caps);
+                self.caps_rx = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => self.caps_rx = None,
+        }
+    }
+
+    fn on_caps(&mut self, caps: &Capabilities) {
+        // with `auto` the gpu switch still needs some hardware encoder to mean anything.
+        let gpu_pref = match self.encoder_pref(true) {
+            EncoderPref::Auto => EncoderPref::Gpu,
+            pref => pref,
+        };
+        self.gpu_unavailable = gpu_pref.resolve(self.codec, caps).err();
+        self.caps = Some(caps.clone());
+        if self.use_gpu
+            && let Some(reason) = &self.gpu_unavailable
+        {
+            // not saved: the option stays on for when the gpu is back.
+            self.use_gpu = false;
+            self.status = Some(format!("gpu off: {reason}"));
+        }
+    }
+
     pub fn set_update_rx(&mut self, rx: Receiver<UpdateMsg>) {
         self.update_rx = Some(rx);
     }
 
     pub fn drain_update(&mut self) {
-        loop {
-            let Some(rx) = self.update_rx.as_ref() else {
-                break;
-            };
+        while let Some(rx) = self.update_rx.as_ref() {
             match rx.try_recv() {
                 Ok(msg) => self.on_update_msg(msg),
                 Err(TryRecvError::Empty) => break,
@@ -384,16 +860,260 @@ impl App {
         self.screen = self.update_prompt_from;
     }
 
-    pub fn on_key(&mut self, _key: KeyEvent) {
-        // none
+    /// Asks on launch whether to pick up a batch that didn't finish last time.
+    pub fn offer_resume(&mut self, queue: Queue) {
+        if queue.unfinished().is_empty() {
+            return;
+        }
+        self.resume = Some(queue);
+        self.screen = Screen::ResumeConfirm;
+    }
+
+    pub fn resume_offer(&self) -> Option<&Queue> {
+        self.resume.as_ref()
+    }
+
+    /// Puts the unfinished files back on the review screen with the batch's preset, encoder,
+    /// container, animation, quality check, preserve and fallback settings.
+    pub fn accept_resume(&mut self) {
+        let Some(mut queue) = self.resume.take() else {
+            return;
+        };
+        queue.discard_partial();
+        self.preset = queue.preset;
+        self.min_savings = queue.min_savings;
+        // not saved: only this batch goes back to its encoder.
+        self.use_gpu = queue.encoder.is_hardware();
+        self.encoder_option = Some(EncoderPref::Fixed(queue.encoder));
+        self.container = queue.container;
+        if let Some(gif) = queue.gif {
+            self.gif = gif;
+        }
+        self.set_measure(queue.measure, queue.min_quality);
+        self.set_preserve(queue.preserve);
+        self.set_cpu_fallback(queue.cpu_fallback);
+        self.set_hw_decode(queue.hw_decode);
+        self.screen = Screen::Landing;
+        self.clear_files();
+        self.batch_args = queue.extra_args.clone();
+        let todo = queue.unfinished();
+        let inputs = todo.iter().map(|&i| queue.items[i].input.clone()).collect();
+        self.add_paths(inputs);
+        for &i in &todo {
+            let item = &queue.items[i];
+            // files still being sniffed take theirs along when they're added.
+            if let Some(f) = self
+                .files
+                .iter_mut()
+                .chain(&mut self.sniffing)
+                .find(|f| f.path == item.input)
+            {
+                f.overrides = item.overrides;
+            }
+        }
+    }
+
+    /// Drops the batch: partial outputs are deleted and the queue file is removed.
+    pub fn decline_resume(&mut self) -> crate::error::Result<()> {
+        self.screen = Screen::Landing;
+        let Some(mut queue) = self.resume.take() else {
+            return Ok(());
+        };
+        queue.discard_partial();
+        queue.remove()
+    }
+
+    pub fn open_settings(&mut self) -> crate::error::Result<()> {
+        let opts = crate::options::load()?;
+        self.settings = Some(SettingsState::new(opts, self.screen));
+        self.screen = Screen::Settings;
+        Ok(())
+    }
+
+    pub fn close_settings(&mut self) {
+        if let Some(s) = self.settings.take() {
+            self.screen = s.from;
+        }
+    }
+
+    pub fn settings(&self) -> Option<&SettingsState> {
+        self.settings.as_ref()
+    }
+
+    /// Whether keys should go to a text field (a setting or a log search) instead of the usual
+    /// bindings.
+    pub fn is_typing(&self) -> bool {
+        match self.screen {
+            Screen::Settings => self.settings.as_ref().is_some_and(|s| s.editing.is_some()),
+            Screen::Log => self.log.as_ref().is_some_and(|l| l.search.is_some()),
+            _ => false,
+        }
+    }
+
+    pub fn job_log(&self) -> Option<&Path> {
+        self.job_log.as_deref()
+    }
+
+    /// Opens the current or last job's ffmpeg log, if one was written.
+    pub fn open_log(&mut self) {
+        let Some(path) = self.job_log.clone().filter(|p| p.exists()) else {
+            return;
+        };
+        self.log = Some(LogViewState::new(path, self.screen));
+        self.screen = Screen::Log;
+    }
+
+    pub fn close_log(&mut self) {
+        if let Some(l) = self.log.take() {
+            self.screen = l.from;
+        }
+    }
+
+    pub fn log_view(&self) -> Option<&LogViewState> {
+        self.log.as_ref()
+    }
+
+    /// Shows the ffmpeg command the selected file would run, as `--dry-run` prints it.
+    pub fn preview_command(&mut self) {
+        let Some(f) = self.review_selected.and_then(|i| self.files.get(i)) else {
+            return;
+        };
+        let Some(ffmpeg) = self.ffmpeg.as_ref().map(|b| b.ffmpeg.clone()) else {
+            self.status = Some("ffmpeg missing; run `tinythis setup` first".to_string());
+            return;
+        };
+        let settings = f.overrides.apply(&self.encode_settings());
+        let preview: crate::error::Result<String> = (|| {
+            let plan = crate::exec::compress::plan_dry(
+                &ffmpeg,
+                &f.path,
+                &settings,
+                &self.extra_args(),
+                f.info.clone(),
+            )?;
+            if let Some(d) = plan.decision.as_ref().filter(|_| plan.is_skip()) {
+                return Ok(format!("skipped: {}", d.reason));
+            }
+            let output = crate::exec::compress::build_output_path(
+                &f.path,
+                plan.settings.preset,
+                plan.settings.container,
+                &self.naming,
+            )?;
+            let args = crate::exec::compress::encode_args(
+                &f.path,
+                &output,
+                &plan.settings,
+                plan.info.as_ref(),
+                &plan.extra_args,
+            );
+            Ok(crate::process::run::command_line(&ffmpeg, &args))
+        })();
+        match preview {
+            Ok(command) => {
+                self.command_preview = Some(command);
+                self.screen = Screen::Command;
+            }
+            Err(e) => self.status = Some(format!("could not build the command: {e}")),
+        }
+    }
+
+    pub fn close_command_preview(&mut self) {
+        self.command_preview = None;
+        self.screen = Screen::Review;
+    }
+
+    pub fn command_preview(&self) -> Option<&str> {
+        self.command_preview.as_deref()
+    }
+
+    /// Opens the in-terminal file browser, for when there's no native dialog (or it's asked for).
+    pub fn open_browser(&mut self) {
+        let browser = BrowserState::new(super::browser::load_recent(), self.screen);
+        self.probe_queue.extend(browser.initial_probes());
+        self.browser = Some(browser);
+        self.screen = Screen::Browser;
+    }
+
+    pub fn close_browser(&mut self) {
+        if let Some(b) = self.browser.take() {
+            self.screen = b.from;
+        }
+    }
+
+    pub fn browser(&self) -> Option<&BrowserState> {
+        self.browser.as_ref()
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) {
+        match self.screen {
+            Screen::Settings => self.on_settings_key(key),
+            Screen::Browser => self.on_browser_key(key),
+            Screen::Log => {
+                if let Some(l) = self.log.as_mut() {
+                    l.on_key(key);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_browser_key(&mut self, key: KeyEvent) {
+        let Some(b) = self.browser.as_mut() else {
+            return;
+        };
+        match b.on_key(key) {
+            BrowserAction::None => {}
+            BrowserAction::Probe(paths) => self.probe_queue.extend(paths),
+            BrowserAction::Add(paths) => {
+                b.remember_dir();
+                super::browser::save_recent(&b.recent);
+                self.close_browser();
+                self.add_paths(paths);
+            }
+        }
+    }
+
+    /// Saves each settings edit right away and applies it to this session.
+    fn on_settings_key(&mut self, key: KeyEvent) {
+        let Some(edit) = self.settings.as_mut().and_then(|s| s.on_key(key)) else {
+            return;
+        };
+        match crate::options::update(|o| edit.apply(o)) {
+            Ok(saved) => {
+                self.apply_options(&saved);
+                if edit.field() == Field::Preset {
+                    self.preset = saved.preset;
+                }
+                if let Some(s) = self.settings.as_mut() {
+                    s.opts = saved;
+                }
+            }
+            Err(e) => {
+                if let Some(s) = self.settings.as_mut() {
+                    s.message = Some(format!("could not save: {e}"));
+                }
+            }
+        }
     }
 }
 
+/// The entry after `current` in `options`, wrapping around; unknown values start over.
+fn next_option<T: Copy + PartialEq>(options: &[Option<T>], current: Option<T>) -> Option<T> {
+    let pos = options.iter().position(|o| *o == current);
+    pos.and_then(|i| options[(i + 1) % options.len()])
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Screen {
     Landing,
     Review,
     UpdateConfirm,
+    ResumeConfirm,
+    Settings,
+    Browser,
+    Command,
+    Log,
     Compressing,
     Done,
     Error,
@@ -404,8 +1124,11 @@ pub struct Progress {
     pub idx: usize,
     pub total: usize,
     pub current_name: String,
+    /// other files encoding at the same time.
+    pub others: usize,
     pub spinner_tick: u64,
-    pub percent: Option<u8>,
+    pub stats: Option<EncodeProgress>,
+    pub batch_eta_secs: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -416,7 +1139,18 @@ pub enum WorkerMsg {
         name: String,
     },
     Progress {
-        percent: u8,
+        idx: usize,
+        stats: EncodeProgress,
+        batch_eta_secs: Option<u64>,
+    },
+    /// file `idx` writes ffmpeg's output here.
+    Log {
+        idx: usize,
+        path: PathBuf,
+    },
+    FileDone {
+        idx: usize,
+        report: FileReport,
     },
     Error {
         message: String,
@@ -424,6 +1158,19 @@ pub enum WorkerMsg {
     Done,
 }
 
+#[derive(Debug)]
+struct RunningFile {
+    idx: usize,
+    name: String,
+    log: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct ProbeMsg {
+    pub path: PathBuf,
+    pub info: Option<MediaInfo>,
+}
+
 #[derive(Debug)]
 pub enum UpdateMsg {
     Available(UpdateInfo),
@@ -434,10 +1181,6 @@ fn normalize_key(path: &Path) -> String {
     path.to_string_lossy().to_lowercase()
 }
 
-fn is_supported_extension(path: &Path) -> bool {
-    crate::exec::input::is_supported_video(path)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -448,6 +1191,99 @@ mod tests {
         p
     }
 
+    #[test]
+    fn accepting_resume_reviews_only_unfinished_files() {
+        use crate::exec::queue::{ItemStatus, QueueItem};
+
+        let dir = tempfile::tempdir().unwrap();
+        let a = touch(dir.path(), "a.mp4");
+        let b = touch(dir.path(), "b.mp4");
+        let partial = touch(dir.path(), "b.tinythis.speed.mp4");
+        let item = |input: &PathBuf, output: Option<PathBuf>, status| QueueItem {
+            input: input.clone(),
+            output,
+            status,
+            overrides: Default::default(),
+        };
+
+        let mut app = App::new();
+        let mut queue = Queue::new(
+            &EncodeSettings {
+                preset: Preset::Speed,
+                encoder: Encoder::CPU,
+                container: Container::Mp4,
+                ..app.encode_settings()
+            },
+            &[],
+        );
+        queue.items = vec![
+            item(&a, None, ItemStatus::Done),
+            item(&b, Some(partial.clone()), ItemStatus::Running),
+        ];
+        queue.cpu_fallback = true;
+        queue.preserve.subtitles = true;
+        queue.items[1].overrides.trim_secs = Some(30);
+        queue.extra_args.output = vec!["-tune".to_string(), "film".to_string()];
+        app.offer_resume(queue);
+        assert_eq!(app.screen(), Screen::ResumeConfirm);
+
+        app.accept_resume();
+        assert_eq!(app.screen(), Screen::Review);
+        assert_eq!(app.preset(), Preset::Speed);
+        assert!(!app.use_gpu());
+        assert!(app.encode_settings().cpu_fallback);
+        assert!(app.encode_settings().preserve.subtitles);
+        assert_eq!(app.files().len(), 1);
+        assert_eq!(app.files()[0].path, b);
+        assert_eq!(app.files()[0].overrides.trim_secs, Some(30));
+        assert_eq!(
+            app.extra_args().for_preset(Preset::Speed).output,
+            ["-tune", "film"]
+        );
+        assert!(!partial.exists());
+
+        app.clear_files();
+        assert!(app.extra_args().for_preset(Preset::Speed).output.is_empty());
+    }
+
+    #[test]
+    fn overrides_cycle_per_file_and_apply_to_all() {
+        let mut app = App::new();
+        let dir = tempfile::tempdir().unwrap();
+        let a = touch(dir.path(), "a.mp4");
+        let b = touch(dir.path(), "b.mp4");
+        app.add_paths(vec![a, b]);
+
+        app.cycle_file_preset();
+        assert_eq!(app.status(), Some("select a file first (up/down)"));
+
+        app.select_next_file();
+        app.cycle_file_preset();
+        app.cycle_file_preset();
+        app.cycle_file_trim();
+        app.cycle_file_resolution();
+        app.cycle_file_resolution();
+        app.cycle_file_encoder();
+        let o = app.files()[0].overrides;
+        assert_eq!(o.preset, Some(Preset::Balanced));
+        assert_eq!(o.trim_secs, Some(15));
+        assert_eq!(o.max_height, Some(720));
+        assert_eq!(o.encoder, Some(Encoder::CPU));
+        assert!(app.files()[1].overrides.is_empty());
+
+        // without detected capabilities only the cpu is offered, then back to the batch's.
+        app.cycle_file_encoder();
+        assert_eq!(app.files()[0].overrides.encoder, None);
+
+        app.apply_overrides_to_all();
+        assert_eq!(app.files()[1].overrides, app.files()[0].overrides);
+        assert_eq!(app.status(), Some("applied to 2 files"));
+
+        let settings = app.files()[1].overrides.apply(&app.encode_settings());
+        assert_eq!(settings.preset, Preset::Balanced);
+        assert_eq!(settings.trim_secs, Some(15));
+    }
+
     #[test]
     fn review_selection_moves_and_clamps() {
         let mut app = App::new();
@@ -508,6 +1344,358 @@ mod tests {
         assert_eq!(app.status(), Some("no files"));
     }
 
+    #[test]
+    fn preset_cycle_includes_remux_auto_and_animations() {
+        let mut app = App::new();
+        assert_eq!(app.preset(), Preset::Balanced);
+        app.next_preset();
+        app.next_preset();
+        assert_eq!(app.preset(), Preset::Remux);
+        app.next_preset();
+        assert_eq!(app.preset(), Preset::Auto);
+        app.next_preset();
+        assert_eq!(app.preset(), Preset::Gif);
+        app.next_preset();
+        assert_eq!(app.preset(), Preset::Webp);
+        app.next_preset();
+        assert_eq!(app.preset(), Preset::Quality);
+        app.prev_preset();
+        assert_eq!(app.preset(), Preset::Webp);
+        app.prev_preset();
+        app.prev_preset();
+        assert_eq!(app.preset(), Preset::Auto);
+    }
+
+    #[test]
+    fn probe_results_attach_to_files_and_drive_auto_decisions() {
+        let mut app = App::new();
+        let dir = tempfile::tempdir().unwrap();
+        let a = touch(dir.path(), "a.mp4");
+        app.add_paths(vec![a.clone()]);
+        assert_eq!(app.take_probe_queue(), vec![a.clone()]);
+        assert!(app.take_probe_queue().is_empty());
+
+        app.probe_sender()
+            .send(ProbeMsg {
+                path: a,
+                info: Some(MediaInfo::default()),
+            })
+            .unwrap();
+        app.drain_probes();
+        assert!(app.files()[0].info.is_some());
+
+        assert!(app.auto_decision(&app.files()[0]).is_none());
+        while app.preset() != Preset::Auto {
+            app.next_preset();
+        }
+        let d = app.auto_decision(&app.files()[0]).unwrap();
+        assert_eq!(d.label(), "skip");
+    }
+
+    #[test]
+    fn unknown_extensions_are_added_once_their_probe_finds_video() {
+        use crate::exec::probe::{StreamInfo, StreamKind};
+
+        let mut app = App::new();
+        let dir = tempfile::tempdir().unwrap();
+        let rec = touch(dir.path(), "recording");
+        let notes = touch(dir.path(), "notes.txt");
+        app.add_paths(vec![rec.clone()]);
+        assert_eq!(app.status(), Some("ignored 1 unsupported"));
+        assert!(app.take_probe_queue().is_empty());
+
+        app.set_ffmpeg(
+            FfmpegBinaries {
+                ffmpeg: PathBuf::from("ffmpeg"),
+            },
+            FfmpegSource::NearExe,
+        );
+        app.add_paths(vec![rec.clone(), notes.clone()]);
+        assert!(app.files().is_empty());
+        assert_eq!(app.screen(), Screen::Landing);
+        assert_eq!(app.status(), Some("checking 2 for video"));
+        assert_eq!(app.take_probe_queue(), vec![rec.clone(), notes.clone()]);
+
+        let video = MediaInfo {
+            duration_us: Some(1_000_000),
+            streams: vec![StreamInfo {
+                kind: StreamKind::Video,
+                codec: "h264".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let tx = app.probe_sender();
+        tx.send(ProbeMsg {
+            path: rec.clone(),
+            info: Some(video),
+        })
+        .unwrap();
+        tx.send(ProbeMsg {
+            path: notes.clone(),
+            info: None,
+        })
+        .unwrap();
+        app.drain_probes();
+        assert_eq!(app.screen(), Screen::Review);
+        assert_eq!(app.status(), Some("added 1 file, ignored 1 unsupported"));
+        assert_eq!(app.files().len(), 1);
+        assert_eq!(app.files()[0].path, rec);
+        // the sniffing probe is the file's probe.
+        assert_eq!(
+            app.files()[0].info.as_ref().and_then(|i| i.duration_us),
+            Some(1_000_000)
+        );
+
+        // a dropped file can be picked again.
+        app.add_paths(vec![notes.clone()]);
+        assert_eq!(app.take_probe_queue(), vec![notes]);
+    }
+
+    #[test]
+    fn command_preview_shows_the_selected_files_encode() {
+        let mut app = App::new();
+        let dir = tempfile::tempdir().unwrap();
+        let a = touch(dir.path(), "a.mp4");
+        app.add_paths(vec![a.clone()]);
+        app.select_next_file();
+        app.preview_command();
+        assert_eq!(app.screen(), Screen::Review);
+        assert!(app.status().is_some_and(|s| s.contains("ffmpeg missing")));
+
+        app.set_ffmpeg(
+            FfmpegBinaries {
+                ffmpeg: PathBuf::from("ffmpeg"),
+            },
+            FfmpegSource::NearExe,
+        );
+        app.preview_command();
+        assert_eq!(app.screen(), Screen::Command);
+        let cmd = app.command_preview().unwrap();
+        assert!(cmd.starts_with("ffmpeg "));
+        assert!(cmd.contains("a.tinythis.balanced.mp4"));
+        app.close_command_preview();
+        assert_eq!(app.screen(), Screen::Review);
+        assert_eq!(app.command_preview(), None);
+
+        app.probe_sender()
+            .send(ProbeMsg {
+                path: a,
+                info: Some(MediaInfo::default()),
+            })
+            .unwrap();
+        app.drain_probes();
+        while app.preset() != Preset::Auto {
+            app.next_preset();
+        }
+        app.preview_command();
+        assert!(app.command_preview().unwrap().starts_with("skipped: "));
+    }
+
+    #[test]
+    fn missing_gpu_encoder_turns_gpu_off() {
+        let mut app = App::new();
+        app.set_use_gpu(true);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        app.set_caps_rx(rx);
+        tx.send(Capabilities {
+            encoders: vec!["libx264".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        app.drain_caps();
+
+        assert!(!app.use_gpu());
+        assert!(app.gpu_unavailable().is_some());
+        assert!(app.status().is_some_and(|s| s.starts_with("gpu off:")));
+        assert_eq!(app.encode_settings().encoder, Encoder::CPU);
+    }
+
+    #[test]
+    fn gpu_switch_uses_whichever_hardware_encoder_works() {
+        let mut app = App::new();
+        app.set_use_gpu(true);
+        assert_eq!(app.encoder(), Encoder::NVENC);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        app.set_caps_rx(rx);
+        tx.send(Capabilities {
+            encoders: vec!["libx264".to_string(), "h264_amf".to_string()],
+            usable_hardware: vec!["h264_amf".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        app.drain_caps();
+
+        assert!(app.use_gpu());
+        assert!(app.gpu_unavailable().is_none());
+        assert_eq!(app.encoder().ffmpeg_name(), "h264_amf");
+    }
+
+    #[test]
+    fn applied_options_switch_codec_but_keep_the_chosen_preset() {
+        let mut app = App::new();
+        app.next_preset();
+        let opts = Options {
+            preset: Preset::Quality,
+            gpu: true,
+            codec: Codec::Hevc,
+            update_check: false,
+            ..Default::default()
+        };
+        app.apply_options(&opts);
+        assert_eq!(app.preset(), Preset::Speed);
+        assert!(!app.update_check());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        app.set_caps_rx(rx);
+        tx.send(Capabilities {
+            encoders: vec!["libx264".to_string(), "hevc_amf".to_string()],
+            usable_hardware: vec!["hevc_amf".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        app.drain_caps();
+        assert_eq!(app.encoder().ffmpeg_name(), "hevc_amf");
+
+        app.apply_options(&Options {
+            codec: Codec::H264,
+            ..opts
+        });
+        // no h264 hardware encoder here, so the gpu switch has nothing to use.
+        assert!(!app.use_gpu());
+        assert_eq!(app.encoder(), Encoder::CPU);
+    }
+
+    #[test]
+    fn batch_eta_carries_over_until_the_next_file_reports() {
+        let mut app = App::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        app.set_worker(rx, 2);
+
+        let stats = EncodeProgress {
+            percent: Some(50),
+            speed: Some(2.0),
+            ..Default::default()
+        };
+        tx.send(WorkerMsg::Progress {
+            idx: 0,
+            stats: stats.clone(),
+            batch_eta_secs: Some(90),
+        })
+        .unwrap();
+        tx.send(WorkerMsg::Started {
+            idx: 2,
+            total: 2,
+            name: "b.mp4".to_string(),
+        })
+        .unwrap();
+        app.drain_worker();
+
+        let p = app.progress().unwrap();
+        assert_eq!(p.stats, None);
+        assert_eq!(p.batch_eta_secs, Some(90));
+
+        tx.send(WorkerMsg::Progress {
+            idx: 2,
+            stats,
+            batch_eta_secs: None,
+        })
+        .unwrap();
+        app.drain_worker();
+        let p = app.progress().unwrap();
+        assert_eq!(p.stats.as_ref().and_then(|s| s.percent), Some(50));
+        assert_eq!(p.batch_eta_secs, Some(90));
+    }
+
+    #[test]
+    fn files_encoding_at_once_hand_the_screen_on() {
+        let mut app = App::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        app.set_worker(rx, 3);
+        for (idx, name) in [(1, "a.mp4"), (2, "b.mp4")] {
+            tx.send(WorkerMsg::Started {
+                idx,
+                total: 3,
+                name: name.to_string(),
+            })
+            .unwrap();
+            tx.send(WorkerMsg::Log {
+                idx,
+                path: PathBuf::from(format!("{name}.log")),
+            })
+            .unwrap();
+        }
+        let stats = EncodeProgress {
+            percent: Some(40),
+            ..Default::default()
+        };
+        tx.send(WorkerMsg::Progress {
+            idx: 1,
+            stats,
+            batch_eta_secs: None,
+        })
+        .unwrap();
+        app.drain_worker();
+
+        let p = app.progress().unwrap();
+        assert_eq!((p.idx, p.current_name.as_str(), p.others), (2, "b.mp4", 1));
+        assert_eq!(p.stats, None);
+        assert_eq!(app.job_log(), Some(Path::new("b.mp4.log")));
+
+        let report = FileReport {
+            input: PathBuf::from("b.mp4"),
+            input_bytes: 0,
+            output: None,
+            output_bytes: None,
+            preset: Preset::Balanced,
+            decision: None,
+            crf: None,
+            scores: None,
+            threshold: None,
+            fallback: None,
+            parts: Vec::new(),
+        };
+        tx.send(WorkerMsg::FileDone { idx: 2, report }).unwrap();
+        app.drain_worker();
+        let p = app.progress().unwrap();
+        assert_eq!((p.idx, p.current_name.as_str(), p.others), (1, "a.mp4", 0));
+        assert_eq!(app.job_log(), Some(Path::new("a.mp4.log")));
+    }
+
+    #[test]
+    fn a_failure_while_reading_the_log_lands_behind_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("a.log");
+        std::fs::write(&log, "$ ffmpeg\nframe=1\n").unwrap();
+
+        let mut app = App::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        app.set_worker(rx, 1);
+        app.open_log();
+        assert_eq!(app.screen(), Screen::Compressing);
+
+        tx.send(WorkerMsg::Log {
+            idx: 0,
+            path: log.clone(),
+        })
+        .unwrap();
+        app.drain_worker();
+        app.open_log();
+        assert_eq!(app.screen(), Screen::Log);
+
+        tx.send(WorkerMsg::Error {
+            message: "boom".to_string(),
+        })
+        .unwrap();
+        app.drain_worker();
+        assert_eq!(app.screen(), Screen::Log);
+        app.close_log();
+        assert_eq!(app.screen(), Screen::Error);
+        assert_eq!(app.job_log(), Some(log.as_path()));
+    }
+
     #[test]
     fn clear_files_allows_reselecting_same_path() {
         let mut app = App::new();
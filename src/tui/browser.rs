@@ -0,0 +1,417 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use crate::error::Result;
+
+use super::app::Screen;
+
+const RECENT_FILE_NAME: &str = "recent_dirs.txt";
+
+/// Recent folders kept; `1`-`9` jump to them.
+const MAX_RECENT: usize = 9;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EntryKind {
+    Parent,
+    Dir,
+    Video { size: u64 },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub name: String,
+    pub kind: EntryKind,
+}
+
+/// `..`, then folders, then supported videos, each sorted by name; hidden entries are left out.
+pub fn list_dir(dir: &Path) -> Result<Vec<Entry>> {
+    let mut dirs = Vec::new();
+    let mut videos = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        // follows symlinks, so a linked folder opens like any other.
+        let Ok(meta) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            dirs.push(Entry {
+                path,
+                name,
+                kind: EntryKind::Dir,
+            });
+        } else if meta.is_file() && crate::exec::input::is_supported_video(&path) {
+            videos.push(Entry {
+                path,
+                name,
+                kind: EntryKind::Video { size: meta.len() },
+            });
+        }
+    }
+    dirs.sort_by_key(|e| e.name.to_lowercase());
+    videos.sort_by_key(|e| e.name.to_lowercase());
+
+    let parent = dir.parent().map(|p| Entry {
+        path: p.to_path_buf(),
+        name: "..".to_string(),
+        kind: EntryKind::Parent,
+    });
+    Ok(parent.into_iter().chain(dirs).chain(videos).collect())
+}
+
+/// Supported videos directly inside `dir`, sorted; what picking a whole folder adds.
+pub fn videos_in(dir: &Path) -> Vec<PathBuf> {
+    list_dir(dir)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|e| matches!(e.kind, EntryKind::Video { .. }))
+        .map(|e| e.path)
+        .collect()
+}
+
+/// What the app has to do after a browser key.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum BrowserAction {
+    None,
+    /// videos now on screen whose length isn't known yet.
+    Probe(Vec<PathBuf>),
+    /// the picked files (folders expanded); the browser is done.
+    Add(Vec<PathBuf>),
+}
+
+#[derive(Debug)]
+pub struct BrowserState {
+    pub dir: PathBuf,
+    pub entries: Vec<Entry>,
+    pub cursor: usize,
+    /// files and whole folders, across every folder visited.
+    pub picked: BTreeSet<PathBuf>,
+    pub recent: Vec<PathBuf>,
+    /// probed lengths; `None` once a probe has failed.
+    pub durations: HashMap<PathBuf, Option<u64>>,
+    pub message: Option<String>,
+    /// where esc goes back to.
+    pub from: Screen,
+}
+
+impl BrowserState {
+    /// Opens in the most recent folder that still exists, else the working directory.
+    pub fn new(recent: Vec<PathBuf>, from: Screen) -> Self {
+        let start = recent
+            .iter()
+            .find(|d| d.is_dir())
+            .cloned()
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        let mut s = Self {
+            dir: PathBuf::new(),
+            entries: Vec::new(),
+            cursor: 0,
+            picked: BTreeSet::new(),
+            recent,
+            durations: HashMap::new(),
+            message: None,
+            from,
+        };
+        s.open(start);
+        s
+    }
+
+    pub fn current(&self) -> Option<&Entry> {
+        self.entries.get(self.cursor)
+    }
+
+    /// Lists `dir`; an unreadable folder leaves the current listing up with a message.
+    fn open(&mut self, dir: PathBuf) {
+        match list_dir(&dir) {
+            Ok(entries) => {
+                // back out of a folder with the cursor on it.
+                self.cursor = entries
+                    .iter()
+                    .position(|e| e.kind == EntryKind::Dir && e.path == self.dir)
+                    .unwrap_or(0);
+                self.entries = entries;
+                self.dir = dir;
+                self.message = None;
+            }
+            Err(e) => self.message = Some(format!("can't open {}: {e}", dir.display())),
+        }
+    }
+
+    fn unprobed(&self) -> Vec<PathBuf> {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e.kind, EntryKind::Video { .. }))
+            .filter(|e| !self.durations.contains_key(&e.path))
+            .map(|e| e.path.clone())
+            .collect()
+    }
+
+    pub fn set_duration(&mut self, path: &Path, duration_us: Option<u64>) {
+        if self.entries.iter().any(|e| e.path == path) {
+            self.durations.insert(path.to_path_buf(), duration_us);
+        }
+    }
+
+    /// Folders go in as the videos directly inside them.
+    fn picked_files(&self) -> Vec<PathBuf> {
+        let mut out = Vec::new();
+        for p in &self.picked {
+            if p.is_dir() {
+                out.extend(videos_in(p));
+            } else {
+                out.push(p.clone());
+            }
+        }
+        out
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) -> BrowserAction {
+        let last = self.entries.len().saturating_sub(1);
+        let before = self.dir.clone();
+        match key.code {
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => self.cursor = (self.cursor + 1).min(last),
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(10),
+            KeyCode::PageDown => self.cursor = (self.cursor + 10).min(last),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = last,
+            KeyCode::Left | KeyCode::Backspace => {
+                if let Some(parent) = self.dir.parent() {
+                    self.open(parent.to_path_buf());
+                }
+            }
+            KeyCode::Right => {
+                if let Some(e) = self.current()
+                    && !matches!(e.kind, EntryKind::Video { .. })
+                {
+                    self.open(e.path.clone());
+                }
+            }
+            KeyCode::Enter => match self.current().cloned() {
+                Some(e) if !matches!(e.kind, EntryKind::Video { .. }) && self.picked.is_empty() => {
+                    self.open(e.path)
+                }
+                current => {
+                    if self.picked.is_empty()
+                        && let Some(e) = current
+                    {
+                        self.picked.insert(e.path);
+                    }
+                    let files = self.picked_files();
+                    if files.is_empty() {
+                        self.message = Some("nothing picked (space: pick)".to_string());
+                        return BrowserAction::None;
+                    }
+                    return BrowserAction::Add(files);
+                }
+            },
+            KeyCode::Char(' ') => {
+                if let Some(e) = self.current()
+                    && e.kind != EntryKind::Parent
+                {
+                    let path = e.path.clone();
+                    if !self.picked.remove(&path) {
+                        self.picked.insert(path);
+                    }
+                    self.cursor = (self.cursor + 1).min(last);
+                }
+            }
+            KeyCode::Char('a') | KeyCode::Char('ф') => {
+                let videos = self
+                    .entries
+                    .iter()
+                    .filter(|e| matches!(e.kind, EntryKind::Video { .. }))
+                    .map(|e| e.path.clone())
+                    .collect::<Vec<_>>();
+                if videos.iter().all(|v| self.picked.contains(v)) {
+                    for v in &videos {
+                        self.picked.remove(v);
+                    }
+                } else {
+                    self.picked.extend(videos);
+                }
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                let idx = c as usize - '1' as usize;
+                if let Some(dir) = self.recent.get(idx).cloned() {
+                    self.open(dir);
+                }
+            }
+            _ => {}
+        }
+
+        if self.dir != before {
+            let todo = self.unprobed();
+            if !todo.is_empty() {
+                return BrowserAction::Probe(todo);
+            }
+        }
+        BrowserAction::None
+    }
+
+    /// Puts the current folder first in the recent list.
+    pub fn remember_dir(&mut self) {
+        let dir = self.dir.clone();
+        self.recent.retain(|d| *d != dir);
+        self.recent.insert(0, dir);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// What to probe right after opening.
+    pub fn initial_probes(&self) -> Vec<PathBuf> {
+        self.unprobed()
+    }
+}
+
+/// Recent folders from `recent_dirs.txt` in the app folder, most recent first.
+pub fn load_recent() -> Vec<PathBuf> {
+    crate::paths::app_root_dir()
+        .map(|root| load_recent_from(&root))
+        .unwrap_or_default()
+}
+
+/// A failed write only costs the bookmarks.
+pub fn save_recent(recent: &[PathBuf]) {
+    if let Ok(root) = crate::paths::app_root_dir() {
+        let _ = save_recent_to(&root, recent);
+    }
+}
+
+fn load_recent_from(app_root: &Path) -> Vec<PathBuf> {
+    std::fs::read_to_string(app_root.join(RECENT_FILE_NAME))
+        .map(|s| {
+            s.lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(PathBuf::from)
+                .take(MAX_RECENT)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn save_recent_to(app_root: &Path, recent: &[PathBuf]) -> Result<()> {
+    std::fs::create_dir_all(app_root)?;
+    let text = recent
+        .iter()
+        .map(|d| format!("{}\n", d.display()))
+        .collect::<String>();
+    let mut tmp = tempfile::NamedTempFile::new_in(app_root)?;
+    tmp.as_file_mut().write_all(text.as_bytes())?;
+    tmp.as_file_mut().flush()?;
+    tmp.persist(app_root.join(RECENT_FILE_NAME))
+        .map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Whether `rfd` can show a native dialog here: not over ssh, and on linux only with a display.
+pub fn native_dialog_available() -> bool {
+    let var = |k: &str| std::env::var_os(k).is_some_and(|v| !v.is_empty());
+    if var("SSH_CONNECTION") || var("SSH_TTY") {
+        return false;
+    }
+    cfg!(any(windows, target_os = "macos")) || var("DISPLAY") || var("WAYLAND_DISPLAY")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crossterm::event::KeyModifiers;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn touch(dir: &Path, name: &str) -> PathBuf {
+        let p = dir.join(name);
+        std::fs::write(&p, b"x").unwrap();
+        p
+    }
+
+    #[test]
+    fn lists_folders_then_videos_and_skips_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("Sub")).unwrap();
+        std::fs::create_dir(dir.path().join(".hidden")).unwrap();
+        touch(dir.path(), "b.MOV");
+        touch(dir.path(), "a.mp4");
+        touch(dir.path(), "notes.txt");
+
+        let names = list_dir(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["..", "Sub", "a.mp4", "b.MOV"]);
+    }
+
+    #[test]
+    fn picks_files_and_folders_across_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        let a = touch(dir.path(), "a.mp4");
+        let c = touch(&sub, "c.mp4");
+        let d = touch(&sub, "d.webm");
+
+        let mut b = BrowserState::new(vec![dir.path().to_path_buf()], Screen::Landing);
+        assert_eq!(b.dir, dir.path());
+        // cursor: .., sub, a.mp4
+        b.on_key(key(KeyCode::Down));
+        b.on_key(key(KeyCode::Char(' ')));
+        assert_eq!(b.current().map(|e| e.path.clone()), Some(a.clone()));
+        b.on_key(key(KeyCode::Char(' ')));
+
+        assert_eq!(
+            b.on_key(key(KeyCode::Enter)),
+            BrowserAction::Add(vec![a.clone(), c.clone(), d.clone()])
+        );
+
+        // right opens a folder even with picks pending; left comes back out onto it.
+        b.on_key(key(KeyCode::Home));
+        b.on_key(key(KeyCode::Down));
+        assert_eq!(
+            b.on_key(key(KeyCode::Right)),
+            BrowserAction::Probe(vec![c.clone(), d.clone()])
+        );
+        assert_eq!(b.dir, sub);
+        b.set_duration(&d, Some(5_000_000));
+        b.on_key(key(KeyCode::Left));
+        assert_eq!(b.current().map(|e| e.path.clone()), Some(sub.clone()));
+
+        // with nothing picked, enter on a folder opens it and enter on a file takes just that.
+        b.picked.clear();
+        // only c is still unprobed; d's length came back earlier.
+        assert_eq!(b.on_key(key(KeyCode::Enter)), BrowserAction::Probe(vec![c]));
+        assert_eq!(b.dir, sub);
+        b.on_key(key(KeyCode::End));
+        assert_eq!(b.on_key(key(KeyCode::Enter)), BrowserAction::Add(vec![d]));
+    }
+
+    #[test]
+    fn recent_folders_round_trip_most_recent_first() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load_recent_from(dir.path()).is_empty());
+
+        let mut b = BrowserState::new(Vec::new(), Screen::Review);
+        b.recent = (0..MAX_RECENT)
+            .map(|i| PathBuf::from(format!("d{i}")))
+            .collect();
+        b.dir = PathBuf::from("d3");
+        b.remember_dir();
+        assert_eq!(b.recent[0], PathBuf::from("d3"));
+        assert_eq!(b.recent.len(), MAX_RECENT);
+
+        save_recent_to(dir.path(), &b.recent).unwrap();
+        assert_eq!(load_recent_from(dir.path()), b.recent);
+    }
+}
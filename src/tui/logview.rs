@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use super::app::Screen;
+
+/// Lines a page up/down moves.
+const PAGE: usize = 20;
+
+#[derive(Debug)]
+pub struct LogViewState {
+    pub path: PathBuf,
+    pub lines: Vec<String>,
+    /// first line on screen.
+    pub scroll: usize,
+    /// the search being typed after `/`.
+    pub search: Option<String>,
+    /// the last search, for `n`/`N` and highlighting.
+    pub query: Option<String>,
+    pub message: Option<String>,
+    /// where esc goes back to.
+    pub from: Screen,
+}
+
+impl LogViewState {
+    /// Opens at the end, where ffmpeg's error is.
+    pub fn new(path: PathBuf, from: Screen) -> Self {
+        let mut s = Self {
+            path,
+            lines: Vec::new(),
+            scroll: 0,
+            search: None,
+            query: None,
+            message: None,
+            from,
+        };
+        s.reload();
+        s.scroll = s.last();
+        s
+    }
+
+    /// Rereads the file, keeping the position; a running encode keeps adding to it.
+    pub fn reload(&mut self) {
+        match crate::exec::joblog::read_lines(&self.path) {
+            Ok(lines) => {
+                self.lines = lines;
+                self.message = None;
+            }
+            Err(e) => self.message = Some(format!("could not read log: {e}")),
+        }
+        self.scroll = self.scroll.min(self.last());
+    }
+
+    fn last(&self) -> usize {
+        self.lines.len().saturating_sub(1)
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) {
+        if let Some(buf) = self.search.as_mut() {
+            match key.code {
+                KeyCode::Char(c) => buf.push(c),
+                KeyCode::Backspace => {
+                    buf.pop();
+                }
+                KeyCode::Esc => self.search = None,
+                KeyCode::Enter => {
+                    let query = self.search.take().unwrap_or_default();
+                    self.query = (!query.is_empty()).then_some(query);
+                    // the line on top counts, so a fresh search can land where you are.
+                    self.find(self.scroll, true);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        let last = self.last();
+        match key.code {
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down => self.scroll = (self.scroll + 1).min(last),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(PAGE),
+            KeyCode::PageDown => self.scroll = (self.scroll + PAGE).min(last),
+            KeyCode::Home => self.scroll = 0,
+            KeyCode::End => self.scroll = last,
+            KeyCode::Char('/') | KeyCode::Char('.') => self.search = Some(String::new()),
+            KeyCode::Char('n') | KeyCode::Char('т') => self.find(self.scroll + 1, true),
+            KeyCode::Char('N') | KeyCode::Char('Т') => {
+                self.find(self.scroll.saturating_sub(1), false)
+            }
+            KeyCode::Char('r') | KeyCode::Char('к') => self.reload(),
+            _ => {}
+        }
+    }
+
+    /// Scrolls to the next line from `start` holding the query, case-insensitively, wrapping.
+    fn find(&mut self, start: usize, forward: bool) {
+        let Some(query) = self.query.as_ref().map(|q| q.to_lowercase()) else {
+            return;
+        };
+        let n = self.lines.len();
+        if n == 0 {
+            return;
+        }
+        let hit = (0..n)
+            .map(|i| {
+                if forward {
+                    (start + i) % n
+                } else {
+                    (start + n - i % n) % n
+                }
+            })
+            .find(|&i| self.lines[i].to_lowercase().contains(&query));
+        match hit {
+            Some(i) => {
+                self.scroll = i;
+                self.message = None;
+            }
+            None => self.message = Some(format!("not found: {query}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crossterm::event::KeyModifiers;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn typed(v: &mut LogViewState, text: &str) {
+        for c in text.chars() {
+            v.on_key(key(KeyCode::Char(c)));
+        }
+    }
+
+    fn view(text: &str) -> (tempfile::TempDir, LogViewState) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("job.log");
+        std::fs::write(&path, text).unwrap();
+        let v = LogViewState::new(path, Screen::Error);
+        (dir, v)
+    }
+
+    #[test]
+    fn opens_at_the_end_and_scrolls_within_the_log() {
+        let text = (0..50).map(|i| format!("line {i}\n")).collect::<String>();
+        let (_dir, mut v) = view(&text);
+        assert_eq!(v.scroll, 49);
+
+        v.on_key(key(KeyCode::Down));
+        assert_eq!(v.scroll, 49);
+        v.on_key(key(KeyCode::PageUp));
+        assert_eq!(v.scroll, 29);
+        v.on_key(key(KeyCode::Home));
+        v.on_key(key(KeyCode::Up));
+        assert_eq!(v.scroll, 0);
+
+        std::fs::write(&v.path, "only\n").unwrap();
+        v.on_key(key(KeyCode::Char('r')));
+        assert_eq!(v.lines, ["only"]);
+        assert_eq!(v.scroll, 0);
+    }
+
+    #[test]
+    fn search_jumps_between_matches_and_wraps() {
+        let (_dir, mut v) =
+            view("$ ffmpeg -i a.mp4\nframe=1\nError opening\nframe=2\nerror: bad\n");
+        v.on_key(key(KeyCode::Home));
+
+        v.on_key(key(KeyCode::Char('/')));
+        typed(&mut v, "error");
+        assert_eq!(v.search.as_deref(), Some("error"));
+        // typed letters belong to the search, not the bindings.
+        assert_eq!(v.scroll, 0);
+        v.on_key(key(KeyCode::Enter));
+        assert_eq!(v.search, None);
+        assert_eq!(v.scroll, 2);
+
+        v.on_key(key(KeyCode::Char('n')));
+        assert_eq!(v.scroll, 4);
+        v.on_key(key(KeyCode::Char('n')));
+        assert_eq!(v.scroll, 2);
+        v.on_key(key(KeyCode::Char('N')));
+        assert_eq!(v.scroll, 4);
+
+        v.on_key(key(KeyCode::Char('/')));
+        typed(&mut v, "vmaf");
+        v.on_key(key(KeyCode::Enter));
+        assert_eq!(v.scroll, 4);
+        assert_eq!(v.message.as_deref(), Some("not found: vmaf"));
+    }
+}
@@ -1,4 +1,7 @@
 mod app;
+mod browser;
+mod logview;
+mod settings;
 mod terminal;
 mod ui;
 
@@ -9,27 +12,49 @@ use std::time::Instant;
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 
 use crate::error::Result;
+use crate::exec::queue::ItemStatus;
 
 pub fn run(initial_status: Option<String>) -> Result<()> {
     let mut app = app::App::new();
     app.set_status_message(initial_status);
 
     let opts = crate::options::load()?;
-    app.set_use_gpu(opts.gpu);
+    app.set_preset(opts.preset);
+    app.apply_options(&opts);
+
+    // an unreadable queue file just means there's nothing to offer, and one another tinythis
+    // holds is still running.
+    if let Ok(Some(mut queue)) = crate::exec::queue::Queue::load()
+        && queue.claim().unwrap_or(false)
+    {
+        app.offer_resume(queue);
+    }
 
     preflight_ffmpeg(&mut app)?;
+    if let Some(bins) = app.ffmpeg() {
+        let ffmpeg = bins.ffmpeg.clone();
+        let (caps_tx, caps_rx) = std::sync::mpsc::channel();
+        app.set_caps_rx(caps_rx);
+        std::thread::spawn(move || {
+            if let Ok(caps) = crate::exec::caps::detect(&ffmpeg) {
+                let _ = caps_tx.send(caps);
+            }
+        });
+    }
 
     let mut session = terminal::TerminalSession::enter()?;
-    let (update_tx, update_rx) = std::sync::mpsc::channel::<app::UpdateMsg>();
-    app.set_update_rx(update_rx);
-    std::thread::spawn(move || {
-        let msg = match crate::update::check_latest_release(crate::update::DEFAULT_REPO) {
-            Ok(Some(info)) => app::UpdateMsg::Available(info),
-            Ok(None) => app::UpdateMsg::None,
-            Err(_) => app::UpdateMsg::None,
-        };
-        let _ = update_tx.send(msg);
-    });
+    if app.update_check() {
+        let (update_tx, update_rx) = std::sync::mpsc::channel::<app::UpdateMsg>();
+        app.set_update_rx(update_rx);
+        std::thread::spawn(move || {
+            let msg = match crate::update::check_latest_release(crate::update::DEFAULT_REPO) {
+                Ok(Some(info)) => app::UpdateMsg::Available(info),
+                Ok(None) => app::UpdateMsg::None,
+                Err(_) => app::UpdateMsg::None,
+            };
+            let _ = update_tx.send(msg);
+        });
+    }
     let tick_rate = Duration::from_millis(80);
     let mut last_tick = Instant::now();
     let mut drop_text = DropTextCollector::new();
@@ -37,6 +62,9 @@ pub fn run(initial_status: Option<String>) -> Result<()> {
     while !app.should_quit() {
         app.drain_worker();
         app.drain_update();
+        app.drain_caps();
+        app.drain_probes();
+        spawn_probes(&mut app);
         if !screen_allows_drop_text(app.screen()) {
             for replay in drop_text.take_replay_keys() {
                 handle_key(&mut session, &mut app, replay)?;
@@ -128,6 +156,12 @@ fn handle_key(
     app: &mut app::App,
     key: KeyEvent,
 ) -> Result<()> {
+    // while a setting or search is being typed, q and esc belong to the text.
+    if app.is_typing() && !key.modifiers.contains(KeyModifiers::CONTROL) {
+        app.on_key(key);
+        return Ok(());
+    }
+
     match key.code {
         KeyCode::Char('q') => app.quit(),
         KeyCode::Esc => match app.screen() {
@@ -137,6 +171,16 @@ fn handle_key(
                 app.set_screen(app::Screen::Landing);
             }
             app::Screen::UpdateConfirm => app.close_update_prompt(),
+            app::Screen::ResumeConfirm => {
+                if let Err(e) = app.decline_resume() {
+                    app.set_error(format!("{e}"));
+                    app.set_screen(app::Screen::Error);
+                }
+            }
+            app::Screen::Settings => app.close_settings(),
+            app::Screen::Browser => app.close_browser(),
+            app::Screen::Command => app.close_command_preview(),
+            app::Screen::Log => app.close_log(),
             app::Screen::Compressing => {}
             app::Screen::Done | app::Screen::Error => app.set_screen(app::Screen::Review),
         },
@@ -146,6 +190,10 @@ fn handle_key(
             if key.modifiers.contains(KeyModifiers::CONTROL)
                 && matches!(app.screen(), app::Screen::Landing | app::Screen::Review) =>
         {
+            if !browser::native_dialog_available() {
+                app.open_browser();
+                return Ok(());
+            }
             let picked = session.suspend_keep_screen(pick_files)?;
             if picked.is_empty() {
                 return Ok(());
@@ -153,6 +201,30 @@ fn handle_key(
             app.add_paths(picked);
         }
 
+        KeyCode::Char('b') | KeyCode::Char('и')
+            if matches!(app.screen(), app::Screen::Landing | app::Screen::Review) =>
+        {
+            app.open_browser()
+        }
+
+        KeyCode::Char('l') | KeyCode::Char('д')
+            if matches!(
+                app.screen(),
+                app::Screen::Compressing | app::Screen::Done | app::Screen::Error
+            ) =>
+        {
+            app.open_log()
+        }
+
+        KeyCode::Char('o') | KeyCode::Char('щ')
+            if matches!(app.screen(), app::Screen::Landing | app::Screen::Review) =>
+        {
+            if let Err(e) = app.open_settings() {
+                app.set_error(format!("{e}"));
+                app.set_screen(app::Screen::Error);
+            }
+        }
+
         KeyCode::Left if matches!(app.screen(), app::Screen::Review) => app.prev_preset(),
         KeyCode::Right if matches!(app.screen(), app::Screen::Review) => app.next_preset(),
 
@@ -165,6 +237,11 @@ fn handle_key(
         KeyCode::Char('g') | KeyCode::Char('G') | KeyCode::Char('п') | KeyCode::Char('П')
             if matches!(app.screen(), app::Screen::Review) =>
         {
+            if let Some(reason) = app.gpu_unavailable() {
+                let msg = format!("gpu unavailable: {reason}");
+                app.set_status_message(Some(msg));
+                return Ok(());
+            }
             let v = app.toggle_use_gpu();
             if let Err(e) = crate::options::set_gpu(v) {
                 app.set_error(format!("{e}"));
@@ -172,6 +249,25 @@ fn handle_key(
             }
         }
 
+        KeyCode::Char('p') | KeyCode::Char('з') if matches!(app.screen(), app::Screen::Review) => {
+            app.cycle_file_preset()
+        }
+        KeyCode::Char('e') | KeyCode::Char('у') if matches!(app.screen(), app::Screen::Review) => {
+            app.cycle_file_encoder()
+        }
+        KeyCode::Char('t') | KeyCode::Char('е') if matches!(app.screen(), app::Screen::Review) => {
+            app.cycle_file_trim()
+        }
+        KeyCode::Char('r') | KeyCode::Char('к') if matches!(app.screen(), app::Screen::Review) => {
+            app.cycle_file_resolution()
+        }
+        KeyCode::Char('a') | KeyCode::Char('ф') if matches!(app.screen(), app::Screen::Review) => {
+            app.apply_overrides_to_all()
+        }
+        KeyCode::Char('c') | KeyCode::Char('с') if matches!(app.screen(), app::Screen::Review) => {
+            app.preview_command()
+        }
+
         KeyCode::Char('u') | KeyCode::Char('U')
             if matches!(app.screen(), app::Screen::Landing | app::Screen::Review) =>
         {
@@ -190,6 +286,8 @@ fn handle_key(
             std::process::exit(0);
         }
 
+        KeyCode::Enter if matches!(app.screen(), app::Screen::ResumeConfirm) => app.accept_resume(),
+
         KeyCode::Enter if matches!(app.screen(), app::Screen::Review) => {
             if app.files().is_empty() {
                 return Ok(());
@@ -236,14 +334,19 @@ fn handle_key(
             };
 
             let files: Vec<crate::exec::compress::SelectedFile> = app.files().to_vec();
-            let preset = app.preset();
-            let use_gpu = app.use_gpu();
+            let batch = Batch {
+                settings: app.encode_settings(),
+                extra: app.extra_args(),
+                batch_args: app.batch_args().clone(),
+                naming: app.naming().clone(),
+                jobs: app.jobs(),
+            };
 
             let (tx, rx) = std::sync::mpsc::channel::<app::WorkerMsg>();
             app.set_worker(rx, files.len());
 
             std::thread::spawn(move || {
-                run_worker(tx, bins.ffmpeg, files, preset, use_gpu);
+                run_worker(tx, bins.ffmpeg, files, batch);
             });
         }
 
@@ -254,11 +357,10 @@ fn handle_key(
 }
 
 fn pick_files() -> Result<Vec<std::path::PathBuf>> {
-    let exts = [
-        "mp4", "mov", "avi", "webm", "ogv", "asx", "mpeg", "m4v", "wmv", "mpg",
-    ];
+    // anything else is still taken when sniffing finds a video in it.
     Ok(rfd::FileDialog::new()
-        .add_filter("video", &exts)
+        .add_filter("video", &crate::exec::input::VIDEO_EXTENSIONS)
+        .add_filter("all files", &["*"])
         .pick_files()
         .unwrap_or_default())
 }
@@ -497,51 +599,169 @@ fn hex_val(b: u8) -> Option<u8> {
     }
 }
 
+/// Probes newly added files in the background so the review screen can show auto decisions.
+fn spawn_probes(app: &mut app::App) {
+    let Some(ffmpeg) = app.ffmpeg().map(|b| b.ffmpeg.clone()) else {
+        return;
+    };
+    let paths = app.take_probe_queue();
+    if paths.is_empty() {
+        return;
+    }
+
+    let tx = app.probe_sender();
+    std::thread::spawn(move || {
+        for path in paths {
+            let info = crate::exec::probe::probe(&ffmpeg, &path).ok();
+            if tx.send(app::ProbeMsg { path, info }).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// What every file of a tui batch shares.
+struct Batch {
+    settings: crate::exec::compress::EncodeSettings,
+    extra: crate::exec::extra_args::PresetArgs,
+    /// the part of `extra` a resumed cli batch brought along, kept in the queue.
+    batch_args: crate::exec::extra_args::ExtraArgs,
+    naming: crate::exec::compress::OutputNaming,
+    jobs: u8,
+}
+
 fn run_worker(
     tx: std::sync::mpsc::Sender<app::WorkerMsg>,
     ffmpeg: std::path::PathBuf,
     files: Vec<crate::exec::compress::SelectedFile>,
-    preset: crate::presets::Preset,
-    use_gpu: bool,
+    batch: Batch,
 ) {
+    let Batch {
+        settings,
+        extra,
+        batch_args,
+        naming,
+        jobs,
+    } = batch;
     let total = files.len();
-    for (i, f) in files.into_iter().enumerate() {
+    let inputs = files.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+    // files without a probed length just don't count towards the batch eta.
+    let lengths = files
+        .iter()
+        .map(|f| {
+            let trim = f.overrides.trim_secs.or(settings.trim_secs);
+            crate::exec::compress::output_length_us(f.info.as_ref(), trim).unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    let mut queue = crate::exec::queue::Queue::new(&settings, &inputs);
+    queue.extra_args = batch_args;
+    for (item, f) in queue.items.iter_mut().zip(&files) {
+        item.overrides = f.overrides;
+    }
+    // a failed queue write only costs the resume prompt next launch.
+    if queue.claim().unwrap_or(false) {
+        // the launch prompt handled any earlier batch; one interrupted since loses its partials.
+        if let Ok(Some(mut old)) = crate::exec::queue::Queue::load()
+            && !old.unfinished().is_empty()
+        {
+            log::warn!("discarding an interrupted batch and its partial outputs");
+            old.discard_partial();
+        }
+        let _ = queue.save();
+    } else {
+        log::warn!("another tinythis is running a batch; this one won't be offered for resume");
+    }
+
+    // one batch eta can't follow several encodes at once.
+    let live_eta = jobs == 1;
+    let queue = std::sync::Mutex::new(queue);
+    let work = files.into_iter().enumerate().collect::<Vec<_>>();
+    // the files that finished already went to the app one by one.
+    let (_, failed) = crate::exec::pool::run(jobs, work, |(i, f)| {
+        let idx = i + 1;
         let name = f
             .path
             .file_name()
             .map(|s| s.to_string_lossy().into_owned())
             .unwrap_or_else(|| f.path.to_string_lossy().into_owned());
-        let _ = tx.send(app::WorkerMsg::Started {
-            idx: i + 1,
-            total,
-            name,
-        });
+        let _ = tx.send(app::WorkerMsg::Started { idx, total, name });
+
+        let res: crate::error::Result<crate::exec::report::FileReport> = (|| {
+            let settings = f.overrides.apply(&settings);
+            let mut plan =
+                crate::exec::compress::plan_file(&ffmpeg, &f.path, &settings, &extra, f.info)?;
+            if plan.is_skip() {
+                queue.lock().unwrap().mark(i, ItemStatus::Skipped, None);
+                return Ok(crate::exec::report::FileReport::new(
+                    &f.path, None, &plan, None,
+                ));
+            }
 
-        let res: crate::error::Result<()> = (|| {
-            let out_path = crate::exec::compress::build_output_path(&f.path, preset)?;
-            let args =
-                crate::exec::compress::build_ffmpeg_args(&f.path, &out_path, preset, use_gpu);
-            let mut args = args;
-            args.extend([
-                std::ffi::OsString::from("-progress"),
-                std::ffi::OsString::from("pipe:1"),
-            ]);
+            let out_path = queue.lock().unwrap().start(i, |taken| {
+                crate::exec::compress::build_output_path_except(
+                    &f.path,
+                    plan.settings.preset,
+                    plan.settings.container,
+                    &naming,
+                    taken,
+                )
+            })?;
+            plan.log = crate::exec::joblog::path_for(&f.path).ok();
+            if let Some(path) = plan.log.clone() {
+                let _ = tx.send(app::WorkerMsg::Log { idx, path });
+            }
 
             let tx_progress = tx.clone();
-            crate::exec::compress::run_ffmpeg(&ffmpeg, &args, move |percent| {
-                let _ = tx_progress.send(app::WorkerMsg::Progress { percent });
-            })?;
-            Ok(())
+            let later_us = lengths[i + 1..].iter().sum::<u64>();
+            crate::exec::compress::compress_file(
+                &ffmpeg,
+                &f.path,
+                &out_path,
+                &mut plan,
+                move |stats| {
+                    let _ = tx_progress.send(app::WorkerMsg::Progress {
+                        idx,
+                        stats: stats.clone(),
+                        batch_eta_secs: live_eta
+                            .then(|| crate::exec::progress::batch_eta_secs(stats, later_us))
+                            .flatten(),
+                    });
+                },
+            )?;
+            let scores = crate::exec::compress::measure_output(&ffmpeg, &f.path, &out_path, &plan)?;
+            crate::exec::split::split_output(
+                &ffmpeg,
+                &f.path,
+                &out_path,
+                naming.on_existing,
+                &mut plan,
+            )?;
+            queue.lock().unwrap().mark(i, ItemStatus::Done, None);
+            Ok(crate::exec::report::FileReport::new(
+                &f.path,
+                Some(&out_path),
+                &plan,
+                scores,
+            ))
         })();
 
-        if let Err(e) = res {
-            let _ = tx.send(app::WorkerMsg::Error {
-                message: worker_error_message(&f.path, &e),
-            });
-            return;
+        match res {
+            Ok(report) => {
+                let _ = tx.send(app::WorkerMsg::FileDone { idx, report });
+                Ok(())
+            }
+            Err(e) => {
+                queue.lock().unwrap().mark(i, ItemStatus::Failed, None);
+                Err(worker_error_message(&f.path, &e))
+            }
         }
+    });
+    if let Some(message) = failed {
+        let _ = tx.send(app::WorkerMsg::Error { message });
+        return;
     }
 
+    let _ = queue.lock().unwrap().remove();
     let _ = tx.send(app::WorkerMsg::Done);
 }
 
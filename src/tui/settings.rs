@@ -0,0 +1,545 @@
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use crate::exec::compress::{OnExisting, OutputNaming};
+use crate::exec::container::Container;
+use crate::exec::gif::Dither;
+use crate::exec::metrics::QualityThreshold;
+use crate::options::Options;
+use crate::presets::Preset;
+use crate::presets::encoder::{Codec, EncoderPref};
+
+use super::app::Screen;
+
+/// One row of the settings screen; each maps to an options.toml entry.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Field {
+    Preset,
+    Gpu,
+    Encoder,
+    Codec,
+    CpuFallback,
+    Container,
+    OutputDir,
+    OutputTemplate,
+    Jobs,
+    OnExisting,
+    SniffInputs,
+    UpdateCheck,
+    AutoMinSavings,
+    GifFps,
+    GifWidth,
+    GifPlays,
+    GifDither,
+    GifMaxSize,
+    Measure,
+    MinQuality,
+    PreserveMetadata,
+    PreserveChapters,
+    PreserveCreationTime,
+    PreserveRotation,
+    PreserveSubtitles,
+    PreserveTimestamps,
+    HwDecodeQuality,
+    HwDecodeBalanced,
+    HwDecodeSpeed,
+}
+
+pub const FIELDS: [Field; 29] = [
+    Field::Preset,
+    Field::Gpu,
+    Field::Encoder,
+    Field::Codec,
+    Field::CpuFallback,
+    Field::Container,
+    Field::OutputDir,
+    Field::OutputTemplate,
+    Field::Jobs,
+    Field::OnExisting,
+    Field::SniffInputs,
+    Field::UpdateCheck,
+    Field::AutoMinSavings,
+    Field::GifFps,
+    Field::GifWidth,
+    Field::GifPlays,
+    Field::GifDither,
+    Field::GifMaxSize,
+    Field::Measure,
+    Field::MinQuality,
+    Field::PreserveMetadata,
+    Field::PreserveChapters,
+    Field::PreserveCreationTime,
+    Field::PreserveRotation,
+    Field::PreserveSubtitles,
+    Field::PreserveTimestamps,
+    Field::HwDecodeQuality,
+    Field::HwDecodeBalanced,
+    Field::HwDecodeSpeed,
+];
+
+const PRESETS: [Preset; 7] = [
+    Preset::Quality,
+    Preset::Balanced,
+    Preset::Speed,
+    Preset::Remux,
+    Preset::Auto,
+    Preset::Gif,
+    Preset::Webp,
+];
+
+const CODECS: [Codec; 3] = [Codec::H264, Codec::Hevc, Codec::Av1];
+
+const CONTAINERS: [Container; 4] = [
+    Container::Mp4,
+    Container::Mkv,
+    Container::Webm,
+    Container::Mov,
+];
+
+const ON_EXISTING: [OnExisting; 2] = [OnExisting::Rename, OnExisting::Overwrite];
+
+const DITHERS: [Dither; 4] = [
+    Dither::Bayer,
+    Dither::FloydSteinberg,
+    Dither::Sierra,
+    Dither::None,
+];
+
+impl Field {
+    pub fn label(self) -> &'static str {
+        match self {
+            Field::Preset => "default preset",
+            Field::Gpu => "use gpu",
+            Field::Encoder => "encoder",
+            Field::Codec => "codec",
+            Field::CpuFallback => "cpu fallback",
+            Field::Container => "container",
+            Field::OutputDir => "output folder",
+            Field::OutputTemplate => "output name",
+            Field::Jobs => "files at once",
+            Field::OnExisting => "if output exists",
+            Field::SniffInputs => "probe unknown files",
+            Field::UpdateCheck => "check for updates",
+            Field::AutoMinSavings => "auto: min savings %",
+            Field::GifFps => "gif: fps",
+            Field::GifWidth => "gif: max width",
+            Field::GifPlays => "gif: plays",
+            Field::GifDither => "gif: dither",
+            Field::GifMaxSize => "gif: max size",
+            Field::Measure => "measure quality",
+            Field::MinQuality => "min quality",
+            Field::PreserveMetadata => "keep metadata",
+            Field::PreserveChapters => "keep chapters",
+            Field::PreserveCreationTime => "keep creation time",
+            Field::PreserveRotation => "keep rotation",
+            Field::PreserveSubtitles => "keep subtitles",
+            Field::PreserveTimestamps => "keep file timestamps",
+            Field::HwDecodeQuality => "gpu decode: quality",
+            Field::HwDecodeBalanced => "gpu decode: balanced",
+            Field::HwDecodeSpeed => "gpu decode: speed",
+        }
+    }
+
+    pub fn value(self, o: &Options) -> String {
+        let on_off = |v: bool| if v { "on" } else { "off" }.to_string();
+        match self {
+            Field::Preset => o.preset.as_str().to_string(),
+            Field::Gpu => on_off(o.gpu),
+            Field::Encoder => o
+                .encoder
+                .map(|e| e.to_string())
+                .unwrap_or_else(|| "any gpu".to_string()),
+            Field::Codec => o.codec.as_str().to_string(),
+            Field::CpuFallback => on_off(o.cpu_fallback),
+            Field::Container => o.container.as_str().to_string(),
+            Field::OutputDir => o
+                .output
+                .dir
+                .as_ref()
+                .map(|d| d.display().to_string())
+                .unwrap_or_else(|| "next to the input".to_string()),
+            Field::OutputTemplate => format!("{}.{}", o.output.template, o.container.as_str()),
+            Field::Jobs => o.jobs.to_string(),
+            Field::OnExisting => o.output.on_existing.as_str().to_string(),
+            Field::SniffInputs => on_off(o.sniff_inputs),
+            Field::UpdateCheck => on_off(o.update_check),
+            Field::AutoMinSavings => o.auto_min_savings.to_string(),
+            Field::GifFps => o.gif.fps.to_string(),
+            Field::GifWidth => format!("{} px", o.gif.width),
+            Field::GifPlays if o.gif.plays == 0 => "forever".to_string(),
+            Field::GifPlays => o.gif.plays.to_string(),
+            Field::GifDither => o.gif.dither.as_str().to_string(),
+            Field::GifMaxSize => o
+                .gif
+                .max_bytes
+                .map(crate::exec::report::size_text)
+                .unwrap_or_else(|| "none".to_string()),
+            Field::Measure => on_off(o.measure),
+            Field::MinQuality => o
+                .min_quality
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "none".to_string()),
+            Field::PreserveMetadata => on_off(o.preserve.metadata),
+            Field::PreserveChapters => on_off(o.preserve.chapters),
+            Field::PreserveCreationTime => on_off(o.preserve.creation_time),
+            Field::PreserveRotation => on_off(o.preserve.rotation),
+            Field::PreserveSubtitles => on_off(o.preserve.subtitles),
+            Field::PreserveTimestamps => on_off(o.preserve.timestamps),
+            Field::HwDecodeQuality => on_off(o.hw_decode.quality),
+            Field::HwDecodeBalanced => on_off(o.hw_decode.balanced),
+            Field::HwDecodeSpeed => on_off(o.hw_decode.speed),
+        }
+    }
+
+    /// Typed in rather than picked from a fixed list.
+    pub fn is_text(self) -> bool {
+        matches!(
+            self,
+            Field::Encoder
+                | Field::OutputDir
+                | Field::OutputTemplate
+                | Field::AutoMinSavings
+                | Field::GifFps
+                | Field::GifWidth
+                | Field::GifPlays
+                | Field::GifMaxSize
+                | Field::MinQuality
+        )
+    }
+
+    /// What the editor starts with; empty where an empty entry means "unset".
+    fn text(self, o: &Options) -> String {
+        match self {
+            Field::Encoder => o.encoder.map(|e| e.to_string()).unwrap_or_default(),
+            Field::OutputDir => o
+                .output
+                .dir
+                .as_ref()
+                .map(|d| d.display().to_string())
+                .unwrap_or_default(),
+            Field::OutputTemplate => o.output.template.clone(),
+            Field::AutoMinSavings => o.auto_min_savings.to_string(),
+            Field::GifFps => o.gif.fps.to_string(),
+            Field::GifWidth => o.gif.width.to_string(),
+            Field::GifPlays => o.gif.plays.to_string(),
+            Field::GifMaxSize => o
+                .gif
+                .max_bytes
+                .map(crate::exec::report::size_text)
+                .unwrap_or_default(),
+            Field::MinQuality => o.min_quality.map(|t| t.to_string()).unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+
+    /// Flips a switch or steps through the choices; text fields are left alone.
+    pub fn cycle(self, o: &mut Options, forward: bool) {
+        match self {
+            Field::Preset => o.preset = step(&PRESETS, o.preset, forward),
+            Field::Codec => o.codec = step(&CODECS, o.codec, forward),
+            Field::Container => o.container = step(&CONTAINERS, o.container, forward),
+            Field::OnExisting => {
+                o.output.on_existing = step(&ON_EXISTING, o.output.on_existing, forward)
+            }
+            Field::GifDither => o.gif.dither = step(&DITHERS, o.gif.dither, forward),
+            Field::Jobs => {
+                let all = (1..=crate::exec::pool::MAX_JOBS).collect::<Vec<_>>();
+                o.jobs = step(&all, o.jobs, forward);
+            }
+            Field::Gpu => o.gpu = !o.gpu,
+            Field::CpuFallback => o.cpu_fallback = !o.cpu_fallback,
+            Field::SniffInputs => o.sniff_inputs = !o.sniff_inputs,
+            Field::UpdateCheck => o.update_check = !o.update_check,
+            Field::Measure => o.measure = !o.measure,
+            Field::PreserveMetadata => o.preserve.metadata = !o.preserve.metadata,
+            Field::PreserveChapters => o.preserve.chapters = !o.preserve.chapters,
+            Field::PreserveCreationTime => o.preserve.creation_time = !o.preserve.creation_time,
+            Field::PreserveRotation => o.preserve.rotation = !o.preserve.rotation,
+            Field::PreserveSubtitles => o.preserve.subtitles = !o.preserve.subtitles,
+            Field::PreserveTimestamps => o.preserve.timestamps = !o.preserve.timestamps,
+            Field::HwDecodeQuality => o.hw_decode.quality = !o.hw_decode.quality,
+            Field::HwDecodeBalanced => o.hw_decode.balanced = !o.hw_decode.balanced,
+            Field::HwDecodeSpeed => o.hw_decode.speed = !o.hw_decode.speed,
+            Field::Encoder
+            | Field::OutputDir
+            | Field::OutputTemplate
+            | Field::AutoMinSavings
+            | Field::GifFps
+            | Field::GifWidth
+            | Field::GifPlays
+            | Field::GifMaxSize
+            | Field::MinQuality => {}
+        }
+    }
+
+    /// Checks typed text and stores it, with the same rules options.toml is held to.
+    pub fn set_text(self, o: &mut Options, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        match self {
+            Field::Encoder if text.is_empty() => o.encoder = None,
+            Field::Encoder => o.encoder = Some(EncoderPref::parse(text)?),
+            Field::OutputDir if text.is_empty() => o.output.dir = None,
+            Field::OutputDir => {
+                if text.contains(['\'', '#']) {
+                    return Err("can't contain ' or #".to_string());
+                }
+                if !Path::new(text).is_dir() {
+                    return Err(format!("{text} is not a folder"));
+                }
+                o.output.dir = Some(PathBuf::from(text));
+            }
+            Field::OutputTemplate => {
+                let template = if text.is_empty() {
+                    OutputNaming::DEFAULT_TEMPLATE
+                } else {
+                    text
+                };
+                OutputNaming::validate_template(template)?;
+                o.output.template = template.to_string();
+            }
+            Field::AutoMinSavings => {
+                o.auto_min_savings = text
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|v| *v <= 100)
+                    .ok_or_else(|| "a percentage (0-100)".to_string())?;
+            }
+            Field::GifFps => {
+                o.gif.fps = text
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|v| (1..=crate::exec::gif::MAX_FPS).contains(v))
+                    .ok_or_else(|| format!("1-{} fps", crate::exec::gif::MAX_FPS))?;
+            }
+            Field::GifWidth => {
+                o.gif.width = text
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|v| *v >= crate::exec::gif::SMALLEST_WIDTH)
+                    .ok_or_else(|| format!("at least {} px", crate::exec::gif::SMALLEST_WIDTH))?;
+            }
+            Field::GifPlays => {
+                o.gif.plays = text
+                    .parse::<u32>()
+                    .map_err(|_| "a number of plays (0 loops forever)".to_string())?;
+            }
+            Field::GifMaxSize if text.is_empty() => o.gif.max_bytes = None,
+            Field::GifMaxSize => {
+                o.gif.max_bytes = Some(
+                    crate::exec::report::parse_size(text)
+                        .ok_or_else(|| "a size like 8M or 500k".to_string())?,
+                );
+            }
+            Field::MinQuality if text.is_empty() => o.min_quality = None,
+            Field::MinQuality => o.min_quality = Some(QualityThreshold::parse(text)?),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn step<T: Copy + PartialEq>(all: &[T], current: T, forward: bool) -> T {
+    let i = all.iter().position(|v| *v == current).unwrap_or(0);
+    let n = all.len();
+    if forward {
+        all[(i + 1) % n]
+    } else {
+        all[(i + n - 1) % n]
+    }
+}
+
+/// A change the settings screen wants written to options.toml.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Cycle(Field, bool),
+    Text(Field, String),
+}
+
+impl Edit {
+    pub fn field(&self) -> Field {
+        match self {
+            Edit::Cycle(f, _) | Edit::Text(f, _) => *f,
+        }
+    }
+
+    pub fn apply(&self, o: &mut Options) {
+        match self {
+            Edit::Cycle(f, forward) => f.cycle(o, *forward),
+            // already checked against the same options; a failure here changes nothing.
+            Edit::Text(f, text) => {
+                let _ = f.set_text(o, text);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SettingsState {
+    /// options as last saved, shown on every row.
+    pub opts: Options,
+    pub selected: usize,
+    /// text being typed into the selected row.
+    pub editing: Option<String>,
+    /// why the last edit was refused or couldn't be saved.
+    pub message: Option<String>,
+    /// where esc goes back to.
+    pub from: Screen,
+}
+
+impl SettingsState {
+    pub fn new(opts: Options, from: Screen) -> Self {
+        Self {
+            opts,
+            selected: 0,
+            editing: None,
+            message: None,
+            from,
+        }
+    }
+
+    pub fn field(&self) -> Field {
+        FIELDS[self.selected]
+    }
+
+    /// Moves the selection or edits the selected row; returns an edit once it's ready to save.
+    pub fn on_key(&mut self, key: KeyEvent) -> Option<Edit> {
+        let field = self.field();
+        if let Some(buf) = self.editing.as_mut() {
+            match key.code {
+                KeyCode::Char(c) => buf.push(c),
+                KeyCode::Backspace => {
+                    buf.pop();
+                }
+                KeyCode::Esc => {
+                    self.editing = None;
+                    self.message = None;
+                }
+                KeyCode::Enter => {
+                    let text = buf.clone();
+                    let mut check = self.opts.clone();
+                    match field.set_text(&mut check, &text) {
+                        Ok(()) => {
+                            self.editing = None;
+                            self.message = None;
+                            return Some(Edit::Text(field, text));
+                        }
+                        Err(e) => self.message = Some(e),
+                    }
+                }
+                _ => {}
+            }
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Up => self.select((self.selected + FIELDS.len() - 1) % FIELDS.len()),
+            KeyCode::Down => self.select((self.selected + 1) % FIELDS.len()),
+            KeyCode::Enter if field.is_text() => self.editing = Some(field.text(&self.opts)),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char(' ') if !field.is_text() => {
+                return Some(Edit::Cycle(field, true));
+            }
+            KeyCode::Left if !field.is_text() => return Some(Edit::Cycle(field, false)),
+            _ => {}
+        }
+        None
+    }
+
+    fn select(&mut self, idx: usize) {
+        self.selected = idx;
+        self.message = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crossterm::event::KeyModifiers;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn text_fields_validate_like_options_toml() {
+        let mut o = Options::default();
+
+        assert!(Field::AutoMinSavings.set_text(&mut o, "101").is_err());
+        Field::AutoMinSavings.set_text(&mut o, " 35 ").unwrap();
+        assert_eq!(o.auto_min_savings, 35);
+
+        assert!(Field::OutputTemplate.set_text(&mut o, "small").is_err());
+        assert!(
+            Field::OutputTemplate
+                .set_text(&mut o, "out/{stem}")
+                .is_err()
+        );
+        Field::OutputTemplate
+            .set_text(&mut o, "{stem}-small")
+            .unwrap();
+        assert_eq!(o.output.template, "{stem}-small");
+        Field::OutputTemplate.set_text(&mut o, "").unwrap();
+        assert_eq!(o.output.template, OutputNaming::DEFAULT_TEMPLATE);
+
+        assert!(Field::Encoder.set_text(&mut o, "potato").is_err());
+        Field::Encoder.set_text(&mut o, "cpu").unwrap();
+        assert!(o.encoder.is_some());
+        Field::Encoder.set_text(&mut o, "").unwrap();
+        assert_eq!(o.encoder, None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        assert!(
+            Field::OutputDir
+                .set_text(&mut o, &missing.display().to_string())
+                .is_err()
+        );
+        Field::OutputDir
+            .set_text(&mut o, &dir.path().display().to_string())
+            .unwrap();
+        assert_eq!(o.output.dir.as_deref(), Some(dir.path()));
+
+        assert!(Field::GifFps.set_text(&mut o, "60").is_err());
+        Field::GifFps.set_text(&mut o, "12").unwrap();
+        assert_eq!(o.gif.fps, 12);
+        assert!(Field::GifMaxSize.set_text(&mut o, "huge").is_err());
+        Field::GifMaxSize.set_text(&mut o, "2.5M").unwrap();
+        assert_eq!(Field::GifMaxSize.value(&o), "2560k");
+        Field::GifMaxSize.set_text(&mut o, "").unwrap();
+        assert_eq!(o.gif.max_bytes, None);
+    }
+
+    #[test]
+    fn editing_keeps_bad_input_open_with_a_message() {
+        let mut s = SettingsState::new(Options::default(), Screen::Landing);
+        s.selected = FIELDS
+            .iter()
+            .position(|f| *f == Field::AutoMinSavings)
+            .unwrap();
+
+        assert_eq!(s.on_key(key(KeyCode::Enter)), None);
+        assert_eq!(s.editing.as_deref(), Some("20"));
+        s.on_key(key(KeyCode::Char('0')));
+        assert_eq!(s.on_key(key(KeyCode::Enter)), None);
+        assert!(s.message.is_some());
+        assert_eq!(s.editing.as_deref(), Some("200"));
+
+        s.on_key(key(KeyCode::Backspace));
+        assert_eq!(
+            s.on_key(key(KeyCode::Enter)),
+            Some(Edit::Text(Field::AutoMinSavings, "20".to_string()))
+        );
+        assert_eq!(s.editing, None);
+        assert_eq!(s.message, None);
+
+        s.selected = 0;
+        assert_eq!(
+            s.on_key(key(KeyCode::Left)),
+            Some(Edit::Cycle(Field::Preset, false))
+        );
+        let mut o = Options::default();
+        Edit::Cycle(Field::Preset, false).apply(&mut o);
+        assert_eq!(o.preset, Preset::Quality);
+    }
+}
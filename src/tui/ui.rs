@@ -4,10 +4,15 @@ use ratatui::style::{Color, Style};
 use ratatui::text::{Line, Span, Text};
 use ratatui::widgets::{Paragraph, Wrap};
 
+use crate::exec::compress::FileOverrides;
+use crate::exec::progress::format_clock;
+use crate::exec::report::format_bytes;
 use crate::presets::Preset;
 
 use super::app::App;
 use super::app::Screen;
+use super::browser::EntryKind;
+use super::settings::FIELDS;
 
 const TINYTHIS_ASCII: &str = r#"
   __  _           __  __   _     __
@@ -22,6 +27,11 @@ pub fn draw(frame: &mut Frame, _app: &App) {
         Screen::Landing => draw_landing(frame, _app),
         Screen::Review => draw_review(frame, _app),
         Screen::UpdateConfirm => draw_update_confirm(frame, _app),
+        Screen::ResumeConfirm => draw_resume_confirm(frame, _app),
+        Screen::Settings => draw_settings(frame, _app),
+        Screen::Browser => draw_browser(frame, _app),
+        Screen::Command => draw_command(frame, _app),
+        Screen::Log => draw_log(frame, _app),
         Screen::Compressing => draw_compressing(frame, _app),
         Screen::Done => draw_done(frame, _app),
         Screen::Error => draw_error(frame, _app),
@@ -100,15 +110,18 @@ fn draw_landing(frame: &mut Frame, app: &App) {
     let mut lines: Vec<Line> = ascii_lines.collect();
 
     lines.push(Line::styled(
-        "select files (ctrl+o: dialog)",
+        "select files (ctrl+o: dialog, b: browse)",
         Style::default().fg(Color::White),
     ));
+    for chunk in crate::exec::input::VIDEO_EXTENSIONS.chunks(9) {
+        let exts = chunk.iter().map(|e| format!(".{e}")).collect::<Vec<_>>();
+        lines.push(Line::styled(
+            exts.join(", "),
+            Style::default().fg(Color::Gray),
+        ));
+    }
     lines.push(Line::styled(
-        ".mp4, .mov, .avi, .webm, .ogv, .asx,",
-        Style::default().fg(Color::Gray),
-    ));
-    lines.push(Line::styled(
-        ".mpeg, .m4v, .wmv, .mpg",
+        "or any file ffmpeg finds a video in",
         Style::default().fg(Color::Gray),
     ));
 
@@ -134,7 +147,7 @@ fn draw_landing(frame: &mut Frame, app: &App) {
 fn draw_review(frame: &mut Frame, app: &App) {
     let area = frame.area();
 
-    let reserved = 9u16;
+    let reserved = 12u16;
     let max_files = area.height.saturating_sub(reserved).max(1) as usize;
 
     let mut lines = Vec::<Line>::new();
@@ -165,17 +178,29 @@ fn draw_review(frame: &mut Frame, app: &App) {
                 .map(|s| s.to_string_lossy().into_owned())
                 .unwrap_or_else(|| f.path.to_string_lossy().into_owned());
 
-            if selected == Some(idx) {
-                lines.push(Line::styled(
-                    format!("> {name} ({})", format_bytes(f.size_bytes)),
-                    Style::default().fg(Color::Cyan),
-                ));
+            let (marker, color) = if selected == Some(idx) {
+                (">", Color::Cyan)
             } else {
-                lines.push(Line::styled(
-                    format!("- {name} ({})", format_bytes(f.size_bytes)),
-                    Style::default().fg(Color::White),
+                ("-", Color::White)
+            };
+            let mut spans = vec![Span::styled(
+                format!("{marker} {name} ({})", format_bytes(f.size_bytes)),
+                Style::default().fg(color),
+            )];
+            if !f.overrides.is_empty() {
+                spans.push(Span::styled(
+                    format!(" [{}]", overrides_label(&f.overrides)),
+                    Style::default().fg(Color::Yellow),
                 ));
             }
+            if f.overrides.preset.unwrap_or(app.preset()) == Preset::Auto {
+                let decision = match app.auto_decision(f) {
+                    Some(d) => format!(" \u{2192} {}: {}", d.label(), d.reason),
+                    None => " \u{2192} probing...".to_string(),
+                };
+                spans.push(Span::styled(decision, Style::default().fg(Color::Gray)));
+            }
+            lines.push(Line::from(spans));
         }
 
         if total > end {
@@ -190,6 +215,12 @@ fn draw_review(frame: &mut Frame, app: &App) {
         "- add files (ctrl+o)",
         Style::default().fg(Color::Gray),
     ));
+    if app.review_selected().is_some() {
+        lines.push(Line::styled(
+            "this file: p preset, e encoder, t trim, r resolution, a apply to all, c command",
+            Style::default().fg(Color::Gray),
+        ));
+    }
 
     if let Some(u) = app.update() {
         lines.push(Line::styled(
@@ -220,10 +251,22 @@ fn draw_review(frame: &mut Frame, app: &App) {
 
     lines.push(Line::raw(""));
     let gpu = if app.use_gpu() { "[x]" } else { "[ ]" };
-    lines.push(Line::styled(
-        format!("{gpu} use gpu (g)"),
-        Style::default().fg(Color::White),
-    ));
+    if app.gpu_unavailable().is_some() {
+        lines.push(Line::styled(
+            format!("{gpu} use gpu (unavailable)"),
+            Style::default().fg(Color::DarkGray),
+        ));
+    } else if app.use_gpu() {
+        lines.push(Line::styled(
+            format!("{gpu} use gpu: {} (g)", app.encoder().ffmpeg_name()),
+            Style::default().fg(Color::White),
+        ));
+    } else {
+        lines.push(Line::styled(
+            format!("{gpu} use gpu (g)"),
+            Style::default().fg(Color::White),
+        ));
+    }
 
     lines.push(Line::raw(""));
     lines.push(Line::styled(
@@ -231,9 +274,36 @@ fn draw_review(frame: &mut Frame, app: &App) {
         Style::default().fg(Color::White),
     ));
 
+    if let Some(status) = app.status() {
+        lines.push(Line::raw(""));
+        lines.push(Line::styled(status, Style::default().fg(Color::Gray)));
+    }
+
     render_top_left(frame, lines);
 }
 
+/// Short description of a file's own settings, e.g. `speed, h264_qsv, first 30s, 720p`.
+fn overrides_label(o: &FileOverrides) -> String {
+    let mut parts = Vec::<String>::new();
+    if let Some(p) = o.preset {
+        parts.push(p.as_str().to_string());
+    }
+    if let Some(e) = o.encoder {
+        parts.push(e.ffmpeg_name().to_string());
+    }
+    if let Some(secs) = o.trim_secs {
+        parts.push(if secs % 60 == 0 {
+            format!("first {}m", secs / 60)
+        } else {
+            format!("first {secs}s")
+        });
+    }
+    if let Some(h) = o.max_height {
+        parts.push(format!("{h}p"));
+    }
+    parts.join(", ")
+}
+
 fn draw_update_confirm(frame: &mut Frame, app: &App) {
     let mut lines = Vec::<Line>::new();
     if let Some(u) = app.update() {
@@ -260,20 +330,214 @@ fn draw_update_confirm(frame: &mut Frame, app: &App) {
     render_centered(frame, lines);
 }
 
+fn draw_resume_confirm(frame: &mut Frame, app: &App) {
+    let mut lines = Vec::<Line>::new();
+    if let Some(q) = app.resume_offer() {
+        let left = q.unfinished().len();
+        lines.push(Line::styled(
+            format!(
+                "unfinished batch: {left} of {} file{} left ({})",
+                q.items.len(),
+                if q.items.len() == 1 { "" } else { "s" },
+                q.preset.as_str()
+            ),
+            Style::default().fg(Color::White),
+        ));
+        lines.push(Line::raw(""));
+        lines.push(Line::styled(
+            "resume? (enter: yes, esc: no)",
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    render_centered(frame, lines);
+}
+
+fn draw_settings(frame: &mut Frame, app: &App) {
+    let Some(s) = app.settings() else {
+        return;
+    };
+    let mut lines = vec![
+        Line::styled("settings", Style::default().fg(Color::White)),
+        Line::raw(""),
+    ];
+
+    let width = FIELDS.iter().map(|f| f.label().len()).max().unwrap_or(0);
+    for (idx, field) in FIELDS.iter().enumerate() {
+        let selected = idx == s.selected;
+        let (marker, color) = if selected {
+            (">", Color::Cyan)
+        } else {
+            ("-", Color::White)
+        };
+        let value = match &s.editing {
+            Some(buf) if selected => format!("{buf}_"),
+            _ => field.value(&s.opts),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{marker} {:<width$}  ", field.label()),
+                Style::default().fg(color),
+            ),
+            Span::styled(value, Style::default().fg(Color::Gray)),
+        ]));
+        if selected && let Some(msg) = &s.message {
+            lines.push(Line::styled(
+                format!("  {msg}"),
+                Style::default().fg(Color::Red),
+            ));
+        }
+    }
+
+    lines.push(Line::raw(""));
+    let help = if s.editing.is_some() {
+        "enter: save, esc: cancel"
+    } else if s.field().is_text() {
+        "up/down: select, enter: edit, esc: back"
+    } else {
+        "up/down: select, left/right/enter: change, esc: back"
+    };
+    lines.push(Line::styled(help, Style::default().fg(Color::Gray)));
+    lines.push(Line::styled(
+        "saved to options.toml as you go",
+        Style::default().fg(Color::DarkGray),
+    ));
+
+    render_top_left(frame, lines);
+}
+
+fn draw_browser(frame: &mut Frame, app: &App) {
+    let Some(b) = app.browser() else {
+        return;
+    };
+    let area = frame.area();
+    let mut lines = vec![
+        Line::styled(
+            b.dir.display().to_string(),
+            Style::default().fg(Color::White),
+        ),
+        Line::raw(""),
+    ];
+
+    // header, help, recent folders and a status line around the listing.
+    let reserved = 8u16;
+    let visible = area.height.saturating_sub(reserved).max(1) as usize;
+    let start = b.cursor.saturating_sub(visible.saturating_sub(1));
+    if b.entries.is_empty() {
+        lines.push(Line::styled(
+            "no folders or videos here",
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    for (idx, e) in b.entries.iter().enumerate().skip(start).take(visible) {
+        let (marker, color) = if idx == b.cursor {
+            (">", Color::Cyan)
+        } else {
+            (" ", Color::White)
+        };
+        let check = if b.picked.contains(&e.path) {
+            "[x]"
+        } else if e.kind == EntryKind::Parent {
+            "   "
+        } else {
+            "[ ]"
+        };
+        let mut spans = vec![Span::styled(
+            format!("{marker} {check} "),
+            Style::default().fg(color),
+        )];
+        match e.kind {
+            EntryKind::Parent | EntryKind::Dir => {
+                spans.push(Span::styled(
+                    format!("{}/", e.name),
+                    Style::default().fg(Color::Blue),
+                ));
+            }
+            EntryKind::Video { size } => {
+                spans.push(Span::styled(e.name.clone(), Style::default().fg(color)));
+                let duration = match b.durations.get(&e.path) {
+                    Some(Some(us)) => format!(", {}", format_clock(us / 1_000_000)),
+                    _ => String::new(),
+                };
+                spans.push(Span::styled(
+                    format!("  {}{duration}", format_bytes(size)),
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+        }
+        lines.push(Line::from(spans));
+    }
+
+    lines.push(Line::raw(""));
+    if !b.recent.is_empty() {
+        let recent = b
+            .recent
+            .iter()
+            .enumerate()
+            .map(|(i, d)| {
+                let name = d.file_name().unwrap_or(d.as_os_str()).to_string_lossy();
+                format!("{}: {name}", i + 1)
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        lines.push(Line::styled(
+            format!("recent  {recent}"),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    lines.push(Line::styled(
+        "space: pick, a: pick all videos here, enter: add (or open folder), right/left: in/out, esc: back",
+        Style::default().fg(Color::Gray),
+    ));
+    if let Some(msg) = &b.message {
+        lines.push(Line::styled(msg.clone(), Style::default().fg(Color::Red)));
+    } else if !b.picked.is_empty() {
+        lines.push(Line::styled(
+            format!("{} picked", b.picked.len()),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+
+    render_top_left(frame, lines);
+}
+
 fn draw_compressing(frame: &mut Frame, app: &App) {
     let mut lines = Vec::<Line>::new();
     let spinner = dots_spinner_frame(app.progress().map(|p| p.spinner_tick).unwrap_or(0));
 
     if let Some(p) = app.progress() {
-        let pct = p.percent.map(|v| format!(" {v}%")).unwrap_or_default();
+        let pct = p
+            .stats
+            .as_ref()
+            .and_then(|s| s.percent)
+            .map(|v| format!(" {v}%"))
+            .unwrap_or_default();
         lines.push(Line::styled(
             format!("{spinner} compressing ({}/{}){pct}", p.idx, p.total),
             Style::default().fg(Color::White),
         ));
+        let others = if p.others > 0 {
+            format!("  (+{} more at once)", p.others)
+        } else {
+            String::new()
+        };
         lines.push(Line::styled(
-            p.current_name.clone(),
+            format!("{}{others}", p.current_name),
             Style::default().fg(Color::Gray),
         ));
+        if let Some(stats) = &p.stats {
+            lines.push(Line::styled(
+                stats.stats_line(),
+                Style::default().fg(Color::Gray),
+            ));
+        }
+        if p.total > 1
+            && let Some(eta) = p.batch_eta_secs
+        {
+            lines.push(Line::styled(
+                format!("batch eta {}", format_clock(eta)),
+                Style::default().fg(Color::Gray),
+            ));
+        }
     } else {
         lines.push(Line::styled(
             format!("{spinner} compressing"),
@@ -286,6 +550,79 @@ fn draw_compressing(frame: &mut Frame, app: &App) {
         format!("mode: {}", app.preset().as_str()),
         Style::default().fg(Color::Gray),
     ));
+    if app.job_log().is_some() {
+        lines.push(Line::styled(
+            "l: ffmpeg log",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    render_top_left(frame, lines);
+}
+
+fn draw_command(frame: &mut Frame, app: &App) {
+    let lines = vec![
+        Line::styled("ffmpeg command", Style::default().fg(Color::White)),
+        Line::raw(""),
+        Line::styled(
+            app.command_preview().unwrap_or_default().to_string(),
+            Style::default().fg(Color::Gray),
+        ),
+        Line::raw(""),
+        Line::styled("esc to go back", Style::default().fg(Color::Gray)),
+    ];
+    render_top_left(frame, lines);
+}
+
+fn draw_log(frame: &mut Frame, app: &App) {
+    let Some(l) = app.log_view() else {
+        return;
+    };
+    let area = frame.area();
+    let mut lines = vec![
+        Line::styled(
+            l.path.display().to_string(),
+            Style::default().fg(Color::White),
+        ),
+        Line::raw(""),
+    ];
+
+    // header and two footer lines around the log.
+    let visible = area.height.saturating_sub(5).max(1) as usize;
+    let query = l.query.as_ref().map(|q| q.to_lowercase());
+    for line in l.lines.iter().skip(l.scroll).take(visible) {
+        let hit = query
+            .as_ref()
+            .is_some_and(|q| line.to_lowercase().contains(q.as_str()));
+        let color = if hit { Color::Yellow } else { Color::Gray };
+        lines.push(Line::styled(line.clone(), Style::default().fg(color)));
+    }
+    if l.lines.is_empty() {
+        lines.push(Line::styled(
+            "(empty)",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    lines.push(Line::raw(""));
+    if let Some(search) = &l.search {
+        lines.push(Line::styled(
+            format!("/{search}_"),
+            Style::default().fg(Color::Cyan),
+        ));
+    } else {
+        lines.push(Line::styled(
+            format!(
+                "{}/{}  /: search, n/N: next/prev, r: reload, esc: back",
+                (l.scroll + 1).min(l.lines.len()),
+                l.lines.len()
+            ),
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    if let Some(msg) = &l.message {
+        lines.push(Line::styled(msg.clone(), Style::default().fg(Color::Red)));
+    }
 
     render_top_left(frame, lines);
 }
@@ -294,8 +631,23 @@ fn draw_done(frame: &mut Frame, app: &App) {
     let mut lines = Vec::<Line>::new();
     lines.push(Line::styled("done", Style::default().fg(Color::White)));
     lines.push(Line::raw(""));
+    for r in app.reports() {
+        let color = if r.is_below_threshold() {
+            Color::Red
+        } else {
+            Color::Gray
+        };
+        lines.push(Line::styled(r.summary_line(), Style::default().fg(color)));
+    }
+    if !app.reports().is_empty() {
+        lines.push(Line::raw(""));
+    }
     lines.push(Line::styled(
-        "esc to go back",
+        if app.job_log().is_some() {
+            "esc to go back, l: ffmpeg log"
+        } else {
+            "esc to go back"
+        },
         Style::default().fg(Color::Gray),
     ));
     if let Some(status) = app.status() {
@@ -320,7 +672,11 @@ fn draw_error(frame: &mut Frame, app: &App) {
 
     lines.push(Line::raw(""));
     lines.push(Line::styled(
-        "esc to go back",
+        if app.job_log().is_some() {
+            "esc to go back, l: ffmpeg log"
+        } else {
+            "esc to go back"
+        },
         Style::default().fg(Color::Gray),
     ));
     render_top_left(frame, lines);
@@ -332,33 +688,21 @@ fn dots_spinner_frame(tick: u64) -> &'static str {
     FRAMES[(tick as usize) % FRAMES.len()]
 }
 
-fn format_bytes(bytes: u64) -> String {
-    const KB: f64 = 1024.0;
-    const MB: f64 = 1024.0 * 1024.0;
-    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
-    let b = bytes as f64;
-
-    if b >= GB {
-        format!("{:.1} GB", b / GB)
-    } else if b >= MB {
-        format!("{:.1} MB", b / MB)
-    } else if b >= KB {
-        format!("{:.1} KB", b / KB)
-    } else {
-        format!("{bytes} B")
-    }
-}
-
 fn preset_description(preset: Preset) -> &'static str {
     match preset {
         Preset::Quality => "best quality, slower processing",
         Preset::Balanced => "good quality, moderate processing",
         Preset::Speed => "lower quality, faster processing",
+        Preset::Remux => "copy streams as-is, re-encode only if needed",
+        Preset::Auto => "pick a preset per file, skip already compact ones",
+        Preset::Gif => "animated gif, no sound",
+        Preset::Webp => "animated webp, smaller than gif, no sound",
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::app::WorkerMsg;
     use super::*;
     use ratatui::Terminal;
     use ratatui::backend::TestBackend;
@@ -381,6 +725,29 @@ mod tests {
         terminal.draw(|f| draw(f, &app)).unwrap();
     }
 
+    #[test]
+    fn draw_browser_does_not_panic() {
+        let mut terminal = Terminal::new(TestBackend::new(40, 4)).unwrap();
+        let mut app = App::new();
+        app.open_browser();
+        terminal.draw(|f| draw(f, &app)).unwrap();
+    }
+
+    #[test]
+    fn draw_log_does_not_panic() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("a.log");
+        std::fs::write(&log, "$ ffmpeg\nError opening output\n").unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(40, 4)).unwrap();
+        let mut app = App::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        app.set_worker(rx, 1);
+        tx.send(WorkerMsg::Log { idx: 0, path: log }).unwrap();
+        app.drain_worker();
+        app.open_log();
+        terminal.draw(|f| draw(f, &app)).unwrap();
+    }
+
     #[test]
     fn centered_rect_stays_in_bounds() {
         let area = Rect::new(0, 0, 80, 24);
@@ -391,6 +758,24 @@ mod tests {
         assert_eq!(r.width, 80);
     }
 
+    #[test]
+    fn overrides_label_lists_only_what_is_set() {
+        let o = FileOverrides {
+            preset: Some(Preset::Speed),
+            trim_secs: Some(60),
+            max_height: Some(720),
+            ..Default::default()
+        };
+        assert_eq!(overrides_label(&o), "speed, first 1m, 720p");
+        assert_eq!(
+            overrides_label(&FileOverrides {
+                trim_secs: Some(15),
+                ..Default::default()
+            }),
+            "first 15s"
+        );
+    }
+
     #[test]
     fn format_bytes_sanity() {
         assert_eq!(format_bytes(0), "0 B");
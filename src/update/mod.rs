@@ -52,6 +52,7 @@ pub fn check_latest_release(repo: &str) -> Result<Option<UpdateInfo>> {
 
     let (owner, name) = split_repo(repo)?;
 
+    log::info!("checking {repo} for a release newer than {current}");
     let releases = self_update::backends::github::ReleaseList::configure()
         .repo_owner(owner)
         .repo_name(name)
@@ -70,9 +71,12 @@ pub fn check_latest_release(repo: &str) -> Result<Option<UpdateInfo>> {
         };
 
         if latest <= current {
+            log::info!("up to date (latest {latest})");
             return Ok(None);
         }
 
+        log::info!("update available: {latest}");
+
         return Ok(Some(UpdateInfo {
             repo: repo.to_string(),
             current,
@@ -81,6 +85,7 @@ pub fn check_latest_release(repo: &str) -> Result<Option<UpdateInfo>> {
         }));
     }
 
+    log::info!("no release for {target}");
     Ok(None)
 }
 
@@ -104,8 +109,9 @@ pub fn apply_update(update: &UpdateInfo, relaunch: bool) -> Result<()> {
         .build()?
         .update()?;
 
+    log::info!("update to {}: {status:?}", update.tag);
     if status.updated() && relaunch {
-        std::process::Command::new(&exe.installed_exe).spawn()?;
+        crate::process::run::command::<&str>(&exe.installed_exe, &[]).spawn()?;
     }
 
     Ok(())